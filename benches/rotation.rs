@@ -96,6 +96,7 @@ fn mk_config(file_size: u64, file_count: u32) -> log4rs::config::Config {
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 struct Stats {
     min: Duration,
     max: Duration,
@@ -135,8 +136,8 @@ impl Stats {
         let mut anomalies = vec![];
         let thresh = self.mean_nanos + ((self.stddev_nanos * 50.0).round() as u128);
         for dur in measurements {
-            if dur.as_nanos() as u128 > thresh {
-                anomalies.push(dur.clone());
+            if dur.as_nanos() > thresh {
+                anomalies.push(*dur);
             }
         }
         anomalies
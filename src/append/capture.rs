@@ -0,0 +1,483 @@
+//! The capture appender.
+//!
+//! The capture appender stores the records it receives in memory, which is
+//! mostly useful to check what code logs in tests. Since the global logger can
+//! only be set once per process, `capture_thread` and `set_thread_logger`
+//! route the records logged on the current thread to a test-scoped logger
+//! instead.
+//!
+//! ```
+//! use log::Level;
+//! use log4rs::{append::capture, assert_logged, assert_not_logged};
+//!
+//! let logs = capture::capture_thread();
+//! log::warn!("disk almost full");
+//!
+//! assert_logged!(logs, Level::Warn, "almost full");
+//! assert_not_logged!(logs, Level::Error, "full");
+//! ```
+//!
+//! Requires the `capture_appender` feature.
+
+use log::{kv, Level, LevelFilter, Log, Metadata, Record};
+use once_cell::sync::OnceCell;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    append::Append,
+    config::{Appender, Config, Root},
+    Logger,
+};
+
+/// A record stored by the `CaptureAppender`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedRecord {
+    level: Level,
+    target: String,
+    message: String,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    key_values: Vec<(String, String)>,
+    mdc: Vec<(String, String)>,
+}
+
+impl CapturedRecord {
+    fn new(record: &Record) -> CapturedRecord {
+        struct Visitor<'a>(&'a mut Vec<(String, String)>);
+
+        impl<'kvs> kv::VisitSource<'kvs> for Visitor<'_> {
+            fn visit_pair(
+                &mut self,
+                key: kv::Key<'kvs>,
+                value: kv::Value<'kvs>,
+            ) -> Result<(), kv::Error> {
+                self.0.push((key.to_string(), value.to_string()));
+                Ok(())
+            }
+        }
+
+        let mut key_values = vec![];
+        let _ = record.key_values().visit(&mut Visitor(&mut key_values));
+
+        let mut mdc = vec![];
+        log_mdc::iter(|k, v| mdc.push((k.to_owned(), v.to_owned())));
+        mdc.sort();
+
+        CapturedRecord {
+            level: record.level(),
+            target: record.target().to_owned(),
+            message: record.args().to_string(),
+            module_path: record.module_path().map(ToOwned::to_owned),
+            file: record.file().map(ToOwned::to_owned),
+            line: record.line(),
+            key_values,
+            mdc,
+        }
+    }
+
+    /// Returns the record's level.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns the record's target.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the record's formatted message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the module path of the logging call, if known.
+    pub fn module_path(&self) -> Option<&str> {
+        self.module_path.as_deref()
+    }
+
+    /// Returns the source file of the logging call, if known.
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// Returns the line of the logging call, if known.
+    pub fn line(&self) -> Option<u32> {
+        self.line
+    }
+
+    /// Returns the record's structured key-value pairs, formatted as strings.
+    pub fn key_values(&self) -> &[(String, String)] {
+        &self.key_values
+    }
+
+    /// Returns the value of a structured key-value pair.
+    pub fn key_value(&self, key: &str) -> Option<&str> {
+        self.key_values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| &**v)
+    }
+
+    /// Returns the MDC entries present when the record was logged, sorted by
+    /// key.
+    pub fn mdc(&self) -> &[(String, String)] {
+        &self.mdc
+    }
+}
+
+impl fmt::Display for CapturedRecord {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{} {} - {}", self.level, self.target, self.message)
+    }
+}
+
+#[derive(Debug)]
+struct Records {
+    records: VecDeque<CapturedRecord>,
+    capacity: Option<usize>,
+}
+
+/// A clonable handle to the records stored by a `CaptureAppender`.
+#[derive(Clone, Debug)]
+pub struct CaptureHandle(Arc<Mutex<Records>>);
+
+impl CaptureHandle {
+    /// Returns a copy of the stored records, oldest first.
+    pub fn records(&self) -> Vec<CapturedRecord> {
+        self.0.lock().unwrap().records.iter().cloned().collect()
+    }
+
+    /// Returns the number of stored records.
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().records.len()
+    }
+
+    /// Determines if no records are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all stored records.
+    pub fn clear(&self) {
+        self.0.lock().unwrap().records.clear();
+    }
+
+    /// Determines if a record with the given level whose message contains
+    /// `pattern` was stored.
+    pub fn contains(&self, level: Level, pattern: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .records
+            .iter()
+            .any(|r| r.level == level && r.message.contains(pattern))
+    }
+}
+
+/// An appender which stores records in memory.
+#[derive(Debug)]
+pub struct CaptureAppender(CaptureHandle);
+
+impl CaptureAppender {
+    /// Creates a new `CaptureAppender` builder.
+    pub fn builder() -> CaptureAppenderBuilder {
+        CaptureAppenderBuilder { capacity: None }
+    }
+
+    /// Returns a handle to the records stored by the appender.
+    pub fn handle(&self) -> CaptureHandle {
+        self.0.clone()
+    }
+}
+
+impl Append for CaptureAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let record = CapturedRecord::new(record);
+        let mut records = (self.0).0.lock().unwrap();
+        records.records.push_back(record);
+        if let Some(capacity) = records.capacity {
+            while records.records.len() > capacity {
+                records.records.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&self) {}
+}
+
+/// A builder for `CaptureAppender`s.
+pub struct CaptureAppenderBuilder {
+    capacity: Option<usize>,
+}
+
+impl CaptureAppenderBuilder {
+    /// Sets the maximum number of records stored. Once it is reached, the
+    /// oldest record is discarded for each new one.
+    ///
+    /// Defaults to unlimited.
+    pub fn capacity(mut self, capacity: usize) -> CaptureAppenderBuilder {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Consumes the `CaptureAppenderBuilder`, producing a `CaptureAppender`.
+    pub fn build(self) -> CaptureAppender {
+        CaptureAppender(CaptureHandle(Arc::new(Mutex::new(Records {
+            records: VecDeque::new(),
+            capacity: self.capacity,
+        }))))
+    }
+}
+
+thread_local! {
+    static THREAD_LOGGERS: RefCell<Vec<Logger>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The global logger installed by `set_thread_logger`, which forwards records
+/// to the current thread's logger.
+struct ThreadLogger;
+
+impl ThreadLogger {
+    fn with<F, T>(f: F) -> Option<T>
+    where
+        F: FnOnce(&Logger) -> T,
+    {
+        // Records logged while the thread is being torn down are dropped.
+        THREAD_LOGGERS
+            .try_with(|loggers| loggers.borrow().last().map(f))
+            .ok()
+            .flatten()
+    }
+}
+
+impl Log for ThreadLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        Self::with(|logger| Log::enabled(logger, metadata)).unwrap_or(false)
+    }
+
+    fn log(&self, record: &Record) {
+        Self::with(|logger| Log::log(logger, record));
+    }
+
+    fn flush(&self) {
+        Self::with(Log::flush);
+    }
+}
+
+/// A guard which routes the current thread's records to a logger until it is
+/// dropped.
+///
+/// The guard can't be sent to other threads, since it belongs to the thread
+/// whose records it routes. Dropping it also ends the routing set up by
+/// guards created after it on the same thread.
+#[derive(Debug)]
+pub struct ThreadLoggerGuard {
+    /// The number of loggers set for the thread before this one.
+    depth: usize,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ThreadLoggerGuard {
+    fn drop(&mut self) {
+        let _ = THREAD_LOGGERS.try_with(|loggers| loggers.borrow_mut().truncate(self.depth));
+    }
+}
+
+/// Routes the records logged on the current thread to `logger` until the
+/// returned guard is dropped.
+///
+/// The first call installs a global logger which forwards each record to the
+/// logger set for the thread it was logged on, and sets the global maximum
+/// log level to `Trace`. Records logged on threads without a logger are
+/// discarded. Calls may be nested, in which case the most recent logger is
+/// used until its guard, or the guard of an enclosing call, is dropped.
+///
+/// # Panics
+///
+/// Panics if a different global logger has already been installed.
+pub fn set_thread_logger(logger: Logger) -> ThreadLoggerGuard {
+    static INSTALLED: OnceCell<bool> = OnceCell::new();
+
+    let installed = *INSTALLED.get_or_init(|| {
+        let installed = log::set_boxed_logger(Box::new(ThreadLogger)).is_ok();
+        if installed {
+            log::set_max_level(LevelFilter::Trace);
+        }
+        installed
+    });
+    assert!(
+        installed,
+        "a global logger other than log4rs's thread logger is already installed"
+    );
+
+    let depth = THREAD_LOGGERS.with(|loggers| {
+        let mut loggers = loggers.borrow_mut();
+        loggers.push(logger);
+        loggers.len() - 1
+    });
+    ThreadLoggerGuard {
+        depth,
+        _not_send: PhantomData,
+    }
+}
+
+/// Captures all records logged on the current thread until the returned
+/// guard is dropped.
+///
+/// See `set_thread_logger` for details.
+pub fn capture_thread() -> CaptureGuard {
+    let appender = CaptureAppender::builder().build();
+    let handle = appender.handle();
+    let config = Config::builder()
+        .appender(Appender::builder().build("capture", Box::new(appender)))
+        .build(
+            Root::builder()
+                .appender("capture")
+                .build(LevelFilter::Trace),
+        )
+        .expect("capture config is valid");
+
+    CaptureGuard {
+        handle,
+        _guard: set_thread_logger(Logger::new(config)),
+    }
+}
+
+/// A guard returned by `capture_thread`.
+///
+/// It dereferences to the `CaptureHandle` holding the captured records.
+#[derive(Debug)]
+pub struct CaptureGuard {
+    handle: CaptureHandle,
+    _guard: ThreadLoggerGuard,
+}
+
+impl CaptureGuard {
+    /// Returns a handle to the captured records, which remains valid after the
+    /// guard is dropped.
+    pub fn handle(&self) -> CaptureHandle {
+        self.handle.clone()
+    }
+}
+
+impl std::ops::Deref for CaptureGuard {
+    type Target = CaptureHandle;
+
+    fn deref(&self) -> &CaptureHandle {
+        &self.handle
+    }
+}
+
+#[doc(hidden)]
+pub fn __format_records(handle: &CaptureHandle) -> String {
+    let records = handle.records();
+    if records.is_empty() {
+        return "no records were captured".to_owned();
+    }
+
+    let mut out = "captured records:".to_owned();
+    for record in records {
+        out.push_str("\n    ");
+        out.push_str(&record.to_string());
+    }
+    out
+}
+
+/// Asserts that a record with the given level whose message contains a
+/// pattern was captured.
+///
+/// The first argument is a `CaptureHandle`, or anything dereferencing to one.
+/// An optional message may be appended, as with `assert!`.
+///
+/// Requires the `capture_appender` feature.
+#[macro_export]
+macro_rules! assert_logged {
+    ($handle:expr, $level:expr, $pattern:expr $(,)?) => {
+        $crate::assert_logged!($handle, $level, $pattern, "")
+    };
+    ($handle:expr, $level:expr, $pattern:expr, $($arg:tt)+) => {{
+        let handle: &$crate::append::capture::CaptureHandle = &$handle;
+        let (level, pattern): (::log::Level, &str) = ($level, &$pattern);
+        if !handle.contains(level, pattern) {
+            panic!(
+                "expected a {} record containing {:?} to be logged: {}\n{}",
+                level,
+                pattern,
+                format_args!($($arg)+),
+                $crate::append::capture::__format_records(handle)
+            );
+        }
+    }};
+}
+
+/// Asserts that no record with the given level whose message contains a
+/// pattern was captured.
+///
+/// Takes the same arguments as `assert_logged!`.
+///
+/// Requires the `capture_appender` feature.
+#[macro_export]
+macro_rules! assert_not_logged {
+    ($handle:expr, $level:expr, $pattern:expr $(,)?) => {
+        $crate::assert_not_logged!($handle, $level, $pattern, "")
+    };
+    ($handle:expr, $level:expr, $pattern:expr, $($arg:tt)+) => {{
+        let handle: &$crate::append::capture::CaptureHandle = &$handle;
+        let (level, pattern): (::log::Level, &str) = ($level, &$pattern);
+        if handle.contains(level, pattern) {
+            panic!(
+                "expected no {} record containing {:?} to be logged: {}\n{}",
+                level,
+                pattern,
+                format_args!($($arg)+),
+                $crate::append::capture::__format_records(handle)
+            );
+        }
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn captures_owned_records() {
+        let appender = CaptureAppender::builder().capacity(2).build();
+        let handle = appender.handle();
+
+        log_mdc::insert("request", "42");
+        for message in ["first", "second", "third"] {
+            appender
+                .append(
+                    &Record::builder()
+                        .level(Level::Info)
+                        .target("app::db")
+                        .line(Some(10))
+                        .args(format_args!("{}", message))
+                        .build(),
+                )
+                .unwrap();
+        }
+        log_mdc::remove("request");
+
+        let records = handle.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].message(), "second");
+        assert_eq!(records[1].target(), "app::db");
+        assert_eq!(records[1].line(), Some(10));
+        assert_eq!(records[1].mdc(), [("request".to_owned(), "42".to_owned())]);
+        assert!(handle.contains(Level::Info, "thi"));
+        assert!(!handle.contains(Level::Warn, "thi"));
+
+        handle.clear();
+        assert!(handle.is_empty());
+    }
+}
@@ -3,7 +3,7 @@
 //! Requires the `console_appender` feature.
 
 use derivative::Derivative;
-use log::Record;
+use log::{Level, Record};
 use std::{
     fmt,
     io::{self, Write},
@@ -19,7 +19,7 @@ use crate::{
         self,
         pattern::PatternEncoder,
         writer::{
-            console::{ConsoleWriter, ConsoleWriterLock},
+            console::{ColorMode, ConsoleWriter, ConsoleWriterLock},
             simple::SimpleWriter,
         },
         Encode, Style,
@@ -35,6 +35,7 @@ pub struct ConsoleAppenderConfig {
     target: Option<ConfigTarget>,
     encoder: Option<EncoderConfig>,
     tty_only: Option<bool>,
+    color: Option<ConfigColor>,
 }
 
 #[cfg(feature = "config_parsing")]
@@ -44,6 +45,19 @@ enum ConfigTarget {
     Stdout,
     #[serde(rename = "stderr")]
     Stderr,
+    #[serde(rename = "split")]
+    Split,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Debug, serde::Deserialize)]
+enum ConfigColor {
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "always")]
+    Always,
+    #[serde(rename = "never")]
+    Never,
 }
 
 enum Writer {
@@ -52,19 +66,24 @@ enum Writer {
 }
 
 impl Writer {
-    fn lock(&self) -> WriterLock {
-        match *self {
-            Writer::Tty(ref w) => WriterLock::Tty(w.lock()),
-            Writer::Raw(ref w) => WriterLock::Raw(SimpleWriter(w.lock())),
+    fn stdout(color: ColorMode) -> Writer {
+        match ConsoleWriter::stdout_with_color(color) {
+            Some(writer) => Writer::Tty(writer),
+            None => Writer::Raw(StdWriter::stdout()),
         }
     }
 
-    fn is_tty(&self) -> bool {
-        // 1.40 compat
-        #[allow(clippy::match_like_matches_macro)]
-        match self {
-            Self::Tty(_) => true,
-            _ => false,
+    fn stderr(color: ColorMode) -> Writer {
+        match ConsoleWriter::stderr_with_color(color) {
+            Some(writer) => Writer::Tty(writer),
+            None => Writer::Raw(StdWriter::stderr()),
+        }
+    }
+
+    fn lock(&self) -> WriterLock<'_> {
+        match *self {
+            Writer::Tty(ref w) => WriterLock::Tty(w.lock()),
+            Writer::Raw(ref w) => WriterLock::Raw(SimpleWriter(w.lock())),
         }
     }
 }
@@ -116,12 +135,16 @@ impl<'a> encode::Write for WriterLock<'a> {
 /// An appender which logs to standard out.
 ///
 /// It supports output styling if standard out is a console buffer on Windows
-/// or is a TTY on Unix.
+/// or is a TTY on Unix. It can also log to standard error, or split records
+/// between the two by level.
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct ConsoleAppender {
     #[derivative(Debug = "ignore")]
     writer: Writer,
+    /// Standard error, if warnings and errors are split from other records.
+    #[derivative(Debug = "ignore")]
+    err_writer: Option<Writer>,
     encoder: Box<dyn Encode>,
     do_write: bool,
 }
@@ -129,7 +152,7 @@ pub struct ConsoleAppender {
 impl Append for ConsoleAppender {
     fn append(&self, record: &Record) -> anyhow::Result<()> {
         if self.do_write {
-            let mut writer = self.writer.lock();
+            let mut writer = self.writer_for(record.level()).lock();
             self.encoder.encode(&mut writer, record)?;
             writer.flush()?;
         }
@@ -140,12 +163,21 @@ impl Append for ConsoleAppender {
 }
 
 impl ConsoleAppender {
+    /// Returns the stream records at `level` are written to.
+    fn writer_for(&self, level: Level) -> &Writer {
+        match &self.err_writer {
+            Some(err_writer) if level <= Level::Warn => err_writer,
+            _ => &self.writer,
+        }
+    }
+
     /// Creates a new `ConsoleAppender` builder.
     pub fn builder() -> ConsoleAppenderBuilder {
         ConsoleAppenderBuilder {
             encoder: None,
             target: Target::Stdout,
             tty_only: false,
+            color: ColorMode::Auto,
         }
     }
 }
@@ -155,6 +187,7 @@ pub struct ConsoleAppenderBuilder {
     encoder: Option<Box<dyn Encode>>,
     target: Target,
     tty_only: bool,
+    color: ColorMode,
 }
 
 impl ConsoleAppenderBuilder {
@@ -174,29 +207,52 @@ impl ConsoleAppenderBuilder {
 
     /// Sets the output to log only when it's a TTY.
     ///
+    /// With `Target::Split`, the appender logs if either stream is a TTY.
+    ///
     /// Defaults to `false`.
     pub fn tty_only(mut self, tty_only: bool) -> ConsoleAppenderBuilder {
         self.tty_only = tty_only;
         self
     }
 
+    /// Sets when the output is styled.
+    ///
+    /// With `ColorMode::Auto`, output is styled if it is a TTY, unless the
+    /// `NO_COLOR` environment variable is set to disable styling, or
+    /// `CLICOLOR_FORCE` to force it. `ColorMode::Always` and
+    /// `ColorMode::Never` override the environment.
+    ///
+    /// Defaults to `ColorMode::Auto`.
+    pub fn color(mut self, color: ColorMode) -> ConsoleAppenderBuilder {
+        self.color = color;
+        self
+    }
+
     /// Consumes the `ConsoleAppenderBuilder`, producing a `ConsoleAppender`.
     pub fn build(self) -> ConsoleAppender {
-        let writer = match self.target {
-            Target::Stderr => match ConsoleWriter::stderr() {
-                Some(writer) => Writer::Tty(writer),
-                None => Writer::Raw(StdWriter::stderr()),
-            },
-            Target::Stdout => match ConsoleWriter::stdout() {
-                Some(writer) => Writer::Tty(writer),
-                None => Writer::Raw(StdWriter::stdout()),
-            },
+        let (writer, err_writer, is_tty) = match self.target {
+            Target::Stdout => (
+                Writer::stdout(self.color),
+                None,
+                ConsoleWriter::is_stdout_tty(),
+            ),
+            Target::Stderr => (
+                Writer::stderr(self.color),
+                None,
+                ConsoleWriter::is_stderr_tty(),
+            ),
+            Target::Split => (
+                Writer::stdout(self.color),
+                Some(Writer::stderr(self.color)),
+                ConsoleWriter::is_stdout_tty() || ConsoleWriter::is_stderr_tty(),
+            ),
         };
 
-        let do_write = writer.is_tty() || !self.tty_only;
+        let do_write = is_tty || !self.tty_only;
 
         ConsoleAppender {
             writer,
+            err_writer,
             encoder: self
                 .encoder
                 .unwrap_or_else(|| Box::<PatternEncoder>::default()),
@@ -212,6 +268,9 @@ pub enum Target {
     Stdout,
     /// Standard error.
     Stderr,
+    /// Standard error for warnings and errors, and standard out for other
+    /// records.
+    Split,
 }
 
 /// A deserializer for the `ConsoleAppender`.
@@ -221,12 +280,20 @@ pub enum Target {
 /// ```yaml
 /// kind: console
 ///
-/// # The output to write to. One of `stdout` or `stderr`. Defaults to `stdout`.
+/// # The output to write to. One of `stdout`, `stderr`, or `split` to write
+/// # warnings and errors to `stderr` and other records to `stdout`. Defaults to
+/// # `stdout`.
 /// target: stdout
 ///
 /// # Set this boolean when the console appender must only write when the target is a TTY.
 /// tty_only: false
 ///
+/// # When to style the output. `auto` styles it if the output is a TTY, unless
+/// # the `NO_COLOR` environment variable disables styling or `CLICOLOR_FORCE`
+/// # forces it. `always` and `never` override the environment. Defaults to
+/// # `auto`.
+/// color: auto
+///
 /// # The encoder to use to format output. Defaults to `kind: pattern`.
 /// encoder:
 ///   kind: pattern
@@ -251,9 +318,18 @@ impl Deserialize for ConsoleAppenderDeserializer {
             let target = match target {
                 ConfigTarget::Stdout => Target::Stdout,
                 ConfigTarget::Stderr => Target::Stderr,
+                ConfigTarget::Split => Target::Split,
             };
             appender = appender.target(target);
         }
+        if let Some(color) = config.color {
+            let color = match color {
+                ConfigColor::Auto => ColorMode::Auto,
+                ConfigColor::Always => ColorMode::Always,
+                ConfigColor::Never => ColorMode::Never,
+            };
+            appender = appender.color(color);
+        }
         if let Some(tty_only) = config.tty_only {
             appender = appender.tty_only(tty_only);
         }
@@ -263,3 +339,50 @@ impl Deserialize for ConsoleAppenderDeserializer {
         Ok(Box::new(appender.build()))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split() {
+        let appender = ConsoleAppender::builder()
+            .target(Target::Split)
+            .color(ColorMode::Never)
+            .encoder(Box::new(PatternEncoder::new("")))
+            .build();
+        assert!(matches!(appender.writer, Writer::Raw(_)));
+        assert!(matches!(appender.err_writer, Some(Writer::Raw(_))));
+
+        let err_writer = appender.err_writer.as_ref().unwrap();
+        for level in [Level::Error, Level::Warn] {
+            assert!(std::ptr::eq(appender.writer_for(level), err_writer));
+        }
+        for level in [Level::Info, Level::Debug, Level::Trace] {
+            assert!(std::ptr::eq(appender.writer_for(level), &appender.writer));
+        }
+
+        for level in [Level::Error, Level::Info] {
+            appender
+                .append(
+                    &Record::builder()
+                        .level(level)
+                        .args(format_args!(""))
+                        .build(),
+                )
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn single_target() {
+        let appender = ConsoleAppender::builder()
+            .target(Target::Stderr)
+            .color(ColorMode::Never)
+            .build();
+        assert!(appender.err_writer.is_none());
+        for level in [Level::Error, Level::Info] {
+            assert!(std::ptr::eq(appender.writer_for(level), &appender.writer));
+        }
+    }
+}
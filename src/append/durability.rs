@@ -0,0 +1,433 @@
+//! Buffering, flushing and syncing of log files.
+//!
+//! The file and rolling file appenders buffer encoded records in memory, and
+//! by default flush the buffer to the file after every record. For high
+//! volumes of logs, flushing less often avoids a system call per record. For
+//! logs which must survive a crash of the host, syncing the file forces the
+//! data to the disk.
+//!
+//! Requires the `file_appender` or `rolling_file_appender` feature.
+
+use log::Level;
+#[cfg(feature = "config_parsing")]
+use serde::de;
+#[cfg(feature = "config_parsing")]
+use std::{convert::TryFrom, fmt};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    sync::Weak,
+    thread,
+    time::Duration,
+};
+
+#[cfg(test)]
+use mock_instant::Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+use parking_lot::Mutex;
+
+use crate::error::{BackgroundReporter, ErrorEvent, ErrorKind};
+
+/// The default size of an appender's buffer, in bytes.
+const DEFAULT_BUFFER_SIZE: usize = 1024;
+
+/// How often the background flusher drains buffers which are flushed by
+/// level, if no interval is configured.
+const IDLE_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When an appender flushes its buffer to the file.
+///
+/// Regardless of the policy, the buffer is flushed when it is full, when the
+/// appender is flushed or dropped, and before the log file is rolled.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum FlushPolicy {
+    /// Flush after every record.
+    #[default]
+    Always,
+    /// Flush after records at or above a level, e.g. `Level::Warn` flushes
+    /// after warnings and errors.
+    ///
+    /// Once a second, a background thread flushes buffers which haven't been
+    /// flushed for a second, so lower level records reach the file within
+    /// about two seconds.
+    Level(Level),
+    /// Flush at most once per interval.
+    ///
+    /// Once per interval, a background thread flushes the buffer if it hasn't
+    /// been flushed for the interval, so records reach the file within about
+    /// twice the interval even if no further records are written.
+    Interval(Duration),
+}
+
+impl FlushPolicy {
+    fn flusher_interval(self) -> Option<Duration> {
+        match self {
+            FlushPolicy::Always => None,
+            FlushPolicy::Level(_) => Some(IDLE_FLUSH_INTERVAL),
+            FlushPolicy::Interval(interval) => Some(interval),
+        }
+    }
+}
+
+/// When an appender syncs the file's data to the disk.
+///
+/// Syncing flushes the buffer first, and uses `fdatasync` or its equivalent.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum SyncPolicy {
+    /// Never sync, leaving it to the operating system.
+    #[default]
+    None,
+    /// Sync after every `n` records.
+    Records(u32),
+    /// Sync after records at or above a level, e.g. `Level::Error`.
+    Level(Level),
+}
+
+/// The buffering configuration of an appender.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub(crate) struct Durability {
+    pub(crate) buffer_size: usize,
+    pub(crate) flush: FlushPolicy,
+    pub(crate) sync: SyncPolicy,
+}
+
+impl Default for Durability {
+    fn default() -> Durability {
+        Durability {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            flush: FlushPolicy::default(),
+            sync: SyncPolicy::default(),
+        }
+    }
+}
+
+/// The state of a buffered log file since it was last flushed and synced.
+#[derive(Debug)]
+pub(crate) struct Pending {
+    flushed: Instant,
+    unsynced: u32,
+    /// Reports errors of the background flusher to the logger which last
+    /// wrote to the file.
+    reporter: BackgroundReporter,
+}
+
+impl Default for Pending {
+    fn default() -> Pending {
+        Pending {
+            flushed: Instant::now(),
+            unsynced: 0,
+            reporter: BackgroundReporter::default(),
+        }
+    }
+}
+
+impl Pending {
+    /// Flushes `file` on behalf of the background flusher if it hasn't been
+    /// flushed for `interval`, reporting a failure to the error handler of the
+    /// logger which last wrote to it.
+    pub(crate) fn flush_idle(&mut self, file: &mut BufWriter<File>, interval: Duration) {
+        if self.flushed.elapsed() < interval {
+            return;
+        }
+        match file.flush() {
+            Ok(()) => self.flushed = Instant::now(),
+            Err(e) => self
+                .reporter
+                .report(&ErrorEvent::new(ErrorKind::Append, &e.into())),
+        }
+    }
+}
+
+impl Durability {
+    /// Returns a buffered writer to `file` with the configured capacity.
+    pub(crate) fn buffer(&self, file: File) -> BufWriter<File> {
+        BufWriter::with_capacity(self.buffer_size, file)
+    }
+
+    /// Flushes and syncs `file` as required after a record at `level` was
+    /// written to it.
+    pub(crate) fn commit(
+        &self,
+        file: &mut BufWriter<File>,
+        pending: &mut Pending,
+        level: Level,
+    ) -> io::Result<()> {
+        pending.reporter = BackgroundReporter::current();
+        let sync = match self.sync {
+            SyncPolicy::None => false,
+            SyncPolicy::Records(n) => {
+                pending.unsynced += 1;
+                pending.unsynced >= n
+            }
+            SyncPolicy::Level(min) => level <= min,
+        };
+        let flush = sync
+            || match self.flush {
+                FlushPolicy::Always => true,
+                FlushPolicy::Level(min) => level <= min,
+                FlushPolicy::Interval(interval) => pending.flushed.elapsed() >= interval,
+            };
+
+        if flush {
+            file.flush()?;
+            pending.flushed = Instant::now();
+        }
+        if sync {
+            file.get_ref().sync_data()?;
+            pending.unsynced = 0;
+        }
+        Ok(())
+    }
+
+    /// Spawns a thread which calls `flush` with the buffer of `state` and the
+    /// flush interval once per interval, if the flush policy can leave records
+    /// in the buffer.
+    ///
+    /// The thread exits once `state` is dropped.
+    pub(crate) fn spawn_flusher<T, F>(&self, state: Weak<Mutex<T>>, flush: F) -> io::Result<()>
+    where
+        T: Send + 'static,
+        F: Fn(&mut T, Duration) + Send + 'static,
+    {
+        let interval = match self.flush.flusher_interval() {
+            Some(interval) => interval,
+            None => return Ok(()),
+        };
+
+        thread::Builder::new()
+            .name("log4rs flusher".to_owned())
+            .spawn(move || loop {
+                thread::sleep(interval);
+                let state = match state.upgrade() {
+                    Some(state) => state,
+                    None => return,
+                };
+                flush(&mut state.lock(), interval);
+            })?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for FlushPolicy {
+    fn deserialize<D>(d: D) -> Result<FlushPolicy, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let s = String::deserialize(d)?;
+        if s == "always" {
+            return Ok(FlushPolicy::Always);
+        }
+        if let Ok(level) = s.parse() {
+            return Ok(FlushPolicy::Level(level));
+        }
+        humantime::parse_duration(&s)
+            .map(FlushPolicy::Interval)
+            .map_err(|_| {
+                de::Error::custom(format!(
+                    "invalid flush `{}`: expected `always`, a level or a duration",
+                    s
+                ))
+            })
+    }
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for SyncPolicy {
+    fn deserialize<D>(d: D) -> Result<SyncPolicy, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct V;
+
+        impl<'de2> de::Visitor<'de2> for V {
+            type Value = SyncPolicy;
+
+            fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.write_str("`none`, a number of records or a level")
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<SyncPolicy, E>
+            where
+                E: de::Error,
+            {
+                match u32::try_from(v) {
+                    Ok(n) if n > 0 => Ok(SyncPolicy::Records(n)),
+                    _ => Err(E::invalid_value(de::Unexpected::Unsigned(v), &self)),
+                }
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<SyncPolicy, E>
+            where
+                E: de::Error,
+            {
+                match u64::try_from(v) {
+                    Ok(v) => self.visit_u64(v),
+                    Err(_) => Err(E::invalid_value(de::Unexpected::Signed(v), &self)),
+                }
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<SyncPolicy, E>
+            where
+                E: de::Error,
+            {
+                if v == "none" {
+                    return Ok(SyncPolicy::None);
+                }
+                v.parse()
+                    .map(SyncPolicy::Level)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+        }
+
+        d.deserialize_any(V)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf};
+
+    use mock_instant::MockClock;
+
+    use super::*;
+
+    struct Log {
+        _dir: tempfile::TempDir,
+        path: PathBuf,
+        file: BufWriter<File>,
+        pending: Pending,
+        durability: Durability,
+    }
+
+    impl Log {
+        fn new(flush: FlushPolicy, sync: SyncPolicy) -> Log {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("log");
+            let durability = Durability {
+                flush,
+                sync,
+                ..Durability::default()
+            };
+            Log {
+                file: durability.buffer(File::create(&path).unwrap()),
+                _dir: dir,
+                path,
+                pending: Pending::default(),
+                durability,
+            }
+        }
+
+        /// Writes a record, returning the length of the file afterwards.
+        fn write(&mut self, level: Level) -> u64 {
+            self.file.write_all(b"x").unwrap();
+            self.durability
+                .commit(&mut self.file, &mut self.pending, level)
+                .unwrap();
+            fs::metadata(&self.path).unwrap().len()
+        }
+    }
+
+    #[test]
+    fn flush_level() {
+        let mut log = Log::new(FlushPolicy::Level(Level::Warn), SyncPolicy::None);
+        assert_eq!(log.write(Level::Info), 0);
+        assert_eq!(log.write(Level::Debug), 0);
+        assert_eq!(log.write(Level::Error), 3);
+        assert_eq!(log.write(Level::Info), 3);
+    }
+
+    #[test]
+    fn flush_interval() {
+        let mut log = Log::new(
+            FlushPolicy::Interval(Duration::from_secs(5)),
+            SyncPolicy::None,
+        );
+        assert_eq!(log.write(Level::Error), 0);
+        MockClock::advance(Duration::from_secs(5));
+        assert_eq!(log.write(Level::Info), 2);
+        assert_eq!(log.write(Level::Info), 2);
+    }
+
+    #[test]
+    fn sync() {
+        let mut log = Log::new(FlushPolicy::Level(Level::Error), SyncPolicy::Records(2));
+        assert_eq!(log.write(Level::Info), 0);
+        assert_eq!(log.write(Level::Info), 2);
+        assert_eq!(log.write(Level::Info), 2);
+
+        let mut log = Log::new(
+            FlushPolicy::Level(Level::Error),
+            SyncPolicy::Level(Level::Warn),
+        );
+        assert_eq!(log.write(Level::Info), 0);
+        assert_eq!(log.write(Level::Warn), 2);
+    }
+
+    #[test]
+    fn flush_idle() {
+        let interval = Duration::from_secs(5);
+        let mut log = Log::new(FlushPolicy::Interval(interval), SyncPolicy::None);
+        assert_eq!(log.write(Level::Info), 0);
+
+        // Nothing is flushed within an interval of the last flush.
+        MockClock::advance(Duration::from_secs(4));
+        log.pending.flush_idle(&mut log.file, interval);
+        assert_eq!(fs::metadata(&log.path).unwrap().len(), 0);
+
+        MockClock::advance(Duration::from_secs(1));
+        log.pending.flush_idle(&mut log.file, interval);
+        assert_eq!(fs::metadata(&log.path).unwrap().len(), 1);
+
+        // The background flush counts towards the interval of records.
+        assert_eq!(log.write(Level::Info), 1);
+        MockClock::advance(Duration::from_secs(5));
+        assert_eq!(log.write(Level::Info), 3);
+    }
+
+    #[test]
+    fn flusher() {
+        let mut log = Log::new(
+            FlushPolicy::Interval(Duration::from_millis(10)),
+            SyncPolicy::None,
+        );
+        log.file.write_all(b"x").unwrap();
+        let state = std::sync::Arc::new(Mutex::new((log.file, Pending::default())));
+        log.durability
+            .spawn_flusher(std::sync::Arc::downgrade(&state), |state, interval| {
+                // The mock clock is per thread, and stands still on this one.
+                MockClock::advance(interval);
+                state.1.flush_idle(&mut state.0, interval)
+            })
+            .unwrap();
+
+        for _ in 0..100 {
+            if fs::metadata(&log.path).unwrap().len() > 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(fs::read(&log.path).unwrap(), b"x");
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn deserialize() {
+        let flush = |s| serde_yaml::from_str::<FlushPolicy>(s);
+        assert_eq!(flush("always").unwrap(), FlushPolicy::Always);
+        assert_eq!(flush("warn").unwrap(), FlushPolicy::Level(Level::Warn));
+        assert_eq!(
+            flush("5 seconds").unwrap(),
+            FlushPolicy::Interval(Duration::from_secs(5))
+        );
+        assert!(flush("sometimes").is_err());
+
+        let sync = |s| serde_yaml::from_str::<SyncPolicy>(s);
+        assert_eq!(sync("none").unwrap(), SyncPolicy::None);
+        assert_eq!(sync("100").unwrap(), SyncPolicy::Records(100));
+        assert_eq!(sync("error").unwrap(), SyncPolicy::Level(Level::Error));
+        assert!(sync("0").is_err());
+    }
+}
@@ -0,0 +1,546 @@
+//! The failover appender.
+//!
+//! Requires the `failover_appender` feature.
+
+use anyhow::anyhow;
+use log::Record;
+#[cfg(feature = "config_parsing")]
+use serde::de;
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::{
+    append::{self, Append},
+    error::{report_background, ErrorEvent, ErrorKind},
+};
+
+/// The failover appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FailoverAppenderConfig {
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    retry_after: Option<Duration>,
+    appenders: Vec<FailoverEntry>,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+struct FailoverEntry {
+    name: String,
+    kind: String,
+    config: Value,
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for FailoverEntry {
+    fn deserialize<D>(d: D) -> Result<FailoverEntry, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut map = BTreeMap::<Value, Value>::deserialize(d)?;
+
+        let name = match map.remove(&Value::String("name".to_owned())) {
+            Some(name) => name.deserialize_into().map_err(|e| e.to_error())?,
+            None => return Err(de::Error::missing_field("name")),
+        };
+
+        let kind = match map.remove(&Value::String("kind".to_owned())) {
+            Some(kind) => kind.deserialize_into().map_err(|e| e.to_error())?,
+            None => return Err(de::Error::missing_field("kind")),
+        };
+
+        Ok(FailoverEntry {
+            name,
+            kind,
+            config: Value::Map(map),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Backend {
+    name: String,
+    appender: Box<dyn Append>,
+    /// Set while the appender is cooling off after a failure.
+    failed_until: Mutex<Option<Instant>>,
+}
+
+impl Backend {
+    fn is_cooling_off(&self, now: Instant) -> bool {
+        match *self.failed_until.lock().unwrap() {
+            Some(until) => now < until,
+            None => false,
+        }
+    }
+}
+
+/// An appender which forwards records to the first working appender of an
+/// ordered list.
+///
+/// Records are sent to the first appender in the list. If it returns an
+/// error, the record is retried on the next one, and so on until an appender
+/// accepts it. An appender which failed is skipped for the configured
+/// cool-off period before it is tried again, unless every other appender
+/// fails as well.
+///
+/// Rolling the appender rolls each of its appenders which supports rolling,
+/// and reopening it reopens all of them.
+///
+/// When an appender fails and the record is handed to the next one, and when
+/// a failed appender recovers, a `Failover` event is passed to the logger's
+/// error handler. As the record was still written, `append` succeeds, and the
+/// appender's error count is unaffected. Only if every appender fails is an
+/// error returned.
+#[derive(Debug)]
+pub struct FailoverAppender {
+    backends: Vec<Backend>,
+    retry_after: Duration,
+}
+
+impl FailoverAppender {
+    /// Creates a new `FailoverAppender` builder.
+    pub fn builder() -> FailoverAppenderBuilder {
+        FailoverAppenderBuilder {
+            backends: vec![],
+            retry_after: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Append for FailoverAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let now = Instant::now();
+        let mut failures = vec![];
+
+        // Appenders which are cooling off are only tried as a last resort.
+        let (ready, cooling_off): (Vec<_>, Vec<_>) = self
+            .backends
+            .iter()
+            .partition(|backend| !backend.is_cooling_off(now));
+
+        for backend in ready.into_iter().chain(cooling_off) {
+            match backend.appender.append(record) {
+                Ok(()) => {
+                    if backend.failed_until.lock().unwrap().take().is_some() {
+                        let e = anyhow!("appender `{}` recovered", backend.name);
+                        report_background(&ErrorEvent::new(ErrorKind::Failover, &e));
+                    }
+                    if !failures.is_empty() {
+                        let e = anyhow!(
+                            "{}; failed over to appender `{}`",
+                            failures.join("; "),
+                            backend.name
+                        );
+                        report_background(&ErrorEvent::new(ErrorKind::Failover, &e));
+                    }
+                    return Ok(());
+                }
+                Err(e) => {
+                    *backend.failed_until.lock().unwrap() = Some(now + self.retry_after);
+                    failures.push(format!("appender `{}` failed: {}", backend.name, e));
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "{}; no failover appender left, record dropped",
+            failures.join("; ")
+        ))
+    }
+
+    fn flush(&self) {
+        for backend in &self.backends {
+            backend.appender.flush();
+        }
+    }
+
+    fn roll(&self) -> anyhow::Result<()> {
+        append::roll_all(
+            self.backends
+                .iter()
+                .map(|backend| (&*backend.name, &*backend.appender)),
+        )
+    }
+
+    fn reopen(&self) -> anyhow::Result<()> {
+        append::reopen_all(
+            self.backends
+                .iter()
+                .map(|backend| (&*backend.name, &*backend.appender)),
+        )
+    }
+}
+
+/// A builder for `FailoverAppender`s.
+pub struct FailoverAppenderBuilder {
+    backends: Vec<Backend>,
+    retry_after: Duration,
+}
+
+impl FailoverAppenderBuilder {
+    /// Adds an appender to the end of the failover list.
+    ///
+    /// The name is used to identify the appender in error messages.
+    pub fn appender<N>(mut self, name: N, appender: Box<dyn Append>) -> FailoverAppenderBuilder
+    where
+        N: Into<String>,
+    {
+        self.backends.push(Backend {
+            name: name.into(),
+            appender,
+            failed_until: Mutex::new(None),
+        });
+        self
+    }
+
+    /// Sets how long an appender which failed is skipped before it is tried
+    /// again.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn retry_after(mut self, retry_after: Duration) -> FailoverAppenderBuilder {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Consumes the `FailoverAppenderBuilder`, producing a `FailoverAppender`.
+    pub fn build(self) -> FailoverAppender {
+        FailoverAppender {
+            backends: self.backends,
+            retry_after: self.retry_after,
+        }
+    }
+}
+
+/// A deserializer for the `FailoverAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: failover
+///
+/// # How long an appender which failed is skipped before it is tried again.
+/// # Defaults to 30 seconds.
+/// retry_after: 30 seconds
+///
+/// # The appenders to try, in order. Each entry requires a `name`, used to
+/// # identify it in error messages, and a `kind`. The remainder of the entry
+/// # is passed along to the appender's deserializer. Required.
+/// appenders:
+///   - name: audit_file
+///     kind: file
+///     path: log/audit.log
+///   - name: audit_console
+///     kind: console
+///     target: stderr
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct FailoverAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for FailoverAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = FailoverAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: FailoverAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        if config.appenders.is_empty() {
+            return Err(anyhow!("failover appender requires at least one appender"));
+        }
+
+        let mut builder = FailoverAppender::builder();
+        if let Some(retry_after) = config.retry_after {
+            builder = builder.retry_after(retry_after);
+        }
+        for entry in config.appenders {
+            let appender = deserializers.deserialize(&entry.kind, entry.config)?;
+            builder = builder.appender(entry.name, appender);
+        }
+        Ok(Box::new(builder.build()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::error::BackgroundReporter;
+
+    #[derive(Debug, Default)]
+    struct Flaky {
+        failing: AtomicBool,
+        attempts: AtomicUsize,
+        written: AtomicUsize,
+    }
+
+    #[derive(Debug)]
+    struct FlakyAppender(Arc<Flaky>);
+
+    impl Append for FlakyAppender {
+        fn append(&self, _: &Record) -> anyhow::Result<()> {
+            self.0.attempts.fetch_add(1, Ordering::SeqCst);
+            if self.0.failing.load(Ordering::SeqCst) {
+                return Err(anyhow!("no space left on device"));
+            }
+            self.0.written.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn failover(retry_after: Duration) -> (FailoverAppender, Arc<Flaky>, Arc<Flaky>) {
+        let primary = Arc::new(Flaky::default());
+        let secondary = Arc::new(Flaky::default());
+        let appender = FailoverAppender::builder()
+            .retry_after(retry_after)
+            .appender("primary", Box::new(FlakyAppender(primary.clone())))
+            .appender("secondary", Box::new(FlakyAppender(secondary.clone())))
+            .build();
+        (appender, primary, secondary)
+    }
+
+    type Events = Arc<Mutex<Vec<(ErrorKind, String)>>>;
+
+    /// Returns a reporter for the appender named `audit` which collects
+    /// events.
+    fn reporter() -> (BackgroundReporter, Events) {
+        let events = Arc::new(Mutex::new(vec![]));
+        let sink = events.clone();
+        let handler = move |e: &ErrorEvent| sink.lock().unwrap().push((e.kind(), e.to_string()));
+        (
+            BackgroundReporter::new(Arc::new(handler), "audit".into()),
+            events,
+        )
+    }
+
+    #[test]
+    fn primary_healthy() {
+        let (appender, primary, secondary) = failover(Duration::from_secs(60));
+        let (reporter, events) = reporter();
+        let record = Record::builder().build();
+
+        reporter.enter(|| appender.append(&record)).unwrap();
+        reporter.enter(|| appender.append(&record)).unwrap();
+
+        assert_eq!(primary.written.load(Ordering::SeqCst), 2);
+        assert_eq!(secondary.attempts.load(Ordering::SeqCst), 0);
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn fails_over_and_cools_off() {
+        let (appender, primary, secondary) = failover(Duration::from_secs(60));
+        let (reporter, events) = reporter();
+        let record = Record::builder().build();
+
+        primary.failing.store(true, Ordering::SeqCst);
+        reporter.enter(|| appender.append(&record)).unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            [(
+                ErrorKind::Failover,
+                "appender `audit`: appender `primary` failed: no space left on device; \
+                 failed over to appender `secondary`"
+                    .to_owned()
+            )]
+        );
+        assert_eq!(secondary.written.load(Ordering::SeqCst), 1);
+
+        // The primary is cooling off, so it is not retried and nothing is reported.
+        reporter.enter(|| appender.append(&record)).unwrap();
+        assert_eq!(primary.attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(secondary.written.load(Ordering::SeqCst), 2);
+        assert_eq!(events.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn primary_recovers() {
+        let (appender, primary, secondary) = failover(Duration::from_secs(0));
+        let (reporter, events) = reporter();
+        let record = Record::builder().build();
+
+        primary.failing.store(true, Ordering::SeqCst);
+        reporter.enter(|| appender.append(&record)).unwrap();
+
+        primary.failing.store(false, Ordering::SeqCst);
+        reporter.enter(|| appender.append(&record)).unwrap();
+        reporter.enter(|| appender.append(&record)).unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[1],
+            (
+                ErrorKind::Failover,
+                "appender `audit`: appender `primary` recovered".to_owned()
+            )
+        );
+        assert_eq!(primary.written.load(Ordering::SeqCst), 2);
+        assert_eq!(secondary.written.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn all_failing() {
+        let (appender, primary, secondary) = failover(Duration::from_secs(60));
+        let (reporter, events) = reporter();
+        let record = Record::builder().build();
+
+        primary.failing.store(true, Ordering::SeqCst);
+        secondary.failing.store(true, Ordering::SeqCst);
+        let err = reporter
+            .enter(|| appender.append(&record))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("no failover appender left"));
+        assert!(events.lock().unwrap().is_empty());
+
+        // Appenders which are cooling off are still tried as a last resort.
+        secondary.failing.store(false, Ordering::SeqCst);
+        reporter.enter(|| appender.append(&record)).unwrap();
+        assert_eq!(events.lock().unwrap().len(), 2);
+        assert_eq!(primary.attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(secondary.written.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn failover_is_not_an_append_error() {
+        use log::{LevelFilter, Log};
+
+        use crate::config::{Appender, Config, Root};
+
+        let (appender, primary, secondary) = failover(Duration::from_secs(60));
+        let config = Config::builder()
+            .appender(Appender::builder().build("audit", Box::new(appender)))
+            .build(Root::builder().appender("audit").build(LevelFilter::Info))
+            .unwrap();
+        let events = Arc::new(Mutex::new(vec![]));
+        let sink = events.clone();
+        let logger = crate::Logger::new_with_error_handler(config, move |e: &ErrorEvent| {
+            sink.lock().unwrap().push(e.kind())
+        });
+
+        primary.failing.store(true, Ordering::SeqCst);
+        logger.log(&Record::builder().level(log::Level::Info).build());
+
+        assert_eq!(secondary.written.load(Ordering::SeqCst), 1);
+        assert_eq!(*events.lock().unwrap(), [ErrorKind::Failover]);
+        assert_eq!(
+            logger.handle().stats().appender("audit").unwrap().errors(),
+            0
+        );
+    }
+
+    /// An appender which counts rolls and reopens, and may fail them.
+    #[derive(Debug, Default)]
+    struct Rollable {
+        calls: Arc<AtomicUsize>,
+        failing: bool,
+    }
+
+    impl Append for Rollable {
+        fn append(&self, _: &Record) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn flush(&self) {}
+
+        fn roll(&self) -> anyhow::Result<()> {
+            self.reopen()
+        }
+
+        fn reopen(&self) -> anyhow::Result<()> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.failing {
+                return Err(anyhow!("permission denied"));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reopen() {
+        let first = Rollable {
+            failing: true,
+            ..Rollable::default()
+        };
+        let second = Rollable::default();
+        let (first_calls, second_calls) = (first.calls.clone(), second.calls.clone());
+        let appender = FailoverAppender::builder()
+            .appender("first", Box::new(first))
+            .appender("second", Box::new(second))
+            .build();
+
+        let err = appender.reopen().unwrap_err();
+        assert_eq!(err.to_string(), "appender `first`: permission denied");
+        assert_eq!(first_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(second_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn roll() {
+        let (appender, _, _) = failover(Duration::from_secs(60));
+        let err = appender.roll().unwrap_err();
+        assert_eq!(err.to_string(), "the appender does not support rolling");
+
+        // Appenders which can't roll are skipped.
+        let rollable = Rollable::default();
+        let calls = rollable.calls.clone();
+        let appender = FailoverAppender::builder()
+            .appender("console", Box::new(FlakyAppender(Arc::default())))
+            .appender("file", Box::new(rollable))
+            .build();
+        appender.roll().unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "console_appender"))]
+    fn deserialize() {
+        use crate::config::{Deserializers, RawConfig};
+
+        let config = "
+appenders:
+    audit:
+        kind: failover
+        retry_after: 10 seconds
+        appenders:
+            - name: first
+              kind: console
+              target: stderr
+            - name: second
+              kind: console
+";
+        let config = ::serde_yaml::from_str::<RawConfig>(config).unwrap();
+        let errors = config.appenders_lossy(&Deserializers::new()).1;
+        assert!(errors.is_empty());
+
+        let config = "
+appenders:
+    audit:
+        kind: failover
+        appenders:
+            - kind: console
+";
+        let config = ::serde_yaml::from_str::<RawConfig>(config).unwrap();
+        let errors = config.appenders_lossy(&Deserializers::new()).1;
+        assert!(!errors.is_empty());
+    }
+}
@@ -5,20 +5,37 @@
 use derivative::Derivative;
 use log::Record;
 use parking_lot::Mutex;
+#[cfg(feature = "config_parsing")]
+use serde::de;
 use std::{
     fs::{self, File, OpenOptions},
     io::{self, BufWriter, Write},
     path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
 
+#[cfg(test)]
+use mock_instant::Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+#[cfg(feature = "config_parsing")]
+use crate::append::permissions::{de_mode, GroupConfig};
 #[cfg(feature = "config_parsing")]
 use crate::config::{Deserialize, Deserializers};
 #[cfg(feature = "config_parsing")]
 use crate::encode::EncoderConfig;
 
 use crate::{
-    append::{env_util::expand_env_vars, Append},
-    encode::{pattern::PatternEncoder, writer::simple::SimpleWriter, Encode},
+    append::{
+        durability::{Durability, FlushPolicy, Pending, SyncPolicy},
+        env_util::expand_env_vars,
+        permissions::Permissions,
+        Append,
+    },
+    encode::{self, pattern::PatternEncoder, Encode},
+    stats::AppendStats,
 };
 
 /// The file appender's configuration.
@@ -29,6 +46,64 @@ pub struct FileAppenderConfig {
     path: String,
     encoder: Option<EncoderConfig>,
     append: Option<bool>,
+    #[serde(default)]
+    reopen: Reopen,
+    #[serde(deserialize_with = "crate::config::de_opt_size", default)]
+    buffer_size: Option<u64>,
+    #[serde(default)]
+    flush: FlushPolicy,
+    #[serde(default)]
+    sync: SyncPolicy,
+    #[serde(deserialize_with = "de_mode", default)]
+    mode: Option<u32>,
+    #[serde(deserialize_with = "de_mode", default)]
+    dir_mode: Option<u32>,
+    uid: Option<u32>,
+    group: Option<GroupConfig>,
+    create_dirs: Option<bool>,
+}
+
+/// When a `FileAppender` checks whether its path still refers to the file it
+/// has open.
+///
+/// Tools like `logrotate` rename the log file and expect the process to start
+/// writing to a new file at the original path. If the check finds that the
+/// path refers to a different file, or to none, the appender reopens it.
+///
+/// On Unix, files are compared by device and inode. Elsewhere, only a missing
+/// file is detected.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Reopen {
+    /// Never check. The file is only reopened on request, for example through
+    /// `Handle::reopen_files`.
+    #[default]
+    Never,
+    /// Check before each write, at the cost of a `stat` call per record.
+    OnWrite,
+    /// Check before a write, at most once per interval.
+    Interval(Duration),
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for Reopen {
+    fn deserialize<D>(d: D) -> Result<Reopen, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let s = String::deserialize(d)?;
+        match &*s {
+            "never" => Ok(Reopen::Never),
+            "on_write" => Ok(Reopen::OnWrite),
+            _ => humantime::parse_duration(&s)
+                .map(Reopen::Interval)
+                .map_err(|_| {
+                    de::Error::custom(format!(
+                        "invalid reopen `{}`: expected `never`, `on_write` or a duration",
+                        s
+                    ))
+                }),
+        }
+    }
 }
 
 /// An appender which logs to a file.
@@ -37,35 +112,153 @@ pub struct FileAppenderConfig {
 pub struct FileAppender {
     path: PathBuf,
     #[derivative(Debug = "ignore")]
-    file: Mutex<SimpleWriter<BufWriter<File>>>,
+    file: Arc<Mutex<FileWriter>>,
     encoder: Box<dyn Encode>,
+    reopen: Reopen,
+    durability: Durability,
+    permissions: Permissions,
 }
 
 impl Append for FileAppender {
     fn append(&self, record: &Record) -> anyhow::Result<()> {
         let mut file = self.file.lock();
+        if self.should_check(&mut file) && self.is_moved(&file) {
+            self.reopen_writer(&mut file)?;
+        }
         self.encoder.encode(&mut *file, record)?;
-        file.flush()?;
+        let file = &mut *file;
+        self.durability
+            .commit(&mut file.file, &mut file.pending, record.level())?;
         Ok(())
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        let _ = self.file.lock().flush();
+    }
+
+    fn stats(&self) -> AppendStats {
+        AppendStats {
+            bytes_written: Some(self.file.lock().written),
+            ..AppendStats::default()
+        }
+    }
+
+    fn reopen(&self) -> anyhow::Result<()> {
+        FileAppender::reopen(self).map_err(Into::into)
+    }
+}
+
+struct FileWriter {
+    file: BufWriter<File>,
+    written: u64,
+    id: Option<FileId>,
+    checked: Instant,
+    pending: Pending,
+}
+
+/// Identifies a file independently of its path.
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_: &fs::Metadata) -> Option<FileId> {
+    None
+}
+
+impl io::Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf).map(|n| {
+            self.written += n as u64;
+            n
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
 }
 
+impl encode::Write for FileWriter {}
+
 impl FileAppender {
     /// Creates a new `FileAppender` builder.
     pub fn builder() -> FileAppenderBuilder {
         FileAppenderBuilder {
             encoder: None,
             append: true,
+            reopen: Reopen::default(),
+            durability: Durability::default(),
+            permissions: Permissions::default(),
+        }
+    }
+
+    /// Closes the log file and opens the file at its path, creating it if
+    /// necessary.
+    ///
+    /// This is what a process is expected to do after `logrotate` renames its
+    /// log file, typically when it receives `SIGHUP`. The new file is always
+    /// appended to.
+    pub fn reopen(&self) -> io::Result<()> {
+        let mut file = self.file.lock();
+        self.reopen_writer(&mut file)
+    }
+
+    fn should_check(&self, file: &mut FileWriter) -> bool {
+        match self.reopen {
+            Reopen::Never => false,
+            Reopen::OnWrite => true,
+            Reopen::Interval(interval) => {
+                let now = Instant::now();
+                if now.duration_since(file.checked) < interval {
+                    return false;
+                }
+                file.checked = now;
+                true
+            }
+        }
+    }
+
+    /// Returns whether the path no longer refers to the open file.
+    fn is_moved(&self, file: &FileWriter) -> bool {
+        match fs::metadata(&self.path) {
+            Ok(metadata) => file_id(&metadata) != file.id,
+            Err(_) => true,
         }
     }
+
+    fn reopen_writer(&self, file: &mut FileWriter) -> io::Result<()> {
+        // Whatever is buffered belongs at the end of the old file.
+        let _ = file.flush();
+        let new = open(&self.path, true, &self.permissions)?;
+        file.id = file_id(&new.metadata()?);
+        file.file = self.durability.buffer(new);
+        Ok(())
+    }
+}
+
+fn open(path: &Path, append: bool, permissions: &Permissions) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .create(true);
+    permissions.open(path, &options)
 }
 
 /// A builder for `FileAppender`s.
 pub struct FileAppenderBuilder {
     encoder: Option<Box<dyn Encode>>,
     append: bool,
+    reopen: Reopen,
+    durability: Durability,
+    permissions: Permissions,
 }
 
 impl FileAppenderBuilder {
@@ -83,6 +276,81 @@ impl FileAppenderBuilder {
         self
     }
 
+    /// Determines when the appender checks whether the log file was moved
+    /// away, so it can reopen the file at its path.
+    ///
+    /// Defaults to `Reopen::Never`.
+    pub fn reopen(mut self, reopen: Reopen) -> FileAppenderBuilder {
+        self.reopen = reopen;
+        self
+    }
+
+    /// Sets the size in bytes of the buffer records are written to before
+    /// they are flushed to the file.
+    ///
+    /// Defaults to 1024.
+    pub fn buffer_size(mut self, buffer_size: usize) -> FileAppenderBuilder {
+        self.durability.buffer_size = buffer_size;
+        self
+    }
+
+    /// Determines when the buffer is flushed to the file.
+    ///
+    /// Defaults to `FlushPolicy::Always`.
+    pub fn flush(mut self, flush: FlushPolicy) -> FileAppenderBuilder {
+        self.durability.flush = flush;
+        self
+    }
+
+    /// Determines when the file's data is synced to the disk.
+    ///
+    /// Defaults to `SyncPolicy::None`.
+    pub fn sync(mut self, sync: SyncPolicy) -> FileAppenderBuilder {
+        self.durability.sync = sync;
+        self
+    }
+
+    /// Sets the mode of the log file if the appender creates it, e.g. `0o640`.
+    ///
+    /// Only supported on Unix. Defaults to the process's umask.
+    pub fn mode(mut self, mode: u32) -> FileAppenderBuilder {
+        self.permissions.mode = Some(mode);
+        self
+    }
+
+    /// Sets the mode of the directories the appender creates, e.g. `0o750`.
+    ///
+    /// Only supported on Unix. Defaults to the process's umask.
+    pub fn dir_mode(mut self, mode: u32) -> FileAppenderBuilder {
+        self.permissions.dir_mode = Some(mode);
+        self
+    }
+
+    /// Sets the user owning the files and directories the appender creates.
+    ///
+    /// Changing the owner typically requires root. Only supported on Unix.
+    pub fn uid(mut self, uid: u32) -> FileAppenderBuilder {
+        self.permissions.uid = Some(uid);
+        self
+    }
+
+    /// Sets the group owning the files and directories the appender creates.
+    ///
+    /// The process must belong to the group unless it runs as root. Only
+    /// supported on Unix.
+    pub fn gid(mut self, gid: u32) -> FileAppenderBuilder {
+        self.permissions.gid = Some(gid);
+        self
+    }
+
+    /// Determines if missing parent directories of the log file are created.
+    ///
+    /// Defaults to `true`.
+    pub fn create_dirs(mut self, create_dirs: bool) -> FileAppenderBuilder {
+        self.permissions.create_dirs = create_dirs;
+        self
+    }
+
     /// Consumes the `FileAppenderBuilder`, producing a `FileAppender`.
     /// The path argument can contain environment variables of the form $ENV{name_here},
     /// where 'name_here' will be the name of the environment variable that
@@ -91,22 +359,29 @@ impl FileAppenderBuilder {
     pub fn build<P: AsRef<Path>>(self, path: P) -> io::Result<FileAppender> {
         let path_cow = path.as_ref().to_string_lossy();
         let path: PathBuf = expand_env_vars(path_cow).as_ref().into();
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        let file = OpenOptions::new()
-            .write(true)
-            .append(self.append)
-            .truncate(!self.append)
-            .create(true)
-            .open(&path)?;
+        let file = open(&path, self.append, &self.permissions)?;
+
+        let file = Arc::new(Mutex::new(FileWriter {
+            id: file_id(&file.metadata()?),
+            file: self.durability.buffer(file),
+            written: 0,
+            checked: Instant::now(),
+            pending: Pending::default(),
+        }));
+        self.durability
+            .spawn_flusher(Arc::downgrade(&file), |file: &mut FileWriter, interval| {
+                file.pending.flush_idle(&mut file.file, interval)
+            })?;
 
         Ok(FileAppender {
             path,
-            file: Mutex::new(SimpleWriter(BufWriter::with_capacity(1024, file))),
+            file,
             encoder: self
                 .encoder
                 .unwrap_or_else(|| Box::<PatternEncoder>::default()),
+            reopen: self.reopen,
+            durability: self.durability,
+            permissions: self.permissions,
         })
     }
 }
@@ -132,6 +407,42 @@ impl FileAppenderBuilder {
 /// # The encoder to use to format output. Defaults to `kind: pattern`.
 /// encoder:
 ///   kind: pattern
+///
+/// # When to check whether the log file was moved away, e.g. by logrotate, and
+/// # reopen the file at `path` if so: `never`, `on_write`, or an interval like
+/// # `10 seconds`. Defaults to `never`.
+/// reopen: 10 seconds
+///
+/// # The size of the buffer records are written to before they are flushed to
+/// # the file. Defaults to 1 kb.
+/// buffer_size: 64 kb
+///
+/// # When the buffer is flushed to the file: `always` after every record, after
+/// # records at or above a level like `warn`, or at most once per interval like
+/// # `5 seconds`. Unless it is `always`, a background thread also flushes buffers
+/// # which haven't been flushed for the interval, or a second. Defaults to `always`.
+/// flush: warn
+///
+/// # When the file is synced to the disk: `none`, after every N records like
+/// # `100`, or after records at or above a level like `error`. Defaults to `none`.
+/// sync: error
+///
+/// # The octal mode of the log file, if the appender creates it. Unix only, and
+/// # must be quoted. Defaults to the process's umask.
+/// mode: "0640"
+///
+/// # The octal mode of directories the appender creates. Unix only, and must be
+/// # quoted. Defaults to the process's umask.
+/// dir_mode: "0750"
+///
+/// # The user id and the group name or id owning the files and directories
+/// # the appender creates. Unix only. Optional.
+/// uid: 1000
+/// group: adm
+///
+/// # Specifies if missing parent directories of the log file are created.
+/// # Defaults to `true`.
+/// create_dirs: true
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
@@ -152,6 +463,28 @@ impl Deserialize for FileAppenderDeserializer {
         if let Some(append) = config.append {
             appender = appender.append(append);
         }
+        appender = appender
+            .reopen(config.reopen)
+            .flush(config.flush)
+            .sync(config.sync);
+        if let Some(buffer_size) = config.buffer_size {
+            appender = appender.buffer_size(buffer_size as usize);
+        }
+        if let Some(mode) = config.mode {
+            appender = appender.mode(mode);
+        }
+        if let Some(mode) = config.dir_mode {
+            appender = appender.dir_mode(mode);
+        }
+        if let Some(uid) = config.uid {
+            appender = appender.uid(uid);
+        }
+        if let Some(group) = config.group {
+            appender = appender.gid(group.gid()?);
+        }
+        if let Some(create_dirs) = config.create_dirs {
+            appender = appender.create_dirs(create_dirs);
+        }
         if let Some(encoder) = config.encoder {
             appender = appender.encoder(deserializers.deserialize(&encoder.kind, encoder.config)?);
         }
@@ -180,4 +513,115 @@ mod test {
             .build(tempdir.path().join("foo.log"))
             .unwrap();
     }
+
+    fn log(appender: &FileAppender, msg: &str) {
+        appender
+            .append(&Record::builder().args(format_args!("{}", msg)).build())
+            .unwrap();
+    }
+
+    #[test]
+    fn reopen() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("foo.log");
+        let rotated = tempdir.path().join("foo.log.1");
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build(&path)
+            .unwrap();
+
+        log(&appender, "a");
+        fs::rename(&path, &rotated).unwrap();
+        log(&appender, "b");
+        appender.reopen().unwrap();
+        log(&appender, "c");
+
+        assert_eq!(fs::read(&rotated).unwrap(), b"ab");
+        assert_eq!(fs::read(&path).unwrap(), b"c");
+        assert_eq!(appender.stats().bytes_written, Some(3));
+    }
+
+    #[test]
+    fn reopen_on_write() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("foo.log");
+        let rotated = tempdir.path().join("foo.log.1");
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .reopen(Reopen::OnWrite)
+            .build(&path)
+            .unwrap();
+
+        log(&appender, "a");
+        fs::rename(&path, &rotated).unwrap();
+        // logrotate's `create` mode puts a new, empty file in place.
+        File::create(&path).unwrap();
+        log(&appender, "b");
+
+        assert_eq!(fs::read(&rotated).unwrap(), b"a");
+        assert_eq!(fs::read(&path).unwrap(), b"b");
+    }
+
+    #[test]
+    fn reopen_interval() {
+        use mock_instant::MockClock;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("foo.log");
+        let rotated = tempdir.path().join("foo.log.1");
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .reopen(Reopen::Interval(Duration::from_secs(10)))
+            .build(&path)
+            .unwrap();
+
+        fs::rename(&path, &rotated).unwrap();
+        log(&appender, "a");
+        MockClock::advance(Duration::from_secs(10));
+        log(&appender, "b");
+
+        assert_eq!(fs::read(&rotated).unwrap(), b"a");
+        assert_eq!(fs::read(&path).unwrap(), b"b");
+    }
+
+    #[test]
+    fn flush_level() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("foo.log");
+        let appender = FileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .flush(FlushPolicy::Level(log::Level::Warn))
+            .build(&path)
+            .unwrap();
+
+        log(&appender, "a");
+        assert_eq!(fs::read(&path).unwrap(), b"");
+        appender
+            .append(
+                &Record::builder()
+                    .level(log::Level::Warn)
+                    .args(format_args!("b"))
+                    .build(),
+            )
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"ab");
+
+        log(&appender, "c");
+        Append::flush(&appender);
+        assert_eq!(fs::read(&path).unwrap(), b"abc");
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn deserialize_reopen() {
+        assert_eq!(
+            serde_yaml::from_str::<Reopen>("on_write").unwrap(),
+            Reopen::OnWrite
+        );
+        assert_eq!(
+            serde_yaml::from_str::<Reopen>("10 seconds").unwrap(),
+            Reopen::Interval(Duration::from_secs(10))
+        );
+        assert!(serde_yaml::from_str::<Reopen>("sometimes").is_err());
+    }
 }
@@ -13,13 +13,24 @@ use std::fmt;
 use crate::config::Deserializable;
 #[cfg(feature = "config_parsing")]
 use crate::filter::FilterConfig;
+use crate::stats::AppendStats;
 
+#[cfg(feature = "capture_appender")]
+pub mod capture;
 #[cfg(feature = "console_appender")]
 pub mod console;
+#[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
+pub mod durability;
+#[cfg(feature = "failover_appender")]
+pub mod failover;
 #[cfg(feature = "file_appender")]
 pub mod file;
+#[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
+pub(crate) mod permissions;
 #[cfg(feature = "rolling_file_appender")]
 pub mod rolling_file;
+#[cfg(feature = "routing_appender")]
+pub mod routing;
 
 #[cfg(any(feature = "file_appender", feature = "rolling_file_appender"))]
 mod env_util {
@@ -93,6 +104,103 @@ pub trait Append: fmt::Debug + Send + Sync + 'static {
 
     /// Flushes all in-flight records.
     fn flush(&self);
+
+    /// Returns the counters maintained by the appender, such as the number of
+    /// bytes it wrote.
+    ///
+    /// The default implementation tracks nothing.
+    fn stats(&self) -> AppendStats {
+        AppendStats::default()
+    }
+
+    /// Rolls the appender's log file over, regardless of its policy's
+    /// trigger.
+    ///
+    /// The default implementation returns an error, as most appenders have
+    /// nothing to roll.
+    fn roll(&self) -> anyhow::Result<()> {
+        Err(RollUnsupported.into())
+    }
+
+    /// Closes and reopens the files the appender writes to, for example
+    /// after they were renamed by an external tool like `logrotate`.
+    ///
+    /// The default implementation does nothing, as most appenders have no
+    /// files to reopen.
+    fn reopen(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// The error returned by appenders which don't support rolling.
+#[derive(Debug)]
+struct RollUnsupported;
+
+impl fmt::Display for RollUnsupported {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("the appender does not support rolling")
+    }
+}
+
+impl std::error::Error for RollUnsupported {}
+
+/// Rolls the appenders wrapped by another appender, such as the backends of a
+/// `FailoverAppender`, identified by name.
+///
+/// Appenders which don't support rolling are skipped, and the others are all
+/// rolled even if some fail. The failures are returned as a single error. If
+/// none of the appenders supports rolling, the wrapping appender doesn't
+/// either.
+#[cfg(any(feature = "failover_appender", feature = "routing_appender"))]
+pub(crate) fn roll_all<'a, I>(appenders: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = (&'a str, &'a dyn Append)>,
+{
+    let mut unsupported = None;
+    let mut failures = vec![];
+    for (name, appender) in appenders {
+        match appender.roll() {
+            Ok(()) => unsupported = Some(false),
+            Err(e) if e.is::<RollUnsupported>() => {
+                unsupported.get_or_insert(true);
+            }
+            Err(e) => {
+                unsupported = Some(false);
+                failures.push(format!("appender `{}`: {}", name, e));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        Err(anyhow::anyhow!("{}", failures.join("; ")))
+    } else if unsupported == Some(true) {
+        Err(RollUnsupported.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Reopens the appenders wrapped by another appender, such as the backends of
+/// a `FailoverAppender`, identified by name.
+///
+/// Every appender is reopened even if some fail, and the failures are
+/// returned as a single error.
+#[cfg(any(feature = "failover_appender", feature = "routing_appender"))]
+pub(crate) fn reopen_all<'a, I>(appenders: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = (&'a str, &'a dyn Append)>,
+{
+    let failures = appenders
+        .into_iter()
+        .filter_map(|(name, appender)| {
+            let e = appender.reopen().err()?;
+            Some(format!("appender `{}`: {}", name, e))
+        })
+        .collect::<Vec<_>>();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("{}", failures.join("; ")))
+    }
 }
 
 #[cfg(feature = "config_parsing")]
@@ -0,0 +1,322 @@
+//! Permissions and ownership of the files and directories appenders create.
+//!
+//! Modes and ownership only apply on Unix, and are ignored elsewhere.
+//!
+//! Requires the `file_appender` or `rolling_file_appender` feature.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::Path,
+};
+
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+/// How an appender creates log files and their directories.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub(crate) struct Permissions {
+    pub(crate) mode: Option<u32>,
+    pub(crate) dir_mode: Option<u32>,
+    pub(crate) uid: Option<u32>,
+    pub(crate) gid: Option<u32>,
+    pub(crate) create_dirs: bool,
+}
+
+impl Default for Permissions {
+    fn default() -> Permissions {
+        Permissions {
+            mode: None,
+            dir_mode: None,
+            uid: None,
+            gid: None,
+            create_dirs: true,
+        }
+    }
+}
+
+impl Permissions {
+    /// Opens `path` with `options`, creating missing parent directories.
+    ///
+    /// If the file is created, it is given the configured mode and owner, and
+    /// removed again if that fails. Existing files are left as they are.
+    pub(crate) fn open(&self, path: &Path, options: &OpenOptions) -> io::Result<File> {
+        self.create_parent(path)?;
+
+        let mut new = options.clone();
+        new.create_new(true);
+        #[cfg(unix)]
+        if let Some(mode) = self.mode {
+            // Keeps the file private until its permissions are set.
+            new.mode(mode);
+        }
+
+        // Creating the file exclusively tells whether it already existed.
+        match new.open(path) {
+            Ok(file) => match self.apply(path, self.mode) {
+                Ok(()) => Ok(file),
+                Err(e) => {
+                    // Don't leave a file with the wrong permissions or owner
+                    // behind, where the next attempt would open it as is.
+                    drop(file);
+                    let _ = fs::remove_file(path);
+                    Err(e)
+                }
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => options.open(path),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Creates the missing parent directories of `path` with the configured
+    /// mode and owner.
+    ///
+    /// Fails if a directory is missing and creating them is disabled.
+    pub(crate) fn create_parent(&self, path: &Path) -> io::Result<()> {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => return Ok(()),
+        };
+        if parent.is_dir() {
+            return Ok(());
+        }
+        if !self.create_dirs {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("directory {} does not exist", parent.display()),
+            ));
+        }
+
+        self.create_parent(parent)?;
+        match fs::create_dir(parent) {
+            Ok(()) => self.apply(parent, self.dir_mode),
+            // Another appender or process created it first.
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists && parent.is_dir() => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    #[cfg(unix)]
+    fn apply(&self, path: &Path, mode: Option<u32>) -> io::Result<()> {
+        if let Some(mode) = mode {
+            // The mode passed when creating is masked by the umask.
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+        chown(path, self.uid, self.gid)
+    }
+
+    #[cfg(not(unix))]
+    fn apply(&self, _: &Path, _: Option<u32>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Gives `path` the permissions and owner described by `metadata`, as far as
+/// the process is allowed to.
+///
+/// Used to make compressed archives match the log file they were made from.
+// Only used by components behind optional features.
+#[allow(dead_code)]
+pub(crate) fn copy(metadata: &fs::Metadata, path: &Path) -> io::Result<()> {
+    fs::set_permissions(path, metadata.permissions())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        // Only root may give files away, but a process may still change the
+        // group to one it belongs to.
+        if chown(path, Some(metadata.uid()), Some(metadata.gid())).is_err() {
+            let _ = chown(path, None, Some(metadata.gid()));
+        }
+    }
+    Ok(())
+}
+
+/// Sets the mode of `path`.
+// Only used by components behind optional features.
+#[allow(dead_code)]
+pub(crate) fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+    Ok(())
+}
+
+#[cfg(unix)]
+fn chown(path: &Path, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    if uid.is_none() && gid.is_none() {
+        return Ok(());
+    }
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    // -1 leaves the id unchanged.
+    let uid = uid.map_or(libc::uid_t::MAX, |uid| uid as libc::uid_t);
+    let gid = gid.map_or(libc::gid_t::MAX, |gid| gid as libc::gid_t);
+    if unsafe { libc::chown(path.as_ptr(), uid, gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Looks up the id of the group named `name`.
+#[cfg(all(unix, feature = "config_parsing"))]
+fn group_id(name: &str) -> io::Result<u32> {
+    use std::{ffi::CString, mem, ptr};
+
+    let c_name = CString::new(name)?;
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        let mut group: libc::group = unsafe { mem::zeroed() };
+        let mut result = ptr::null_mut();
+        let ret = unsafe {
+            libc::getgrnam_r(
+                c_name.as_ptr(),
+                &mut group,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        match ret {
+            0 if result.is_null() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no group named `{}`", name),
+                ))
+            }
+            0 => return Ok(group.gr_gid as u32),
+            libc::ERANGE => {
+                let len = buf.len() * 2;
+                buf.resize(len, 0);
+            }
+            _ => return Err(io::Error::from_raw_os_error(ret)),
+        }
+    }
+}
+
+/// The group of created files in a configuration, by name or id.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(untagged)]
+pub(crate) enum GroupConfig {
+    Id(u32),
+    Name(String),
+}
+
+#[cfg(feature = "config_parsing")]
+impl GroupConfig {
+    /// Returns the id of the group, looking it up by name if necessary.
+    pub(crate) fn gid(&self) -> io::Result<u32> {
+        match self {
+            GroupConfig::Id(gid) => Ok(*gid),
+            #[cfg(unix)]
+            GroupConfig::Name(name) => group_id(name),
+            #[cfg(not(unix))]
+            GroupConfig::Name(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "group names are only supported on Unix",
+            )),
+        }
+    }
+}
+
+/// Deserializes an octal mode like `"0640"`.
+#[cfg(feature = "config_parsing")]
+pub(crate) fn de_mode<'de, D>(d: D) -> Result<Option<u32>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    use serde::Deserialize;
+
+    let s = match Option::<String>::deserialize(d)? {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+    match u32::from_str_radix(&s, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(Some(mode)),
+        _ => Err(serde::de::Error::custom(format!(
+            "invalid mode `{}`: expected an octal mode like \"0640\"",
+            s
+        ))),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::os::unix::fs::MetadataExt;
+
+    use super::*;
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().mode() & 0o7777
+    }
+
+    #[test]
+    fn open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a").join("b").join("foo.log");
+        let permissions = Permissions {
+            mode: Some(0o640),
+            dir_mode: Some(0o750),
+            gid: Some(fs::metadata(dir.path()).unwrap().gid()),
+            ..Permissions::default()
+        };
+        let mut options = OpenOptions::new();
+        options.write(true).create(true);
+
+        permissions.open(&path, &options).unwrap();
+        assert_eq!(mode(&path), 0o640);
+        assert_eq!(mode(&dir.path().join("a")), 0o750);
+        assert_eq!(mode(&dir.path().join("a").join("b")), 0o750);
+
+        // Existing files are left alone.
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        permissions.open(&path, &options).unwrap();
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    #[test]
+    fn create_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let permissions = Permissions {
+            create_dirs: false,
+            ..Permissions::default()
+        };
+        let mut options = OpenOptions::new();
+        options.write(true).create(true);
+
+        permissions
+            .open(&dir.path().join("foo.log"), &options)
+            .unwrap();
+        let err = permissions
+            .open(&dir.path().join("missing").join("foo.log"), &options)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    #[cfg(feature = "yaml_format")]
+    fn deserialize() {
+        #[derive(serde::Deserialize)]
+        struct Config {
+            #[serde(deserialize_with = "de_mode", default)]
+            mode: Option<u32>,
+            group: Option<GroupConfig>,
+        }
+
+        let config = serde_yaml::from_str::<Config>("mode: \"0640\"\ngroup: 4").unwrap();
+        assert_eq!(config.mode, Some(0o640));
+        assert_eq!(config.group.unwrap().gid().unwrap(), 4);
+
+        if cfg!(target_os = "linux") {
+            let config = serde_yaml::from_str::<Config>("group: root").unwrap();
+            assert_eq!(config.group.unwrap().gid().unwrap(), 0);
+        }
+
+        assert!(serde_yaml::from_str::<Config>("mode: \"0980\"").is_err());
+        assert!(serde_yaml::from_str::<Config>("mode: \"17777\"").is_err());
+    }
+}
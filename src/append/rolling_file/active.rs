@@ -0,0 +1,205 @@
+//! Generated names for the active log file, and the link which follows it.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local,
+};
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Literal(String),
+    Date(String),
+}
+
+/// A pattern for the names of active log files, like `app-{d(%Y-%m-%dT%H)}.log`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct NamePattern(Vec<Token>);
+
+impl NamePattern {
+    /// Parses a pattern containing `{d}` or `{d(format)}` date tokens.
+    pub(crate) fn parse(mut pattern: &str) -> io::Result<NamePattern> {
+        let mut tokens = vec![];
+        let mut literal = String::new();
+
+        while let Some(start) = pattern.find('{') {
+            literal.push_str(&pattern[..start]);
+            let rest = &pattern[start..];
+
+            let (format, len) = if rest.starts_with("{d}") {
+                (DEFAULT_DATE_FORMAT, "{d}".len())
+            } else if let Some(format) = rest.strip_prefix("{d(") {
+                let end = match format.find(")}") {
+                    Some(end) => end,
+                    None => return Err(invalid("unterminated date token in active file pattern")),
+                };
+                let format = &format[..end];
+                if StrftimeItems::new(format).any(|i| i == Item::Error) {
+                    return Err(invalid(&format!("invalid date format `{}`", format)));
+                }
+                (format, "{d(".len() + end + ")}".len())
+            } else {
+                literal.push('{');
+                pattern = &rest[1..];
+                continue;
+            };
+
+            if !literal.is_empty() {
+                tokens.push(Token::Literal(std::mem::take(&mut literal)));
+            }
+            tokens.push(Token::Date(format.to_owned()));
+            pattern = &rest[len..];
+        }
+
+        literal.push_str(pattern);
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+        if !tokens.iter().any(|t| matches!(t, Token::Date(_))) {
+            return Err(invalid(
+                "active file pattern does not contain a `{d}` date token",
+            ));
+        }
+        Ok(NamePattern(tokens))
+    }
+
+    /// Returns the path of the active log file opened at `time`.
+    pub(crate) fn format(&self, time: &DateTime<Local>) -> PathBuf {
+        let mut path = String::new();
+        for token in &self.0 {
+            match token {
+                Token::Literal(literal) => path.push_str(literal),
+                Token::Date(format) => path.push_str(&time.format(format).to_string()),
+            }
+        }
+        PathBuf::from(path)
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Returns the file `link` points to, if it is a symlink to an existing file.
+///
+/// Returns an error if `link` exists but is not a symlink, since replacing it
+/// would delete a log file.
+pub(crate) fn link_target(link: &Path) -> io::Result<Option<PathBuf>> {
+    match fs::symlink_metadata(link) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {}
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a symbolic link", link.display()),
+            ))
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let target = fs::read_link(link)?;
+    let target = match link.parent() {
+        Some(parent) if target.is_relative() => parent.join(target),
+        _ => target,
+    };
+    Ok(Some(target).filter(|t| t.is_file()))
+}
+
+/// Atomically points `link` at `target`.
+///
+/// A new link is created next to `link` and renamed over it, so readers
+/// following `link` never find it missing.
+pub(crate) fn update_link(link: &Path, target: &Path) -> io::Result<()> {
+    // Prefer a relative link, so the directory can be moved or mounted
+    // elsewhere.
+    let target = if target.parent() == link.parent() {
+        PathBuf::from(target.file_name().unwrap_or_default())
+    } else {
+        match target.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => {
+                fs::canonicalize(parent)?.join(target.file_name().unwrap_or_default())
+            }
+            _ => fs::canonicalize(target)?,
+        }
+    };
+
+    let mut name = link.file_name().unwrap_or_default().to_owned();
+    name.push(format!(".{}.tmp", process::id()));
+    let temp = link.with_file_name(name);
+    let _ = fs::remove_file(&temp);
+    symlink(&target, &temp)?;
+    fs::rename(&temp, link).map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        e
+    })
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn format() {
+        let pattern = NamePattern::parse("logs/app-{d(%Y-%m-%dT%H)}.{x}.log").unwrap();
+        let time = Local.with_ymd_and_hms(2026, 10, 18, 10, 30, 0).unwrap();
+        assert_eq!(
+            pattern.format(&time),
+            PathBuf::from("logs/app-2026-10-18T10.{x}.log")
+        );
+        assert_eq!(
+            NamePattern::parse("app-{d}.log").unwrap().format(&time),
+            PathBuf::from("app-2026-10-18.log")
+        );
+
+        assert!(NamePattern::parse("app.log").is_err());
+        assert!(NamePattern::parse("app-{d(%Y.log").is_err());
+        assert!(NamePattern::parse("app-{d(%Q)}.log").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn link() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("current.log");
+        assert_eq!(link_target(&link).unwrap(), None);
+
+        let first = dir.path().join("app-1.log");
+        fs::write(&first, b"first").unwrap();
+        update_link(&link, &first).unwrap();
+        assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("app-1.log"));
+        assert_eq!(link_target(&link).unwrap(), Some(first.clone()));
+
+        let second = dir.path().join("sub").join("app-2.log");
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(&second, b"second").unwrap();
+        update_link(&link, &second).unwrap();
+        assert_eq!(fs::read(&link).unwrap(), b"second");
+        assert!(fs::read_link(&link).unwrap().is_absolute());
+
+        // Only the link itself remains next to the active files.
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+
+        fs::remove_file(&second).unwrap();
+        assert_eq!(link_target(&link).unwrap(), None);
+        assert!(link_target(&first).is_err());
+    }
+}
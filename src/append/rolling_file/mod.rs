@@ -16,6 +16,7 @@
 //!
 //! Requires the `rolling_file_appender` feature.
 
+use chrono::Local;
 use derivative::Derivative;
 use log::Record;
 use parking_lot::Mutex;
@@ -23,6 +24,10 @@ use std::{
     fs::{self, File, OpenOptions},
     io::{self, BufWriter, Write},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 
 #[cfg(feature = "config_parsing")]
@@ -31,16 +36,25 @@ use serde_value::Value;
 use std::collections::BTreeMap;
 
 use crate::{
-    append::Append,
+    append::{
+        durability::{Durability, FlushPolicy, Pending, SyncPolicy},
+        permissions::Permissions,
+        Append,
+    },
     encode::{self, pattern::PatternEncoder, Encode},
+    stats::AppendStats,
 };
 
+#[cfg(feature = "config_parsing")]
+use crate::append::permissions::{de_mode, GroupConfig};
 #[cfg(feature = "config_parsing")]
 use crate::config::{Deserialize, Deserializers};
 #[cfg(feature = "config_parsing")]
 use crate::encode::EncoderConfig;
 
+mod active;
 pub mod policy;
+pub(crate) mod shared;
 
 /// Configuration for the rolling file appender.
 #[cfg(feature = "config_parsing")]
@@ -48,9 +62,24 @@ pub mod policy;
 #[serde(deny_unknown_fields)]
 pub struct RollingFileAppenderConfig {
     path: String,
+    active_file: Option<String>,
     append: Option<bool>,
     encoder: Option<EncoderConfig>,
     policy: Policy,
+    #[serde(deserialize_with = "crate::config::de_opt_size", default)]
+    buffer_size: Option<u64>,
+    #[serde(default)]
+    flush: FlushPolicy,
+    #[serde(default)]
+    sync: SyncPolicy,
+    #[serde(deserialize_with = "de_mode", default)]
+    mode: Option<u32>,
+    #[serde(deserialize_with = "de_mode", default)]
+    dir_mode: Option<u32>,
+    uid: Option<u32>,
+    group: Option<GroupConfig>,
+    create_dirs: Option<bool>,
+    shared: Option<bool>,
 }
 
 #[cfg(feature = "config_parsing")]
@@ -84,6 +113,7 @@ impl<'de> serde::Deserialize<'de> for Policy {
 struct LogWriter {
     file: BufWriter<File>,
     len: u64,
+    pending: Pending,
 }
 
 impl io::Write for LogWriter {
@@ -150,32 +180,54 @@ impl<'a> LogFile<'a> {
     }
 }
 
+/// The log file currently written to.
+#[derive(Debug)]
+struct ActiveFile {
+    writer: Option<LogWriter>,
+    path: PathBuf,
+}
+
 /// An appender which archives log files in a configurable strategy.
+///
+/// By default, the appender writes to a fixed path. Alternatively, it can
+/// write to a file named after the time it was opened, like
+/// `app-2026-10-18T10.log`, in which case the configured path is a symlink
+/// which always points at the active file. The symlink is replaced
+/// atomically each time a new file is opened, so tools following the path
+/// never find it missing.
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct RollingFileAppender {
     #[derivative(Debug = "ignore")]
-    writer: Mutex<Option<LogWriter>>,
+    active: Arc<Mutex<ActiveFile>>,
     path: PathBuf,
+    active_pattern: Option<active::NamePattern>,
     append: bool,
     encoder: Box<dyn Encode>,
     policy: Box<dyn policy::Policy>,
+    durability: Durability,
+    permissions: Permissions,
+    shared: Option<shared::SharedLock>,
+    bytes_written: AtomicU64,
+    rolls: AtomicU64,
 }
 
 impl Append for RollingFileAppender {
     fn append(&self, record: &Record) -> anyhow::Result<()> {
         // TODO(eas): Perhaps this is better as a concurrent queue?
-        let mut writer = self.writer.lock();
+        let mut guard = self.active.lock();
+        let active = &mut *guard;
+        let _shared = self.lock_shared(active)?;
 
         let is_pre_process = self.policy.is_pre_process();
-        let log_writer = self.get_writer(&mut writer)?;
+        let log_writer = self.get_writer(active)?;
 
         if is_pre_process {
             let len = log_writer.len;
 
             let mut file = LogFile {
-                writer: &mut writer,
-                path: &self.path,
+                writer: &mut active.writer,
+                path: &active.path,
                 len,
             };
 
@@ -183,28 +235,59 @@ impl Append for RollingFileAppender {
             // data that comes in while we are processing the file rotation.
 
             self.policy.process(&mut file)?;
+            self.count_roll(&active.writer);
 
-            let log_writer_new = self.get_writer(&mut writer)?;
+            let log_writer_new = self.get_writer(active)?;
+            let start_len = log_writer_new.len;
             self.encoder.encode(log_writer_new, record)?;
-            log_writer_new.flush()?;
+            self.commit(log_writer_new, record)?;
+            self.count_bytes(log_writer_new.len - start_len);
         } else {
+            let start_len = log_writer.len;
             self.encoder.encode(log_writer, record)?;
-            log_writer.flush()?;
+            self.commit(log_writer, record)?;
             let len = log_writer.len;
+            self.count_bytes(len - start_len);
 
             let mut file = LogFile {
-                writer: &mut writer,
-                path: &self.path,
+                writer: &mut active.writer,
+                path: &active.path,
                 len,
             };
 
             self.policy.process(&mut file)?;
+            self.count_roll(&active.writer);
         }
 
         Ok(())
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        if let Some(writer) = &mut self.active.lock().writer {
+            let _ = writer.flush();
+        }
+    }
+
+    fn stats(&self) -> AppendStats {
+        AppendStats {
+            bytes_written: Some(self.bytes_written.load(Ordering::Relaxed)),
+            rolls: Some(self.rolls.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn roll(&self) -> anyhow::Result<()> {
+        RollingFileAppender::roll(self)
+    }
+
+    fn reopen(&self) -> anyhow::Result<()> {
+        let mut active = self.active.lock();
+        if let Some(writer) = &mut active.writer {
+            writer.flush()?;
+        }
+        // The next write opens the file at the active path again.
+        active.writer = None;
+        Ok(())
+    }
 }
 
 impl RollingFileAppender {
@@ -213,25 +296,119 @@ impl RollingFileAppender {
         RollingFileAppenderBuilder {
             append: true,
             encoder: None,
+            active_pattern: None,
+            durability: Durability::default(),
+            permissions: Permissions::default(),
+            shared: false,
+        }
+    }
+
+    /// Rolls the log file over immediately, without consulting the policy's
+    /// trigger.
+    ///
+    /// The log file is closed and handed to the policy's `Policy::roll`,
+    /// exactly as it is when a trigger fires, while holding the lock which
+    /// serializes writes, so no record is split across the roll. A new log
+    /// file is opened by the next write.
+    ///
+    /// If the log has already rolled and nothing was written to it since,
+    /// there is nothing to roll, and this does nothing.
+    pub fn roll(&self) -> anyhow::Result<()> {
+        let mut guard = self.active.lock();
+        let active = &mut *guard;
+        let _shared = self.lock_shared(active)?;
+        let len = match &active.writer {
+            Some(writer) => writer.len,
+            // Opening the log file would create an empty one only to archive
+            // it, unless another process sharing the file wrote to it.
+            None => match fs::metadata(&active.path) {
+                Ok(metadata) if metadata.len() > 0 => self.get_writer(active)?.len,
+                _ => return Ok(()),
+            },
+        };
+
+        let mut file = LogFile {
+            writer: &mut active.writer,
+            path: &active.path,
+            len,
+        };
+        let result = self.policy.roll(&mut file);
+        self.count_roll(&active.writer);
+        result
+    }
+
+    fn count_bytes(&self, len: u64) {
+        self.bytes_written.fetch_add(len, Ordering::Relaxed);
+    }
+
+    fn commit(&self, writer: &mut LogWriter, record: &Record) -> io::Result<()> {
+        self.durability
+            .commit(&mut writer.file, &mut writer.pending, record.level())?;
+        if self.shared.is_some() {
+            // Nothing may be written once the lock is released.
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Locks a shared log file, catching up with the writes and rolls of
+    /// other processes.
+    fn lock_shared(&self, active: &mut ActiveFile) -> io::Result<Option<shared::SharedGuard<'_>>> {
+        let lock = match &self.shared {
+            Some(lock) => lock.lock()?,
+            None => return Ok(None),
+        };
+
+        if let Some(writer) = &mut active.writer {
+            let file = writer.file.get_ref();
+            if shared::is_current(file, &active.path)? {
+                writer.len = file.metadata()?.len();
+            } else {
+                // Another process rolled the log, so the next write opens the
+                // new file.
+                active.writer = None;
+            }
         }
+        Ok(Some(lock))
     }
 
-    fn get_writer<'a>(&self, writer: &'a mut Option<LogWriter>) -> io::Result<&'a mut LogWriter> {
+    /// The policy closes the writer when it rolls the log.
+    fn count_roll(&self, writer: &Option<LogWriter>) {
         if writer.is_none() {
-            let file = OpenOptions::new()
+            self.rolls.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn get_writer<'a>(&self, active: &'a mut ActiveFile) -> io::Result<&'a mut LogWriter> {
+        let writer = &mut active.writer;
+        if writer.is_none() {
+            // A new name is generated unless the active file is still in
+            // place, i.e. when the appender starts or after a roll.
+            if let Some(pattern) = &self.active_pattern {
+                if !active.path.is_file() {
+                    active.path = pattern.format(&Local::now());
+                }
+            }
+
+            let mut options = OpenOptions::new();
+            options
                 .write(true)
                 .append(self.append)
                 .truncate(!self.append)
-                .create(true)
-                .open(&self.path)?;
+                .create(true);
+            let file = self.permissions.open(&active.path, &options)?;
+            if self.active_pattern.is_some() {
+                active::update_link(&self.path, &active.path)?;
+            }
             let len = if self.append {
                 file.metadata()?.len()
             } else {
                 0
             };
             *writer = Some(LogWriter {
-                file: BufWriter::with_capacity(1024, file),
+                file: self.durability.buffer(file),
                 len,
+                pending: Pending::default(),
             });
         }
 
@@ -244,6 +421,10 @@ impl RollingFileAppender {
 pub struct RollingFileAppenderBuilder {
     append: bool,
     encoder: Option<Box<dyn Encode>>,
+    active_pattern: Option<String>,
+    durability: Durability,
+    permissions: Permissions,
+    shared: bool,
 }
 
 impl RollingFileAppenderBuilder {
@@ -263,6 +444,106 @@ impl RollingFileAppenderBuilder {
         self
     }
 
+    /// Writes to files named after a pattern instead of directly to the path
+    /// passed to `build`, which becomes a symlink to the active file.
+    ///
+    /// The pattern must contain at least one `{d(format)}` token, replaced
+    /// with the local time the file is opened, formatted with the
+    /// `strftime`-style format. `{d}` is short for `{d(%Y-%m-%d)}`. A new
+    /// name is generated each time the log rolls. If appending, the file the
+    /// symlink points to is reused when the appender is created.
+    ///
+    /// The pattern can contain environment variables like the path.
+    pub fn active_file_pattern(mut self, pattern: &str) -> RollingFileAppenderBuilder {
+        self.active_pattern = Some(pattern.to_owned());
+        self
+    }
+
+    /// Sets the size in bytes of the buffer records are written to before
+    /// they are flushed to the file.
+    ///
+    /// Defaults to 1024.
+    pub fn buffer_size(mut self, buffer_size: usize) -> RollingFileAppenderBuilder {
+        self.durability.buffer_size = buffer_size;
+        self
+    }
+
+    /// Determines when the buffer is flushed to the file.
+    ///
+    /// Defaults to `FlushPolicy::Always`.
+    pub fn flush(mut self, flush: FlushPolicy) -> RollingFileAppenderBuilder {
+        self.durability.flush = flush;
+        self
+    }
+
+    /// Determines when the file's data is synced to the disk.
+    ///
+    /// Defaults to `SyncPolicy::None`.
+    pub fn sync(mut self, sync: SyncPolicy) -> RollingFileAppenderBuilder {
+        self.durability.sync = sync;
+        self
+    }
+
+    /// Sets the mode of the log files the appender creates, e.g. `0o640`.
+    ///
+    /// This applies to the first log file and to those created after each
+    /// roll. Compressed archives keep the mode of the file they were made
+    /// from. Only supported on Unix. Defaults to the process's umask.
+    pub fn mode(mut self, mode: u32) -> RollingFileAppenderBuilder {
+        self.permissions.mode = Some(mode);
+        self
+    }
+
+    /// Sets the mode of the directories the appender creates, e.g. `0o750`.
+    ///
+    /// Only supported on Unix. Defaults to the process's umask.
+    pub fn dir_mode(mut self, mode: u32) -> RollingFileAppenderBuilder {
+        self.permissions.dir_mode = Some(mode);
+        self
+    }
+
+    /// Sets the user owning the files and directories the appender creates.
+    ///
+    /// Changing the owner typically requires root. Only supported on Unix.
+    pub fn uid(mut self, uid: u32) -> RollingFileAppenderBuilder {
+        self.permissions.uid = Some(uid);
+        self
+    }
+
+    /// Sets the group owning the files and directories the appender creates.
+    ///
+    /// The process must belong to the group unless it runs as root. Only
+    /// supported on Unix.
+    pub fn gid(mut self, gid: u32) -> RollingFileAppenderBuilder {
+        self.permissions.gid = Some(gid);
+        self
+    }
+
+    /// Determines if missing parent directories of the log files are
+    /// created.
+    ///
+    /// Defaults to `true`.
+    pub fn create_dirs(mut self, create_dirs: bool) -> RollingFileAppenderBuilder {
+        self.permissions.create_dirs = create_dirs;
+        self
+    }
+
+    /// Determines if several processes may write to and roll the log file.
+    ///
+    /// Each write and roll then holds an exclusive advisory `flock` lock on
+    /// `<path>.lock`. Before writing, the appender reopens the log file if
+    /// another process rolled it, and its triggers see the size of the file
+    /// including other processes' records. Records are flushed before the
+    /// lock is released, whatever the flush policy, and rollers rotate on the
+    /// logging thread even with the `background_rotation` feature.
+    ///
+    /// Requires appending, and doesn't support active file patterns. Only
+    /// supported on Unix. Defaults to `false`.
+    pub fn shared(mut self, shared: bool) -> RollingFileAppenderBuilder {
+        self.shared = shared;
+        self
+    }
+
     /// Constructs a `RollingFileAppender`.
     /// The path argument can contain environment variables of the form $ENV{name_here},
     /// where 'name_here' will be the name of the environment variable that
@@ -276,23 +557,69 @@ impl RollingFileAppenderBuilder {
     where
         P: AsRef<Path>,
     {
-        let path = super::env_util::expand_env_vars(path.as_ref().to_string_lossy());
+        let path = PathBuf::from(
+            super::env_util::expand_env_vars(path.as_ref().to_string_lossy()).as_ref(),
+        );
+        let active_pattern = self
+            .active_pattern
+            .map(|p| active::NamePattern::parse(&super::env_util::expand_env_vars(p)))
+            .transpose()?;
+
+        if self.shared && (!self.append || active_pattern.is_some()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "shared log files require appending and don't support active file patterns",
+            ));
+        }
+
+        let mut active_path = path.clone();
+        if active_pattern.is_some() {
+            // Refuses to replace a regular file with the symlink.
+            let target = active::link_target(&path)?;
+            active_path = match target {
+                Some(target) if self.append => target,
+                _ => PathBuf::new(),
+            };
+        }
+
+        let shared = if self.shared {
+            Some(shared::SharedLock::open(&path, &self.permissions)?)
+        } else {
+            None
+        };
+
         let appender = RollingFileAppender {
-            writer: Mutex::new(None),
-            path: path.as_ref().into(),
+            active: Arc::new(Mutex::new(ActiveFile {
+                writer: None,
+                path: active_path,
+            })),
+            path,
+            active_pattern,
             append: self.append,
             encoder: self
                 .encoder
                 .unwrap_or_else(|| Box::<PatternEncoder>::default()),
             policy,
+            durability: self.durability,
+            permissions: self.permissions,
+            shared,
+            bytes_written: AtomicU64::new(0),
+            rolls: AtomicU64::new(0),
         };
 
-        if let Some(parent) = appender.path.parent() {
-            fs::create_dir_all(parent)?;
-        }
+        appender.permissions.create_parent(&appender.path)?;
 
         // open the log file immediately
-        appender.get_writer(&mut appender.writer.lock())?;
+        appender.get_writer(&mut appender.active.lock())?;
+
+        appender.durability.spawn_flusher(
+            Arc::downgrade(&appender.active),
+            |active: &mut ActiveFile, interval| {
+                if let Some(writer) = &mut active.writer {
+                    writer.pending.flush_idle(&mut writer.file, interval);
+                }
+            },
+        )?;
 
         Ok(appender)
     }
@@ -312,6 +639,14 @@ impl RollingFileAppenderBuilder {
 /// # $ENV{name_here} will NOT be replaced in the path.
 /// path: log/foo.log
 ///
+/// # A pattern for the name of the active log file. If set, the appender writes
+/// # to a file named after the local time it was opened, and `path` is a
+/// # symlink which is atomically replaced to point at the active file whenever
+/// # a new one is opened. It must contain at least one `{d(format)}` token,
+/// # formatted with the `strftime`-style format; `{d}` is short for
+/// # `{d(%Y-%m-%d)}`. Optional.
+/// active_file: log/foo-{d(%Y-%m-%dT%H)}.log
+///
 /// # Specifies if the appender should append to or truncate the log file if it
 /// # already exists. Defaults to `true`.
 /// append: true
@@ -320,6 +655,41 @@ impl RollingFileAppenderBuilder {
 /// encoder:
 ///   kind: pattern
 ///
+/// # The size of the buffer records are written to before they are flushed to
+/// # the file. Defaults to 1 kb.
+/// buffer_size: 64 kb
+///
+/// # When the buffer is flushed to the file: `always` after every record, after
+/// # records at or above a level like `warn`, or at most once per interval like
+/// # `5 seconds`. Unless it is `always`, a background thread also flushes buffers
+/// # which haven't been flushed for the interval, or a second. Defaults to `always`.
+/// flush: always
+///
+/// # When the file is synced to the disk: `none`, after every N records like
+/// # `100`, or after records at or above a level like `error`. Defaults to `none`.
+/// sync: none
+///
+/// # The octal modes of the log files and directories the appender creates,
+/// # which must be quoted. Compressed archives keep the mode of the log file.
+/// # Unix only. Default to the process's umask.
+/// mode: "0640"
+/// dir_mode: "0750"
+///
+/// # The user id and the group name or id owning the files and directories
+/// # the appender creates. Unix only. Optional.
+/// uid: 1000
+/// group: adm
+///
+/// # Specifies if missing parent directories of the log files are created.
+/// # Defaults to `true`.
+/// create_dirs: true
+///
+/// # Specifies if several processes write to and roll the log file. Writes and
+/// # rolls then hold an advisory lock on `<path>.lock`, and the appender reopens
+/// # the log file after another process rolls it. Requires `append: true`, and
+/// # doesn't support `active_file`. Unix only. Defaults to `false`.
+/// shared: false
+///
 /// # The policy which handles rotation of the log file. Required.
 /// policy:
 ///   # Identifies which policy is to be used. If no kind is specified, it will
@@ -354,6 +724,31 @@ impl Deserialize for RollingFileAppenderDeserializer {
         if let Some(append) = config.append {
             builder = builder.append(append);
         }
+        if let Some(pattern) = config.active_file {
+            builder = builder.active_file_pattern(&pattern);
+        }
+        if let Some(buffer_size) = config.buffer_size {
+            builder = builder.buffer_size(buffer_size as usize);
+        }
+        builder = builder.flush(config.flush).sync(config.sync);
+        if let Some(mode) = config.mode {
+            builder = builder.mode(mode);
+        }
+        if let Some(mode) = config.dir_mode {
+            builder = builder.dir_mode(mode);
+        }
+        if let Some(uid) = config.uid {
+            builder = builder.uid(uid);
+        }
+        if let Some(group) = config.group {
+            builder = builder.gid(group.gid()?);
+        }
+        if let Some(create_dirs) = config.create_dirs {
+            builder = builder.create_dirs(create_dirs);
+        }
+        if let Some(shared) = config.shared {
+            builder = builder.shared(shared);
+        }
         if let Some(encoder) = config.encoder {
             let encoder = deserializers.deserialize(&encoder.kind, encoder.config)?;
             builder = builder.encoder(encoder);
@@ -368,7 +763,7 @@ impl Deserialize for RollingFileAppenderDeserializer {
 #[cfg(test)]
 mod test {
     use std::{
-        fs::File,
+        fs::{self, File},
         io::{Read, Write},
     };
 
@@ -474,4 +869,316 @@ appenders:
             .unwrap();
         assert_eq!(contents, b"");
     }
+
+    #[derive(Debug)]
+    struct DeletePolicy;
+
+    impl Policy for DeletePolicy {
+        fn process(&self, file: &mut LogFile) -> anyhow::Result<()> {
+            file.roll();
+            fs::remove_file(file.path())?;
+            Ok(())
+        }
+        fn is_pre_process(&self) -> bool {
+            false
+        }
+    }
+
+    /// A policy which only rolls when asked to.
+    #[derive(Debug)]
+    struct ManualPolicy;
+
+    impl Policy for ManualPolicy {
+        fn process(&self, _: &mut LogFile) -> anyhow::Result<()> {
+            Ok(())
+        }
+        fn roll(&self, file: &mut LogFile) -> anyhow::Result<()> {
+            file.roll();
+            fs::remove_file(file.path())?;
+            Ok(())
+        }
+        fn is_pre_process(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn roll() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("roll.log");
+        let appender = RollingFileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build(&path, Box::new(ManualPolicy))
+            .unwrap();
+        File::create(&path).unwrap().write_all(b"hello").unwrap();
+
+        Append::roll(&appender).unwrap();
+        assert!(!path.exists());
+        assert_eq!(appender.stats().rolls, Some(1));
+
+        // Nothing was written since, so there is nothing to roll.
+        Append::roll(&appender).unwrap();
+        assert!(!path.exists());
+        assert_eq!(appender.stats().rolls, Some(1));
+
+        appender
+            .append(&Record::builder().args(format_args!("world")).build())
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"world");
+
+        let appender = RollingFileAppender::builder()
+            .build(dir.path().join("nop.log"), Box::new(NopPolicy))
+            .unwrap();
+        assert!(appender.roll().is_err());
+        assert_eq!(appender.stats().rolls, Some(0));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs").join("mode.log");
+        let appender = RollingFileAppender::builder()
+            .mode(0o640)
+            .dir_mode(0o750)
+            .build(&path, Box::new(ManualPolicy))
+            .unwrap();
+        assert_eq!(mode(&path), 0o640);
+        assert_eq!(mode(path.parent().unwrap()), 0o750);
+
+        appender.roll().unwrap();
+        appender
+            .append(&Record::builder().args(format_args!("hello")).build())
+            .unwrap();
+        assert_eq!(mode(&path), 0o640);
+
+        assert!(RollingFileAppender::builder()
+            .create_dirs(false)
+            .build(
+                dir.path().join("missing").join("mode.log"),
+                Box::new(ManualPolicy)
+            )
+            .is_err());
+    }
+
+    /// A policy which moves the log file aside when asked to roll.
+    #[derive(Debug)]
+    struct MovePolicy(PathBuf);
+
+    impl Policy for MovePolicy {
+        fn process(&self, _: &mut LogFile) -> anyhow::Result<()> {
+            Ok(())
+        }
+        fn roll(&self, file: &mut LogFile) -> anyhow::Result<()> {
+            file.roll();
+            fs::rename(file.path(), &self.0)?;
+            Ok(())
+        }
+        fn is_pre_process(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn active_file_pattern() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("current.log");
+        let archive = dir.path().join("archive.log");
+        let pattern = dir.path().join("app-{d(%Y)}.log");
+        let build = || {
+            RollingFileAppender::builder()
+                .encoder(Box::new(PatternEncoder::new("{m}")))
+                .active_file_pattern(pattern.to_str().unwrap())
+                .build(&link, Box::new(MovePolicy(archive.clone())))
+        };
+        let log = |appender: &RollingFileAppender, msg: &str| {
+            appender
+                .append(&Record::builder().args(format_args!("{}", msg)).build())
+                .unwrap();
+        };
+
+        let appender = build().unwrap();
+        let active = dir
+            .path()
+            .join(format!("app-{}.log", Local::now().format("%Y")));
+        assert_eq!(fs::read_link(&link).unwrap(), active.file_name().unwrap());
+
+        log(&appender, "hello");
+        assert_eq!(fs::read(&link).unwrap(), b"hello");
+
+        appender.roll().unwrap();
+        assert_eq!(fs::read(&archive).unwrap(), b"hello");
+        log(&appender, "world");
+        assert_eq!(fs::read(&active).unwrap(), b"world");
+        drop(appender);
+
+        // A new appender keeps writing to the file the link points to.
+        let appender = build().unwrap();
+        log(&appender, "!");
+        assert_eq!(fs::read(&link).unwrap(), b"world!");
+
+        // The link never replaces a log file.
+        fs::remove_file(&link).unwrap();
+        fs::write(&link, b"log").unwrap();
+        assert!(build().is_err());
+    }
+
+    #[test]
+    fn stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let appender = RollingFileAppender::builder()
+            .encoder(Box::new(PatternEncoder::new("{m}")))
+            .build(dir.path().join("stats.log"), Box::new(DeletePolicy))
+            .unwrap();
+
+        for _ in 0..3 {
+            appender
+                .append(&Record::builder().args(format_args!("hello")).build())
+                .unwrap();
+        }
+
+        let stats = appender.stats();
+        assert_eq!(stats.bytes_written, Some(15));
+        assert_eq!(stats.rolls, Some(3));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn shared() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shared.log");
+        let rolled = dir.path().join("shared.log.1");
+        let build = || {
+            RollingFileAppender::builder()
+                .encoder(Box::new(PatternEncoder::new("{m}")))
+                .shared(true)
+                .build(&path, Box::new(MovePolicy(rolled.clone())))
+                .unwrap()
+        };
+        let log = |appender: &RollingFileAppender, msg: &str| {
+            appender
+                .append(&Record::builder().args(format_args!("{}", msg)).build())
+                .unwrap();
+        };
+
+        // Stands in for two processes.
+        let a = build();
+        let b = build();
+        log(&a, "a");
+        log(&b, "b");
+        b.roll().unwrap();
+        log(&a, "c");
+        log(&b, "d");
+
+        assert_eq!(fs::read(&rolled).unwrap(), b"ab");
+        assert_eq!(fs::read(&path).unwrap(), b"cd");
+        assert!(dir.path().join("shared.log.lock").exists());
+
+        assert!(RollingFileAppender::builder()
+            .shared(true)
+            .append(false)
+            .build(&path, Box::new(NopPolicy))
+            .is_err());
+    }
+
+    /// Set in the environment of the processes spawned by `shared_processes`
+    /// to the directory they log to.
+    #[cfg(all(
+        target_os = "linux",
+        feature = "compound_policy",
+        feature = "size_trigger",
+        feature = "fixed_window_roller"
+    ))]
+    const SHARED_DIR: &str = "LOG4RS_TEST_SHARED_DIR";
+
+    #[test]
+    #[cfg(all(
+        target_os = "linux",
+        feature = "compound_policy",
+        feature = "size_trigger",
+        feature = "fixed_window_roller"
+    ))]
+    fn shared_processes() {
+        use std::{
+            collections::{BTreeMap, BTreeSet},
+            env,
+            process::{Command, Stdio},
+        };
+
+        use crate::append::rolling_file::policy::compound::{
+            roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger, CompoundPolicy,
+        };
+
+        const PROCESSES: usize = 4;
+        const RECORDS: usize = 500;
+
+        if let Some(dir) = env::var_os(SHARED_DIR) {
+            let dir = PathBuf::from(dir);
+            let roller = FixedWindowRoller::builder()
+                .build(dir.join("app.{}.log").to_str().unwrap(), 1000)
+                .unwrap();
+            let policy = CompoundPolicy::new(Box::new(SizeTrigger::new(512)), Box::new(roller));
+            let appender = RollingFileAppender::builder()
+                .encoder(Box::new(PatternEncoder::new("{m}{n}")))
+                .shared(true)
+                .build(dir.join("app.log"), Box::new(policy))
+                .unwrap();
+
+            let id = std::process::id();
+            for i in 0..RECORDS {
+                appender
+                    .append(&Record::builder().args(format_args!("{} {}", id, i)).build())
+                    .unwrap();
+            }
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let children = (0..PROCESSES)
+            .map(|_| {
+                Command::new(env::current_exe().unwrap())
+                    .args([
+                        "--exact",
+                        "append::rolling_file::test::shared_processes",
+                        "--test-threads=1",
+                        "--quiet",
+                    ])
+                    .env(SHARED_DIR, dir.path())
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        for mut child in children {
+            assert!(child.wait().unwrap().success());
+        }
+
+        let mut records = BTreeMap::new();
+        let mut files = 0;
+        for entry in fs::read_dir(dir.path()).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(true, |e| e != "log") {
+                continue;
+            }
+            files += 1;
+            for line in fs::read_to_string(&path).unwrap().lines() {
+                *records.entry(line.to_owned()).or_insert(0) += 1;
+            }
+        }
+
+        // Every record is written exactly once, across many rolls.
+        assert!(files > 10);
+        assert_eq!(records.len(), PROCESSES * RECORDS);
+        assert!(records.values().all(|&n| n == 1));
+        let processes = records
+            .keys()
+            .map(|r| r.split(' ').next().unwrap())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(processes.len(), PROCESSES);
+    }
 }
@@ -1,6 +1,8 @@
 //! The compound rolling policy.
 //!
 //! Requires the `compound_policy` feature.
+use std::path::Path;
+
 #[cfg(feature = "config_parsing")]
 use serde::de;
 #[cfg(feature = "config_parsing")]
@@ -9,11 +11,20 @@ use serde_value::Value;
 use std::collections::BTreeMap;
 
 use crate::append::rolling_file::{
-    policy::{compound::roll::Roll, Policy},
+    policy::{
+        compound::roll::{
+            hook::{PostRoll, PostRollCommand},
+            Roll,
+        },
+        Policy,
+    },
     LogFile,
 };
 #[cfg(feature = "config_parsing")]
-use crate::config::{Deserialize, Deserializers};
+use crate::{
+    append::rolling_file::policy::compound::roll::hook::PostRollCommandConfig,
+    config::{Deserialize, Deserializers},
+};
 
 pub mod roll;
 pub mod trigger;
@@ -23,20 +34,22 @@ pub mod trigger;
 #[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct CompoundPolicyConfig {
-    trigger: Trigger,
+    trigger: TriggerConfig,
     roller: Roller,
+    post_roll_command: Option<PostRollCommandConfig>,
 }
 
+/// The kind and configuration of a trigger.
 #[cfg(feature = "config_parsing")]
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
-struct Trigger {
-    kind: String,
-    config: Value,
+pub(crate) struct TriggerConfig {
+    pub(crate) kind: String,
+    pub(crate) config: Value,
 }
 
 #[cfg(feature = "config_parsing")]
-impl<'de> serde::Deserialize<'de> for Trigger {
-    fn deserialize<D>(d: D) -> Result<Trigger, D::Error>
+impl<'de> serde::Deserialize<'de> for TriggerConfig {
+    fn deserialize<D>(d: D) -> Result<TriggerConfig, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
@@ -47,7 +60,7 @@ impl<'de> serde::Deserialize<'de> for Trigger {
             None => return Err(de::Error::missing_field("kind")),
         };
 
-        Ok(Trigger {
+        Ok(TriggerConfig {
             kind,
             config: Value::Map(map),
         })
@@ -86,28 +99,63 @@ impl<'de> serde::Deserialize<'de> for Roller {
 /// The trigger determines if the log file should roll, for example, by checking
 /// the size of the file. The roller processes the old log file, for example,
 /// by compressing it and moving it to a different location.
+///
+/// A callback and a command may be run on each archive the roller creates,
+/// for example to upload or index it. They are run by the built-in rollers,
+/// on a separate thread once the archive is in place and old archives have
+/// been deleted; the delete roller passes them the path of the deleted log
+/// file.
 #[derive(Debug)]
 pub struct CompoundPolicy {
     trigger: Box<dyn trigger::Trigger>,
     roller: Box<dyn Roll>,
+    post_roll: PostRoll,
 }
 
 impl CompoundPolicy {
     /// Creates a new `CompoundPolicy`.
     pub fn new(trigger: Box<dyn trigger::Trigger>, roller: Box<dyn Roll>) -> CompoundPolicy {
-        CompoundPolicy { trigger, roller }
+        CompoundPolicy {
+            trigger,
+            roller,
+            post_roll: PostRoll::default(),
+        }
+    }
+
+    /// Sets a callback which is run with the path of each new archive.
+    ///
+    /// A panicking callback is reported to the logger's error handler.
+    pub fn post_roll<F>(mut self, callback: F) -> CompoundPolicy
+    where
+        F: Fn(&Path) + Send + Sync + 'static,
+    {
+        self.post_roll.set_callback(callback);
+        self
+    }
+
+    /// Sets a command which is run with the path of each new archive.
+    ///
+    /// Failures, including timeouts, are reported to the logger's error
+    /// handler.
+    pub fn post_roll_command(mut self, command: PostRollCommand) -> CompoundPolicy {
+        self.post_roll.set_command(command);
+        self
     }
 }
 
 impl Policy for CompoundPolicy {
     fn process(&self, log: &mut LogFile) -> anyhow::Result<()> {
         if self.trigger.trigger(log)? {
-            log.roll();
-            self.roller.roll(log.path())?;
+            self.roll(log)?;
         }
         Ok(())
     }
 
+    fn roll(&self, log: &mut LogFile) -> anyhow::Result<()> {
+        log.roll();
+        self.post_roll.enter(|| self.roller.roll(log.path()))
+    }
+
     fn is_pre_process(&self) -> bool {
         self.trigger.is_pre_process()
     }
@@ -138,6 +186,14 @@ impl Policy for CompoundPolicy {
 ///
 ///   # The remainder of the configuration is passed to the roller's
 ///   # deserializer, and will vary based on the kind of roller.
+///
+/// # A command run on each new archive, with the archive's path appended to its
+/// # arguments. Failures are reported to the logger's error handler. Optional.
+/// post_roll_command:
+///   program: /usr/local/bin/ship-log
+///   args: [--bucket, logs]
+///   # How long the command may run before it is killed. Defaults to 60 seconds.
+///   timeout: 30 seconds
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
@@ -156,6 +212,10 @@ impl Deserialize for CompoundPolicyDeserializer {
     ) -> anyhow::Result<Box<dyn Policy>> {
         let trigger = deserializers.deserialize(&config.trigger.kind, config.trigger.config)?;
         let roller = deserializers.deserialize(&config.roller.kind, config.roller.config)?;
-        Ok(Box::new(CompoundPolicy::new(trigger, roller)))
+        let mut policy = CompoundPolicy::new(trigger, roller);
+        if let Some(command) = config.post_roll_command {
+            policy = policy.post_roll_command(command.into());
+        }
+        Ok(Box::new(policy))
     }
 }
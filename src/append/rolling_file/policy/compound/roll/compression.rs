@@ -0,0 +1,271 @@
+//! Archive compression shared by the rollers.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::Path,
+};
+
+use crate::append::permissions;
+
+/// The codec used to compress archives, along with its compression level.
+///
+/// A level of `None` selects the codec's default level.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub(crate) enum Compression {
+    None,
+    #[cfg(feature = "gzip")]
+    Gzip(Option<u32>),
+    #[cfg(feature = "zstd")]
+    Zstd(Option<u32>),
+    #[cfg(feature = "xz")]
+    Xz(Option<u32>),
+    #[cfg(feature = "bzip2")]
+    Bzip2(Option<u32>),
+}
+
+impl Compression {
+    /// Selects the compression from the extension of an archive pattern.
+    ///
+    /// `.gz`, `.zst`, `.xz` and `.bz2` select gzip, zstd, xz and bzip2
+    /// respectively, each of which requires the Cargo feature of the same
+    /// name. Any other extension leaves archives uncompressed, in which case
+    /// no level may be given.
+    pub(crate) fn from_pattern(pattern: &str, level: Option<u32>) -> anyhow::Result<Compression> {
+        let extension = Path::new(pattern)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+
+        match extension {
+            #[cfg(feature = "gzip")]
+            "gz" => with_level(Compression::Gzip, "gzip", level, 0..=9),
+            #[cfg(not(feature = "gzip"))]
+            "gz" => anyhow::bail!("gzip compression requires the `gzip` feature"),
+            #[cfg(feature = "zstd")]
+            "zst" => with_level(Compression::Zstd, "zstd", level, 1..=22),
+            #[cfg(not(feature = "zstd"))]
+            "zst" => anyhow::bail!("zstd compression requires the `zstd` feature"),
+            #[cfg(feature = "xz")]
+            "xz" => with_level(Compression::Xz, "xz", level, 0..=9),
+            #[cfg(not(feature = "xz"))]
+            "xz" => anyhow::bail!("xz compression requires the `xz` feature"),
+            #[cfg(feature = "bzip2")]
+            "bz2" => with_level(Compression::Bzip2, "bzip2", level, 1..=9),
+            #[cfg(not(feature = "bzip2"))]
+            "bz2" => anyhow::bail!("bzip2 compression requires the `bzip2` feature"),
+            _ if level.is_some() => {
+                anyhow::bail!(
+                    "a compression level requires a `.gz`, `.zst`, `.xz` or `.bz2` pattern"
+                )
+            }
+            _ => Ok(Compression::None),
+        }
+    }
+
+    /// Moves `src` to `dst`, compressing it along the way.
+    ///
+    /// If `append` is set and `dst` already exists, the compressed contents
+    /// of `src` are appended to it rather than replacing it. The supported
+    /// formats all allow concatenated streams. Otherwise, `dst` is given the
+    /// permissions and owner of `src`.
+    pub(crate) fn compress(&self, src: &Path, dst: &Path, append: bool) -> io::Result<()> {
+        let replace = !(append && dst.exists());
+        if *self == Compression::None && replace {
+            return move_file(src, dst);
+        }
+
+        let mut i = File::open(src)?;
+        let o = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(dst)?;
+        if replace {
+            permissions::copy(&i.metadata()?, dst)?;
+        }
+
+        match *self {
+            Compression::None => {
+                let mut o = o;
+                io::copy(&mut i, &mut o)?;
+            }
+            #[cfg(feature = "gzip")]
+            Compression::Gzip(level) => {
+                use flate2::write::GzEncoder;
+
+                let level =
+                    level.map_or_else(flate2::Compression::default, flate2::Compression::new);
+                let mut o = GzEncoder::new(o, level);
+                io::copy(&mut i, &mut o)?;
+                drop(o.finish()?);
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => {
+                // zstd interprets 0 as its default level
+                let mut o = zstd::Encoder::new(o, level.unwrap_or(0) as i32)?;
+                io::copy(&mut i, &mut o)?;
+                drop(o.finish()?);
+            }
+            #[cfg(feature = "xz")]
+            Compression::Xz(level) => {
+                use xz2::write::XzEncoder;
+
+                let mut o = XzEncoder::new(o, level.unwrap_or(6));
+                io::copy(&mut i, &mut o)?;
+                drop(o.finish()?);
+            }
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2(level) => {
+                use bzip2::write::BzEncoder;
+
+                let level = level.map_or_else(bzip2::Compression::default, bzip2::Compression::new);
+                let mut o = BzEncoder::new(o, level);
+                io::copy(&mut i, &mut o)?;
+                drop(o.finish()?);
+            }
+        }
+        drop(i); // needs to happen before remove_file call on Windows
+
+        fs::remove_file(src)
+    }
+}
+
+#[cfg(any(feature = "gzip", feature = "zstd", feature = "xz", feature = "bzip2"))]
+fn with_level(
+    compression: fn(Option<u32>) -> Compression,
+    name: &str,
+    level: Option<u32>,
+    levels: std::ops::RangeInclusive<u32>,
+) -> anyhow::Result<Compression> {
+    match level {
+        Some(level) if !levels.contains(&level) => anyhow::bail!(
+            "{} compression level must be between {} and {}",
+            name,
+            levels.start(),
+            levels.end()
+        ),
+        _ => Ok(compression(level)),
+    }
+}
+
+pub(crate) fn move_file<P, Q>(src: P, dst: Q) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    // first try a rename
+    match fs::rename(src.as_ref(), dst.as_ref()) {
+        Ok(()) => return Ok(()),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(_) => {}
+    }
+
+    // fall back to a copy and delete if src and dst are on different mounts
+    fs::copy(src.as_ref(), dst.as_ref()).and_then(|_| fs::remove_file(src.as_ref()))
+}
+
+#[cfg(feature = "background_rotation")]
+pub(crate) fn make_temp_file_name<P>(file: P) -> std::path::PathBuf
+where
+    P: AsRef<Path>,
+{
+    let mut n = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap_or_else(|_| std::time::Duration::from_secs(0))
+        .as_secs();
+    let mut temp = file.as_ref().to_path_buf();
+    temp.set_extension(format!("{}", n));
+    while temp.exists() {
+        n += 1;
+        temp.set_extension(format!("{}", n));
+    }
+    temp
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+
+    use super::*;
+
+    #[test]
+    fn select() {
+        assert_eq!(
+            Compression::from_pattern("foo.{}.log", None).unwrap(),
+            Compression::None
+        );
+        assert!(Compression::from_pattern("foo.{}.log", Some(3)).is_err());
+
+        for (pattern, feature) in [
+            ("foo.{}.gz", cfg!(feature = "gzip")),
+            ("foo.{}.zst", cfg!(feature = "zstd")),
+            ("foo.{}.xz", cfg!(feature = "xz")),
+            ("foo.{}.bz2", cfg!(feature = "bzip2")),
+        ] {
+            assert_eq!(Compression::from_pattern(pattern, None).is_ok(), feature);
+            assert_eq!(Compression::from_pattern(pattern, Some(5)).is_ok(), feature);
+            assert!(Compression::from_pattern(pattern, Some(23)).is_err());
+        }
+    }
+
+    /// Compresses two files into the same archive and decompresses it.
+    #[allow(dead_code)]
+    fn round_trip<F>(pattern: &str, level: Option<u32>, decompress: F)
+    where
+        F: Fn(File) -> Box<dyn Read>,
+    {
+        let dir = tempfile::tempdir().unwrap();
+        let compression = Compression::from_pattern(pattern, level).unwrap();
+        let src = dir.path().join("foo.log");
+        let dst = dir.path().join(pattern);
+
+        for contents in [b"first\n", b"other\n"] {
+            File::create(&src).unwrap().write_all(contents).unwrap();
+            compression.compress(&src, &dst, true).unwrap();
+            assert!(!src.exists());
+        }
+
+        let mut actual = String::new();
+        decompress(File::open(&dst).unwrap())
+            .read_to_string(&mut actual)
+            .unwrap();
+        assert_eq!(actual, "first\nother\n");
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn gzip() {
+        round_trip("foo.gz", Some(9), |f| {
+            Box::new(flate2::read::MultiGzDecoder::new(f))
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn zstd() {
+        round_trip("foo.zst", None, |f| {
+            Box::new(zstd::Decoder::new(f).unwrap())
+        });
+        round_trip("foo.zst", Some(19), |f| {
+            Box::new(zstd::Decoder::new(f).unwrap())
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "xz")]
+    fn xz() {
+        round_trip("foo.xz", Some(1), |f| {
+            Box::new(xz2::read::XzDecoder::new_multi_decoder(f))
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "bzip2")]
+    fn bzip2() {
+        round_trip("foo.bz2", None, |f| {
+            Box::new(bzip2::read::MultiBzDecoder::new(f))
+        });
+    }
+}
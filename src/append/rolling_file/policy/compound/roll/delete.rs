@@ -4,7 +4,7 @@
 
 use std::{fs, path::Path};
 
-use crate::append::rolling_file::policy::compound::roll::Roll;
+use crate::append::rolling_file::policy::compound::roll::{hook::PostRoll, Roll};
 #[cfg(feature = "config_parsing")]
 use crate::config::{Deserialize, Deserializers};
 
@@ -18,12 +18,18 @@ pub struct DeleteRollerConfig {
 }
 
 /// A roller which deletes the log file.
+///
+/// The post-roll hooks of the compound policy are run after each deletion, on
+/// a separate thread. They receive the path the log file had, which no longer
+/// exists.
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
 pub struct DeleteRoller(());
 
 impl Roll for DeleteRoller {
     fn roll(&self, file: &Path) -> anyhow::Result<()> {
-        fs::remove_file(file).map_err(Into::into)
+        fs::remove_file(file)?;
+        PostRoll::current().spawn(file);
+        Ok(())
     }
 }
 
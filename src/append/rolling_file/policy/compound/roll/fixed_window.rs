@@ -10,11 +10,30 @@ use std::sync::Arc;
 use std::{
     fs, io,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
-use crate::append::{env_util::expand_env_vars, rolling_file::policy::compound::roll::Roll};
+#[cfg(feature = "background_rotation")]
+use crate::append::rolling_file::{
+    policy::compound::roll::compression::make_temp_file_name, shared,
+};
+use crate::append::{
+    env_util::expand_env_vars,
+    permissions,
+    rolling_file::policy::compound::roll::{
+        compression::{move_file, Compression},
+        hook::PostRoll,
+        retention::{ArchivedFile, Retention},
+        Roll,
+    },
+};
+#[cfg(feature = "background_rotation")]
+use crate::error::{BackgroundReporter, ErrorEvent, ErrorKind};
 #[cfg(feature = "config_parsing")]
-use crate::config::{Deserialize, Deserializers};
+use crate::{
+    append::permissions::de_mode,
+    config::{Deserialize, Deserializers},
+};
 
 /// Configuration for the fixed window roller.
 #[cfg(feature = "config_parsing")]
@@ -24,38 +43,15 @@ pub struct FixedWindowRollerConfig {
     pattern: String,
     base: Option<u32>,
     count: u32,
-}
-
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
-enum Compression {
-    None,
-    #[cfg(feature = "gzip")]
-    Gzip,
-}
-
-impl Compression {
-    fn compress(&self, src: &Path, dst: &str) -> io::Result<()> {
-        match *self {
-            Compression::None => move_file(src, dst),
-            #[cfg(feature = "gzip")]
-            Compression::Gzip => {
-                #[cfg(feature = "flate2")]
-                use flate2::write::GzEncoder;
-                use std::fs::File;
-
-                let mut i = File::open(src)?;
-
-                let o = File::create(dst)?;
-                let mut o = GzEncoder::new(o, flate2::Compression::default());
-
-                io::copy(&mut i, &mut o)?;
-                drop(o.finish()?);
-                drop(i); // needs to happen before remove_file call on Windows
-
-                fs::remove_file(src)
-            }
-        }
-    }
+    compression_level: Option<u32>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    max_age: Option<Duration>,
+    #[serde(deserialize_with = "crate::config::de_opt_size", default)]
+    max_total_size: Option<u64>,
+    #[serde(deserialize_with = "crate::config::de_opt_size", default)]
+    min_free_disk: Option<u64>,
+    #[serde(deserialize_with = "de_mode", default)]
+    mode: Option<u32>,
 }
 
 /// A roller which maintains a fixed window of archived log files.
@@ -75,8 +71,25 @@ impl Compression {
 /// `archive/foo.1.log`, and the new log file will be renamed to
 /// `archive/foo.0.log`.
 ///
-/// If the file extension of the pattern is `.gz` and the `gzip` Cargo feature
-/// is enabled, the archive files will be gzip-compressed.
+/// If the file extension of the pattern is `.gz`, `.zst`, `.xz` or `.bz2`,
+/// the archive files will be compressed with gzip, zstd, xz or bzip2
+/// respectively. Each codec requires the Cargo feature of the same name.
+///
+/// In addition to the count, the archives may be limited by age, by their
+/// total size, and by the free space left on the disk. After each roll, the
+/// oldest archives are deleted until every configured limit is satisfied.
+///
+/// Archives keep the permissions and owner of the log file they were made
+/// from, unless a mode is configured for them, e.g. to make them read-only.
+///
+/// The post-roll hooks of the compound policy are run on each new archive
+/// once it is in place and old archives have been deleted. They run on a
+/// separate thread,
+/// and neither logging nor the next roll waits for them, so a hook which is
+/// still running when the log rolls again may find the archive renamed. A
+/// panicking callback is reported to the logger's error handler. If the
+/// count is 0, the log file is deleted without being archived, and no hooks
+/// are run.
 ///
 /// Note that this roller will have to rename every archived file every time the
 /// log rolls over. Performance may be negatively impacted by specifying a large
@@ -87,6 +100,8 @@ pub struct FixedWindowRoller {
     compression: Compression,
     base: u32,
     count: u32,
+    retention: Retention,
+    mode: Option<u32>,
     #[cfg(feature = "background_rotation")]
     cond_pair: Arc<(Mutex<bool>, Condvar)>,
 }
@@ -94,7 +109,7 @@ pub struct FixedWindowRoller {
 impl FixedWindowRoller {
     /// Returns a new builder for the `FixedWindowRoller`.
     pub fn builder() -> FixedWindowRollerBuilder {
-        FixedWindowRollerBuilder { base: 0 }
+        FixedWindowRollerBuilder::default()
     }
 }
 
@@ -105,13 +120,8 @@ impl Roll for FixedWindowRoller {
             return fs::remove_file(file).map_err(Into::into);
         }
 
-        rotate(
-            self.pattern.clone(),
-            self.compression,
-            self.base,
-            self.count,
-            file.to_path_buf(),
-        )?;
+        let archive = rotate(self, file.to_path_buf())?;
+        PostRoll::current().spawn(&archive);
 
         Ok(())
     }
@@ -122,6 +132,12 @@ impl Roll for FixedWindowRoller {
             return fs::remove_file(file).map_err(Into::into);
         }
 
+        if shared::is_locked() {
+            let archive = rotate(self, file.to_path_buf())?;
+            PostRoll::current().spawn(&archive);
+            return Ok(());
+        }
+
         // rename the file
         let temp = make_temp_file_name(file);
         move_file(file, &temp)?;
@@ -135,70 +151,51 @@ impl Roll for FixedWindowRoller {
         *ready = false;
         drop(ready);
 
-        let pattern = self.pattern.clone();
-        let compression = self.compression;
-        let base = self.base;
-        let count = self.count;
-        let cond_pair = self.cond_pair.clone();
+        let roller = self.clone();
+        let reporter = BackgroundReporter::current();
+        let post_roll = PostRoll::current();
         // rotate in the separate thread
         std::thread::spawn(move || {
-            let (lock, cvar) = &*cond_pair;
-            let mut ready = lock.lock();
-
-            if let Err(e) = rotate(pattern, compression, base, count, temp) {
-                use std::io::Write;
-                let _ = writeln!(io::stderr(), "log4rs, error rotating: {}", e);
-            }
-            *ready = true;
-            cvar.notify_one();
+            reporter.enter(|| {
+                let result = {
+                    let (lock, cvar) = &*roller.cond_pair;
+                    let mut ready = lock.lock();
+                    let result = rotate(&roller, temp);
+                    *ready = true;
+                    cvar.notify_one();
+                    result
+                };
+
+                // The hooks run once the next roll may proceed, so that a slow
+                // or panicking hook doesn't hold it up.
+                match result {
+                    Ok(archive) => post_roll.run(&archive),
+                    Err(e) => {
+                        let e = anyhow::anyhow!("error rotating log file: {}", e);
+                        reporter.report(&ErrorEvent::new(ErrorKind::Roll, &e));
+                    }
+                }
+            })
         });
 
         Ok(())
     }
 }
 
-fn move_file<P, Q>(src: P, dst: Q) -> io::Result<()>
-where
-    P: AsRef<Path>,
-    Q: AsRef<Path>,
-{
-    // first try a rename
-    match fs::rename(src.as_ref(), dst.as_ref()) {
-        Ok(()) => return Ok(()),
-        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
-        Err(_) => {}
-    }
-
-    // fall back to a copy and delete if src and dst are on different mounts
-    fs::copy(src.as_ref(), dst.as_ref()).and_then(|_| fs::remove_file(src.as_ref()))
-}
-
-#[cfg(feature = "background_rotation")]
-fn make_temp_file_name<P>(file: P) -> PathBuf
-where
-    P: AsRef<Path>,
-{
-    let mut n = std::time::SystemTime::now()
-        .duration_since(std::time::SystemTime::UNIX_EPOCH)
-        .unwrap_or_else(|_| std::time::Duration::from_secs(0))
-        .as_secs();
-    let mut temp = file.as_ref().to_path_buf();
-    temp.set_extension(format!("{}", n));
-    while temp.exists() {
-        n += 1;
-        temp.set_extension(format!("{}", n));
-    }
-    temp
-}
-
 // TODO(eas): compress to tmp file then move into place once prev task is done
-fn rotate(
-    pattern: String,
-    compression: Compression,
-    base: u32,
-    count: u32,
-    file: PathBuf,
-) -> io::Result<()> {
+/// Archives `file`, returning the path of the new archive.
+fn rotate(roller: &FixedWindowRoller, file: PathBuf) -> io::Result<PathBuf> {
+    let FixedWindowRoller {
+        pattern,
+        compression,
+        base,
+        count,
+        retention,
+        mode,
+        ..
+    } = roller;
+    let (base, count) = (*base, *count);
+
     let dst_0 = expand_env_vars(pattern.replace("{}", &base.to_string()));
 
     if let Some(parent) = Path::new(dst_0.as_ref()).parent() {
@@ -209,7 +206,7 @@ fn rotate(
     // directory, so avoid extra filesystem calls in that case.
     let parent_varies = match (
         Path::new(dst_0.as_ref()).parent(),
-        Path::new(expand_env_vars(&pattern).as_ref()).parent(),
+        Path::new(expand_env_vars(pattern).as_ref()).parent(),
     ) {
         (Some(a), Some(b)) => a != b,
         _ => false, // Only case that can actually happen is (None, None)
@@ -228,17 +225,37 @@ fn rotate(
         move_file(src.as_ref(), dst.as_ref())?;
     }
 
-    compression.compress(&file, &dst_0).map_err(|e| {
-        println!("err compressing: {:?}, dst: {:?}", file, dst_0);
-        e
-    })?;
-    Ok(())
+    compression
+        .compress(&file, Path::new(dst_0.as_ref()), false)
+        .map_err(|e| {
+            println!("err compressing: {:?}, dst: {:?}", file, dst_0);
+            e
+        })?;
+
+    if let Some(mode) = mode {
+        permissions::set_mode(Path::new(dst_0.as_ref()), *mode)?;
+    }
+
+    if *retention != Retention::default() {
+        // Oldest first.
+        let mut archives = vec![];
+        for i in (base..base + count).rev() {
+            let path = expand_env_vars(pattern.replace("{}", &i.to_string()));
+            archives.extend(ArchivedFile::open(PathBuf::from(path.as_ref()))?);
+        }
+        retention.apply(archives)?;
+    }
+
+    Ok(PathBuf::from(dst_0.as_ref()))
 }
 
 /// A builder for the `FixedWindowRoller`.
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
 pub struct FixedWindowRollerBuilder {
     base: u32,
+    compression_level: Option<u32>,
+    retention: Retention,
+    mode: Option<u32>,
 }
 
 impl FixedWindowRollerBuilder {
@@ -250,15 +267,60 @@ impl FixedWindowRollerBuilder {
         self
     }
 
+    /// Sets the compression level of archived log files.
+    ///
+    /// The valid levels depend on the codec selected by the pattern: 0-9 for
+    /// gzip and xz, 1-22 for zstd and 1-9 for bzip2. Defaults to the codec's
+    /// default level.
+    pub fn compression_level(mut self, level: u32) -> FixedWindowRollerBuilder {
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// Sets the maximum age of archived log files, by modification time.
+    ///
+    /// Defaults to unlimited.
+    pub fn max_age(mut self, max_age: Duration) -> FixedWindowRollerBuilder {
+        self.retention.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets the maximum total size in bytes of archived log files.
+    ///
+    /// Defaults to unlimited.
+    pub fn max_total_size(mut self, max_total_size: u64) -> FixedWindowRollerBuilder {
+        self.retention.max_total_size = Some(max_total_size);
+        self
+    }
+
+    /// Sets the amount of free disk space in bytes to preserve. Archived log
+    /// files are deleted while less space than this is available, except for
+    /// the newest, which is kept whatever the retention limits.
+    ///
+    /// Defaults to none.
+    pub fn min_free_disk(mut self, min_free_disk: u64) -> FixedWindowRollerBuilder {
+        self.retention.min_free_disk = Some(min_free_disk);
+        self
+    }
+
+    /// Sets the mode of new archives, e.g. `0o440` to make them read-only.
+    ///
+    /// Only supported on Unix. Defaults to the mode of the archived log file.
+    pub fn mode(mut self, mode: u32) -> FixedWindowRollerBuilder {
+        self.mode = Some(mode);
+        self
+    }
+
     /// Constructs a new `FixedWindowRoller`.
     ///
     /// `pattern` is either an absolute path or lacking a leading `/`, relative
     /// to the `cwd` of your application. The pattern must contain at least one
     /// instance of `{}`, all of which will be replaced with an archived log file's index.
     ///
-    /// If the file extension of the pattern is `.gz` and the `gzip` Cargo
-    /// feature is enabled, the archive files will be gzip-compressed.
-    /// If the extension is `.gz` and the `gzip` feature is *not* enabled, an error will be returned.
+    /// If the file extension of the pattern is `.gz`, `.zst`, `.xz` or `.bz2`
+    /// and the `gzip`, `zstd`, `xz` or `bzip2` Cargo feature respectively is
+    /// enabled, the archive files will be compressed with that codec.
+    /// If the codec's feature is *not* enabled, an error will be returned.
     ///
     /// `count` is the maximum number of archived logs to maintain.
     pub fn build(self, pattern: &str, count: u32) -> anyhow::Result<FixedWindowRoller> {
@@ -268,21 +330,15 @@ impl FixedWindowRollerBuilder {
             bail!(msg);
         }
 
-        let compression = match Path::new(pattern).extension() {
-            #[cfg(feature = "gzip")]
-            Some(e) if e == "gz" => Compression::Gzip,
-            #[cfg(not(feature = "gzip"))]
-            Some(e) if e == "gz" => {
-                bail!("gzip compression requires the `gzip` feature");
-            }
-            _ => Compression::None,
-        };
+        let compression = Compression::from_pattern(pattern, self.compression_level)?;
 
         Ok(FixedWindowRoller {
             pattern: pattern.to_owned(),
             compression,
             base: self.base,
             count,
+            retention: self.retention,
+            mode: self.mode,
             #[cfg(feature = "background_rotation")]
             cond_pair: Arc::new((Mutex::new(true), Condvar::new())),
         })
@@ -299,8 +355,9 @@ impl FixedWindowRollerBuilder {
 /// # The filename pattern for archived logs. This is either an absolute path or if lacking a leading `/`,
 /// # relative to the `cwd` of your application. The pattern must contain at least one
 /// # instance of `{}`, all of which will be replaced with an archived log file's index.
-/// # If the file extension of the pattern is `.gz` and the `gzip` Cargo feature
-/// # is enabled, the archive files will be gzip-compressed.
+/// # If the file extension of the pattern is `.gz`, `.zst`, `.xz` or `.bz2`, the archive
+/// # files will be compressed with gzip, zstd, xz or bzip2 respectively. Each codec
+/// # requires the Cargo feature of the same name.
 /// # Required.
 /// pattern: archive/foo.{}.log
 ///
@@ -309,6 +366,24 @@ impl FixedWindowRollerBuilder {
 ///
 /// # The base value for archived log indices. Defaults to 0.
 /// base: 1
+///
+/// # The compression level of archived logs, if the pattern selects a codec: 0-9 for
+/// # gzip and xz, 1-22 for zstd and 1-9 for bzip2. Defaults to the codec's default level.
+/// compression_level: 9
+///
+/// # The maximum age of archived logs, by modification time. Defaults to unlimited.
+/// max_age: 30 days
+///
+/// # The maximum total size of archived logs. Defaults to unlimited.
+/// max_total_size: 1 gb
+///
+/// # Archived logs are deleted, oldest first, while less than this much disk space
+/// # is free. The newest archived log is always kept. Defaults to none.
+/// min_free_disk: 500 mb
+///
+/// # The octal mode of new archives, which must be quoted, e.g. "0440" to make
+/// # them read-only. Unix only. Defaults to the mode of the archived log file.
+/// mode: "0440"
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
@@ -329,6 +404,21 @@ impl Deserialize for FixedWindowRollerDeserializer {
         if let Some(base) = config.base {
             builder = builder.base(base);
         }
+        if let Some(level) = config.compression_level {
+            builder = builder.compression_level(level);
+        }
+        if let Some(max_age) = config.max_age {
+            builder = builder.max_age(max_age);
+        }
+        if let Some(max_total_size) = config.max_total_size {
+            builder = builder.max_total_size(max_total_size);
+        }
+        if let Some(min_free_disk) = config.min_free_disk {
+            builder = builder.min_free_disk(min_free_disk);
+        }
+        if let Some(mode) = config.mode {
+            builder = builder.mode(mode);
+        }
 
         Ok(Box::new(builder.build(&config.pattern, config.count)?))
     }
@@ -342,7 +432,7 @@ mod test {
     };
 
     use super::*;
-    use crate::append::rolling_file::policy::compound::roll::Roll;
+    use crate::append::rolling_file::policy::compound::roll::{hook::wait_until, Roll};
 
     #[cfg(feature = "background_rotation")]
     fn wait_for_roller(roller: &FixedWindowRoller) {
@@ -451,7 +541,7 @@ mod test {
         wait_for_roller(&roller);
         assert!(!file.exists());
         contents.clear();
-        File::open(dir.path().join(&format!("{}.{}", fname, base + 1)))
+        File::open(dir.path().join(format!("{}.{}", fname, base + 1)))
             .unwrap()
             .read_to_end(&mut contents)
             .unwrap();
@@ -596,4 +686,161 @@ mod test {
         //Check the new rolled file has the same contents as the old one
         assert_eq!(contents, fcontent);
     }
+
+    #[test]
+    fn max_total_size() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let base = dir.path().to_str().unwrap();
+        let roller = FixedWindowRoller::builder()
+            .max_total_size(10)
+            .build(&format!("{}/foo.log.{{}}", base), 5)
+            .unwrap();
+
+        let file = dir.path().join("foo.log");
+        for contents in [b"file1", b"file2", b"file3"] {
+            File::create(&file).unwrap().write_all(contents).unwrap();
+            roller.roll(&file).unwrap();
+            wait_for_roller(&roller);
+        }
+
+        assert!(dir.path().join("foo.log.0").exists());
+        assert!(dir.path().join("foo.log.1").exists());
+        assert!(!dir.path().join("foo.log.2").exists());
+    }
+
+    #[test]
+    fn post_roll() {
+        use std::sync::{Arc, Mutex};
+
+        let dir = tempfile::tempdir().unwrap();
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+
+        let mut post_roll = PostRoll::default();
+        post_roll.set_callback(move |path| {
+            let contents = fs::read_to_string(path).unwrap();
+            seen2.lock().unwrap().push((path.to_owned(), contents));
+        });
+
+        let base = dir.path().to_str().unwrap();
+        let roller = FixedWindowRoller::builder()
+            .build(&format!("{}/foo.log.{{}}", base), 2)
+            .unwrap();
+
+        let file = dir.path().join("foo.log");
+        for (i, contents) in [b"file1", b"file2"].iter().enumerate() {
+            File::create(&file).unwrap().write_all(*contents).unwrap();
+            post_roll.enter(|| roller.roll(&file)).unwrap();
+            wait_until(|| seen.lock().unwrap().len() > i);
+        }
+
+        let archive = dir.path().join("foo.log.0");
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (archive.clone(), "file1".to_owned()),
+                (archive, "file2".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn post_roll_panic() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls2 = calls.clone();
+
+        let mut post_roll = PostRoll::default();
+        post_roll.set_callback(move |_| {
+            calls2.fetch_add(1, Ordering::SeqCst);
+            panic!("hook failed");
+        });
+
+        let base = dir.path().to_str().unwrap();
+        let roller = FixedWindowRoller::builder()
+            .build(&format!("{}/foo.log.{{}}", base), 2)
+            .unwrap();
+
+        // A panicking hook doesn't prevent later rolls.
+        let file = dir.path().join("foo.log");
+        for contents in [b"file1", b"file2"] {
+            File::create(&file).unwrap().write_all(contents).unwrap();
+            post_roll.enter(|| roller.roll(&file)).unwrap();
+            wait_for_roller(&roller);
+        }
+        wait_until(|| calls.load(Ordering::SeqCst) == 2);
+        assert_eq!(fs::read(dir.path().join("foo.log.0")).unwrap(), b"file2");
+        assert_eq!(fs::read(dir.path().join("foo.log.1")).unwrap(), b"file1");
+    }
+
+    #[test]
+    fn no_post_roll_without_archive() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls2 = calls.clone();
+
+        let mut post_roll = PostRoll::default();
+        post_roll.set_callback(move |_| {
+            calls2.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let base = dir.path().to_str().unwrap();
+        let roller = FixedWindowRoller::builder()
+            .build(&format!("{}/foo.log.{{}}", base), 0)
+            .unwrap();
+
+        let file = dir.path().join("foo.log");
+        File::create(&file).unwrap().write_all(b"file1").unwrap();
+        post_roll.enter(|| roller.roll(&file)).unwrap();
+        assert!(!file.exists());
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_str().unwrap();
+        let roller = FixedWindowRoller::builder()
+            .mode(0o440)
+            .build(&format!("{}/foo.log.{{}}", base), 2)
+            .unwrap();
+
+        let file = dir.path().join("foo.log");
+        for _ in 0..2 {
+            File::create(&file).unwrap().write_all(b"file").unwrap();
+            roller.roll(&file).unwrap();
+            wait_for_roller(&roller);
+        }
+        assert_eq!(mode(&dir.path().join("foo.log.0")), 0o440);
+        assert_eq!(mode(&dir.path().join("foo.log.1")), 0o440);
+
+        #[cfg(feature = "gzip")]
+        {
+            let roller = FixedWindowRoller::builder()
+                .build(&format!("{}/foo.log.{{}}.gz", base), 2)
+                .unwrap();
+            File::create(&file).unwrap().write_all(b"file").unwrap();
+            fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+            roller.roll(&file).unwrap();
+            wait_for_roller(&roller);
+            assert_eq!(mode(&dir.path().join("foo.log.0.gz")), 0o640);
+        }
+    }
 }
@@ -0,0 +1,322 @@
+//! Hooks run on archived log files.
+
+use std::{
+    cell::RefCell,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::error::{report_background, BackgroundReporter, ErrorEvent, ErrorKind};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Configuration for a command run on each archived log file.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostRollCommandConfig {
+    program: PathBuf,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    timeout: Option<Duration>,
+}
+
+#[cfg(feature = "config_parsing")]
+impl From<PostRollCommandConfig> for PostRollCommand {
+    fn from(config: PostRollCommandConfig) -> PostRollCommand {
+        let mut command = PostRollCommand::new(config.program).args(config.args);
+        if let Some(timeout) = config.timeout {
+            command = command.timeout(timeout);
+        }
+        command
+    }
+}
+
+/// An external command run on each archived log file.
+///
+/// The command is run with the configured arguments followed by the path of
+/// the archive. It must exit successfully within its timeout, or it is
+/// killed and the failure is reported to the logger's error handler.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PostRollCommand {
+    program: PathBuf,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl PostRollCommand {
+    /// Creates a new `PostRollCommand` which runs `program`.
+    ///
+    /// The timeout defaults to 60 seconds.
+    pub fn new<P>(program: P) -> PostRollCommand
+    where
+        P: Into<PathBuf>,
+    {
+        PostRollCommand {
+            program: program.into(),
+            args: vec![],
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Adds arguments passed to the program before the archive's path.
+    pub fn args<I, S>(mut self, args: I) -> PostRollCommand
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets how long the program may run before it is killed.
+    pub fn timeout(mut self, timeout: Duration) -> PostRollCommand {
+        self.timeout = timeout;
+        self
+    }
+
+    fn run(&self, archive: &Path) -> anyhow::Result<()> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .arg(archive)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("error running `{}`: {}", self.program.display(), e))?;
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                anyhow::bail!(
+                    "`{}` timed out after {:?} on {}",
+                    self.program.display(),
+                    self.timeout,
+                    archive.display()
+                );
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        if !status.success() {
+            anyhow::bail!(
+                "`{}` failed on {}: {}",
+                self.program.display(),
+                archive.display(),
+                status
+            );
+        }
+        Ok(())
+    }
+}
+
+type Callback = Arc<dyn Fn(&Path) + Send + Sync>;
+
+thread_local! {
+    static CURRENT: RefCell<PostRoll> = RefCell::new(PostRoll::default());
+}
+
+/// The hooks run on each archived log file.
+///
+/// They belong to the compound policy, which makes them current while it
+/// calls its roller, so rollers pick them up with `PostRoll::current` rather
+/// than storing them.
+#[derive(Clone, Default)]
+pub(crate) struct PostRoll {
+    callback: Option<Callback>,
+    command: Option<PostRollCommand>,
+}
+
+// Only used by rollers behind optional features.
+#[allow(dead_code)]
+impl PostRoll {
+    /// Returns the hooks of the roll in progress on the current thread.
+    pub(crate) fn current() -> PostRoll {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// Runs `f` with these hooks as the current ones.
+    pub(crate) fn enter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Restore(PostRoll);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = std::mem::take(&mut self.0);
+                CURRENT.with(|current| *current.borrow_mut() = previous);
+            }
+        }
+
+        let _restore = Restore(CURRENT.with(|current| current.replace(self.clone())));
+        f()
+    }
+
+    pub(crate) fn set_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&Path) + Send + Sync + 'static,
+    {
+        self.callback = Some(Arc::new(callback));
+    }
+
+    pub(crate) fn set_command(&mut self, command: PostRollCommand) {
+        self.command = Some(command);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.callback.is_none() && self.command.is_none()
+    }
+
+    /// Runs the hooks on `archive`, waiting for them to finish.
+    ///
+    /// Failures, including a panicking callback, are reported to the logger's
+    /// error handler rather than returned, since the log has already rolled
+    /// successfully.
+    pub(crate) fn run(&self, archive: &Path) {
+        if let Some(callback) = &self.callback {
+            if panic::catch_unwind(AssertUnwindSafe(|| callback(archive))).is_err() {
+                let e = anyhow::anyhow!("post-roll callback panicked on {}", archive.display());
+                report_background(&ErrorEvent::new(ErrorKind::PostRoll, &e));
+            }
+        }
+        if let Some(command) = &self.command {
+            if let Err(e) = command.run(archive) {
+                report_background(&ErrorEvent::new(ErrorKind::PostRoll, &e));
+            }
+        }
+    }
+
+    /// Runs the hooks on `archive` on a separate thread, so that neither the
+    /// appender nor the lock of a shared log file is held while they run.
+    pub(crate) fn spawn(&self, archive: &Path) {
+        if self.is_empty() {
+            return;
+        }
+
+        let hooks = self.clone();
+        let archive = archive.to_owned();
+        let reporter = BackgroundReporter::current();
+        thread::spawn(move || reporter.enter(|| hooks.run(&archive)));
+    }
+
+    /// Returns the address of the callback, which identifies it.
+    fn callback_addr(&self) -> Option<*const u8> {
+        self.callback.as_ref().map(|c| Arc::as_ptr(c) as *const u8)
+    }
+}
+
+impl fmt::Debug for PostRoll {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("PostRoll")
+            .field("callback", &self.callback_addr())
+            .field("command", &self.command)
+            .finish()
+    }
+}
+
+/// Waits up to five seconds for `done` to return true, e.g. for hooks run on
+/// a separate thread to finish.
+#[cfg(test)]
+pub(crate) fn wait_until<F>(done: F)
+where
+    F: Fn() -> bool,
+{
+    for _ in 0..500 {
+        if done() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("timed out waiting for post-roll hooks");
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn callback() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let mut hooks = PostRoll::default();
+        assert!(hooks.is_empty());
+        let seen2 = seen.clone();
+        hooks.set_callback(move |path| seen2.lock().unwrap().push(path.to_owned()));
+
+        hooks.run(Path::new("archive.0.log"));
+        assert_eq!(*seen.lock().unwrap(), vec![PathBuf::from("archive.0.log")]);
+    }
+
+    #[test]
+    fn current() {
+        let mut hooks = PostRoll::default();
+        hooks.set_command(PostRollCommand::new("ship-log"));
+
+        assert!(PostRoll::current().is_empty());
+        hooks.enter(|| {
+            assert_eq!(PostRoll::current().command, hooks.command);
+            PostRoll::default().enter(|| assert!(PostRoll::current().is_empty()));
+            assert!(!PostRoll::current().is_empty());
+        });
+        assert!(PostRoll::current().is_empty());
+    }
+
+    #[test]
+    fn panicking_callback() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let sink = events.clone();
+        let handler = move |e: &ErrorEvent| sink.lock().unwrap().push(e.to_string());
+        let reporter = BackgroundReporter::new(Arc::new(handler), "file".into());
+
+        let mut hooks = PostRoll::default();
+        hooks.set_callback(|_| panic!("callback failed"));
+        reporter.enter(|| hooks.spawn(Path::new("archive.0.log")));
+
+        wait_until(|| !events.lock().unwrap().is_empty());
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["appender `file`: post-roll callback panicked on archive.0.log"]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn command() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let command = PostRollCommand::new("sh")
+            .args(["-c".to_owned(), format!("echo \"$0\" > {}", out.display())]);
+        command.run(Path::new("archive.0.log")).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "archive.0.log\n");
+
+        let err = PostRollCommand::new("false")
+            .run(Path::new("archive.0.log"))
+            .unwrap_err();
+        assert!(err.to_string().contains("failed on archive.0.log"));
+
+        let start = Instant::now();
+        let err = PostRollCommand::new("sh")
+            .args(["-c", "sleep 10"])
+            .timeout(Duration::from_millis(100))
+            .run(Path::new("archive.0.log"))
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        assert!(PostRollCommand::new(dir.path().join("missing"))
+            .run(Path::new("archive.0.log"))
+            .is_err());
+    }
+}
@@ -5,10 +5,17 @@ use std::{fmt, path::Path};
 #[cfg(feature = "config_parsing")]
 use crate::config::Deserializable;
 
+#[cfg(any(feature = "fixed_window_roller", feature = "time_based_roller"))]
+mod compression;
 #[cfg(feature = "delete_roller")]
 pub mod delete;
 #[cfg(feature = "fixed_window_roller")]
 pub mod fixed_window;
+pub mod hook;
+#[cfg(any(feature = "fixed_window_roller", feature = "time_based_roller"))]
+mod retention;
+#[cfg(feature = "time_based_roller")]
+pub mod time_based;
 
 /// A trait which processes log files after they have been rolled over.
 pub trait Roll: fmt::Debug + Send + Sync + 'static {
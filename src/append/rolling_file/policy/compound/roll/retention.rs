@@ -0,0 +1,211 @@
+//! Archive retention shared by the rollers.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// An archived log file considered for deletion.
+#[derive(Debug)]
+pub(crate) struct ArchivedFile {
+    pub(crate) path: PathBuf,
+    pub(crate) modified: SystemTime,
+    pub(crate) len: u64,
+}
+
+impl ArchivedFile {
+    /// Returns the archive at `path`, if it exists.
+    #[cfg_attr(not(feature = "fixed_window_roller"), allow(dead_code))]
+    pub(crate) fn open(path: PathBuf) -> io::Result<Option<ArchivedFile>> {
+        match fs::metadata(&path) {
+            Ok(metadata) => Ok(Some(ArchivedFile {
+                path,
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                len: metadata.len(),
+            })),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Limits on the archives kept by a roller, beyond its archive count.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub(crate) struct Retention {
+    pub(crate) max_age: Option<Duration>,
+    pub(crate) max_total_size: Option<u64>,
+    pub(crate) min_free_disk: Option<u64>,
+}
+
+impl Retention {
+    /// Deletes archives, oldest first, which are older than the maximum age,
+    /// exceed the total size budget, or sit on a filesystem with less free
+    /// space than the minimum.
+    ///
+    /// The newest archive is always kept, so the records from just before
+    /// the latest roll survive whatever the limits are.
+    ///
+    /// `archives` must be sorted from oldest to newest.
+    pub(crate) fn apply(&self, mut archives: Vec<ArchivedFile>) -> io::Result<()> {
+        if *self == Retention::default() || archives.len() < 2 {
+            return Ok(());
+        }
+
+        let now = SystemTime::now();
+        let mut total = archives.iter().map(|a| a.len).sum::<u64>();
+        let mut free = match self.min_free_disk {
+            Some(_) => Some(free_space(&archives[0].path)?),
+            None => None,
+        };
+
+        // The newest archive counts towards the total size, but is never
+        // deleted.
+        archives.pop();
+        for archive in archives {
+            let expired = match (self.max_age, now.duration_since(archive.modified)) {
+                (Some(max_age), Ok(age)) => age > max_age,
+                _ => false,
+            };
+            let over_budget = self.max_total_size.map_or(false, |max| total > max);
+            let low_on_disk = match (self.min_free_disk, free) {
+                (Some(min), Some(free)) => free < min,
+                _ => false,
+            };
+            if !(expired || over_budget || low_on_disk) {
+                continue;
+            }
+
+            match fs::remove_file(&archive.path) {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            total -= archive.len;
+            free = free.map(|f| f + archive.len);
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the space available to unprivileged users on the filesystem
+/// containing `path`.
+#[cfg(unix)]
+fn free_space(path: &Path) -> io::Result<u64> {
+    use std::{ffi::CString, mem, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Returns the space available to the current user on the volume containing
+/// `path`.
+#[cfg(windows)]
+fn free_space(path: &Path) -> io::Result<u64> {
+    use std::{os::windows::ffi::OsStrExt, ptr};
+    use winapi::um::{fileapi::GetDiskFreeSpaceExW, winnt::ULARGE_INTEGER};
+
+    let path = path
+        .as_os_str()
+        .encode_wide()
+        .chain(Some(0))
+        .collect::<Vec<_>>();
+    let mut available: ULARGE_INTEGER = unsafe { std::mem::zeroed() };
+    if unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    } == 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { *available.QuadPart() })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+
+    use super::*;
+
+    fn archive(dir: &Path, name: &str, len: usize, modified: SystemTime) -> ArchivedFile {
+        let path = dir.join(name);
+        fs::write(&path, vec![b'x'; len]).unwrap();
+        ArchivedFile {
+            path,
+            modified,
+            len: len as u64,
+        }
+    }
+
+    #[test]
+    fn max_age_and_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        let archives = vec![
+            archive(dir.path(), "a", 10, now - day * 10),
+            archive(dir.path(), "b", 10, now - day * 2),
+            archive(dir.path(), "c", 10, now - day),
+            archive(dir.path(), "d", 10, now),
+        ];
+
+        let retention = Retention {
+            max_age: Some(day * 5),
+            max_total_size: Some(15),
+            ..Retention::default()
+        };
+        retention.apply(archives).unwrap();
+
+        assert!(!dir.path().join("a").exists());
+        assert!(!dir.path().join("b").exists());
+        assert!(!dir.path().join("c").exists());
+        assert!(dir.path().join("d").exists());
+    }
+
+    #[test]
+    fn min_free_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        File::create(dir.path().join("a")).unwrap();
+        let free = free_space(&dir.path().join("a")).unwrap();
+        assert!(free > 0);
+
+        let archives = vec![
+            archive(dir.path(), "a", 10, SystemTime::now()),
+            archive(dir.path(), "b", 10, SystemTime::now()),
+        ];
+        // No filesystem has this much free space, so everything but the
+        // newest archive goes.
+        let retention = Retention {
+            min_free_disk: Some(u64::MAX),
+            ..Retention::default()
+        };
+        retention.apply(archives).unwrap();
+        assert!(!dir.path().join("a").exists());
+        assert!(dir.path().join("b").exists());
+    }
+
+    #[test]
+    fn keeps_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = SystemTime::now() - Duration::from_secs(60 * 60);
+        let archives = vec![archive(dir.path(), "a", 10, old)];
+
+        let retention = Retention {
+            max_age: Some(Duration::from_secs(60)),
+            max_total_size: Some(0),
+            ..Retention::default()
+        };
+        retention.apply(archives).unwrap();
+        assert!(dir.path().join("a").exists());
+    }
+}
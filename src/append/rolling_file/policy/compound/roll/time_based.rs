@@ -0,0 +1,867 @@
+//! The time-based roller.
+//!
+//! Requires the `time_based_roller` feature.
+
+use anyhow::bail;
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, FixedOffset, Utc,
+};
+#[cfg(feature = "background_rotation")]
+use parking_lot::{Condvar, Mutex};
+#[cfg(feature = "background_rotation")]
+use std::sync::Arc;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::append::{
+    env_util::expand_env_vars,
+    rolling_file::policy::compound::{
+        roll::{
+            compression::Compression,
+            hook::PostRoll,
+            retention::{ArchivedFile, Retention},
+            Roll,
+        },
+        trigger::Timezone,
+    },
+};
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+#[cfg(feature = "background_rotation")]
+use crate::error::{BackgroundReporter, ErrorEvent, ErrorKind};
+
+/// Configuration for the time-based roller.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeBasedRollerConfig {
+    pattern: String,
+    base: Option<u32>,
+    count: Option<u32>,
+    compression_level: Option<u32>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    max_age: Option<Duration>,
+    #[serde(deserialize_with = "crate::config::de_opt_size", default)]
+    max_total_size: Option<u64>,
+    #[serde(deserialize_with = "crate::config::de_opt_size", default)]
+    min_free_disk: Option<u64>,
+    #[serde(default)]
+    timezone: Timezone,
+}
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Literal(String),
+    Date(String),
+    Index,
+}
+
+/// A piece of an archive file name, used to match existing archives.
+enum Piece<'a> {
+    Literal(&'a str),
+    Any,
+    Index,
+}
+
+fn parse_tokens(mut pattern: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut literal = String::new();
+
+    while let Some(start) = pattern.find('{') {
+        literal.push_str(&pattern[..start]);
+        let rest = &pattern[start..];
+
+        let (token, len) = if rest.starts_with("{i}") {
+            (Token::Index, "{i}".len())
+        } else if rest.starts_with("{d}") {
+            (Token::Date(DEFAULT_DATE_FORMAT.to_owned()), "{d}".len())
+        } else if let Some(format) = rest.strip_prefix("{d(") {
+            let end = match format.find(")}") {
+                Some(end) => end,
+                None => bail!("unterminated date token in pattern"),
+            };
+            let format = &format[..end];
+            if StrftimeItems::new(format).any(|i| i == Item::Error) {
+                bail!("invalid date format `{}`", format);
+            }
+            (
+                Token::Date(format.to_owned()),
+                "{d(".len() + end + ")}".len(),
+            )
+        } else {
+            literal.push('{');
+            pattern = &rest[1..];
+            continue;
+        };
+
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(std::mem::take(&mut literal)));
+        }
+        tokens.push(token);
+        pattern = &rest[len..];
+    }
+
+    literal.push_str(pattern);
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+    Ok(tokens)
+}
+
+fn match_pieces(pieces: &[Piece], name: &str, index: &mut Option<u32>) -> bool {
+    match pieces.split_first() {
+        None => name.is_empty(),
+        Some((Piece::Literal(literal), rest)) => match name.strip_prefix(literal) {
+            Some(name) => match_pieces(rest, name, index),
+            None => false,
+        },
+        Some((Piece::Any, rest)) => (1..=name.len())
+            .filter(|&i| name.is_char_boundary(i))
+            .any(|i| match_pieces(rest, &name[i..], index)),
+        Some((Piece::Index, rest)) => {
+            let digits = name.bytes().take_while(u8::is_ascii_digit).count();
+            for i in (1..=digits).rev() {
+                if match_pieces(rest, &name[i..], index) {
+                    *index = name[..i].parse().ok();
+                    return true;
+                }
+            }
+            false
+        }
+    }
+}
+
+/// An archive found in the archive directory.
+struct Archive {
+    path: PathBuf,
+    index: Option<u32>,
+    modified: SystemTime,
+    len: u64,
+}
+
+/// A roller which names archived log files after the date their records were
+/// written.
+///
+/// The archive file name pattern contains one or more `{d(format)}` tokens,
+/// which are replaced with the date formatted with the given `strftime`-style
+/// format, and optionally `{i}` tokens, which are replaced with an index
+/// distinguishing archives of the same period. `{d}` is short for
+/// `{d(%Y-%m-%d)}`. Tokens may only appear in the file name, not in the
+/// directory part of the pattern.
+///
+/// The date is taken from the log file's last modification time in the
+/// configured timezone, the local timezone of the host by default, so that a
+/// file rolled over at midnight by a time trigger is named after the day its
+/// records were written. Configure the trigger and the roller with the same
+/// timezone.
+///
+/// For example, with the pattern `archive/app-{d(%Y-%m-%d)}.{i}.log.gz` and
+/// a base index of 0, the first log file archived on the 18th of October 2026
+/// is named `archive/app-2026-10-18.0.log.gz`, the next one
+/// `archive/app-2026-10-18.1.log.gz`, and so on. The index is one more than
+/// the highest index of the period's existing archives. If the pattern has
+/// no `{i}` token and an archive for the period already exists, the log file
+/// is appended to it.
+///
+/// Unlike the `FixedWindowRoller`, existing archives are never renamed. If a
+/// count is configured, the archive directory is scanned for files matching
+/// the pattern after each roll and the oldest are deleted, by modification
+/// time, until at most `count` remain. The archives may also be limited by
+/// age, by their total size, and by the free space left on the disk, in which
+/// case the oldest are deleted until every configured limit is satisfied.
+///
+/// If the file extension of the pattern is `.gz`, `.zst`, `.xz` or `.bz2`,
+/// the archive files will be compressed with gzip, zstd, xz or bzip2
+/// respectively. Each codec requires the Cargo feature of the same name.
+///
+/// The post-roll hooks of the compound policy are run on each new archive
+/// once it is in place and old archives have been deleted. They run on a
+/// separate thread, and neither logging nor the next roll waits for them. A
+/// panicking callback is reported to the logger's error handler.
+#[derive(Clone, Debug)]
+pub struct TimeBasedRoller {
+    directory: PathBuf,
+    tokens: Vec<Token>,
+    compression: Compression,
+    base: u32,
+    count: Option<u32>,
+    retention: Retention,
+    timezone: Timezone,
+    #[cfg(feature = "background_rotation")]
+    cond_pair: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl TimeBasedRoller {
+    /// Returns a new builder for the `TimeBasedRoller`.
+    pub fn builder() -> TimeBasedRollerBuilder {
+        TimeBasedRollerBuilder::default()
+    }
+
+    fn has_index(&self) -> bool {
+        self.tokens.contains(&Token::Index)
+    }
+
+    /// Returns the archives of the period formatted from `time`, or of all
+    /// periods if it is `None`.
+    fn archives(&self, time: Option<&DateTime<FixedOffset>>) -> anyhow::Result<Vec<Archive>> {
+        let dates = self
+            .tokens
+            .iter()
+            .map(|t| match (t, time) {
+                (Token::Date(format), Some(time)) => Some(time.format(format).to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let pieces = self
+            .tokens
+            .iter()
+            .zip(&dates)
+            .map(|(token, date)| match (token, date) {
+                (Token::Literal(literal), _) => Piece::Literal(literal),
+                (Token::Date(_), Some(date)) => Piece::Literal(date),
+                (Token::Date(_), None) => Piece::Any,
+                (Token::Index, _) => Piece::Index,
+            })
+            .collect::<Vec<_>>();
+
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut archives = vec![];
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = match name.to_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            let mut index = None;
+            if !match_pieces(&pieces, name, &mut index) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            archives.push(Archive {
+                path: entry.path(),
+                index,
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                len: metadata.len(),
+            });
+        }
+        Ok(archives)
+    }
+
+    fn archive_path(&self, time: &DateTime<FixedOffset>, index: u32) -> PathBuf {
+        let mut name = String::new();
+        for token in &self.tokens {
+            match token {
+                Token::Literal(literal) => name.push_str(literal),
+                Token::Date(format) => name.push_str(&time.format(format).to_string()),
+                Token::Index => name.push_str(&index.to_string()),
+            }
+        }
+        self.directory.join(name)
+    }
+
+    /// Archives `file`, returning the path of the new archive.
+    fn archive(&self, file: &Path, time: &DateTime<FixedOffset>) -> anyhow::Result<PathBuf> {
+        fs::create_dir_all(&self.directory)?;
+
+        let index = if self.has_index() {
+            self.archives(Some(time))?
+                .iter()
+                .filter_map(|a| a.index)
+                .max()
+                .map_or(self.base, |max| self.base.max(max + 1))
+        } else {
+            self.base
+        };
+        let dst = self.archive_path(time, index);
+        self.compression.compress(file, &dst, !self.has_index())?;
+
+        if self.count.is_none() && self.retention == Retention::default() {
+            return Ok(dst);
+        }
+
+        let mut archives = self.archives(None)?;
+        // Oldest first.
+        archives.sort_by(|a, b| {
+            a.modified
+                .cmp(&b.modified)
+                .then_with(|| a.index.cmp(&b.index))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        if let Some(count) = self.count {
+            let excess = archives.len().saturating_sub(count as usize);
+            for archive in archives.drain(..excess) {
+                fs::remove_file(&archive.path)?;
+            }
+        }
+        self.retention.apply(
+            archives
+                .into_iter()
+                .map(|a| ArchivedFile {
+                    path: a.path,
+                    modified: a.modified,
+                    len: a.len,
+                })
+                .collect(),
+        )?;
+
+        Ok(dst)
+    }
+}
+
+fn modified_time(file: &Path, timezone: Timezone) -> DateTime<FixedOffset> {
+    let time = fs::metadata(file)
+        .and_then(|m| m.modified())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_else(|_| Utc::now());
+    timezone.to_fixed(time)
+}
+
+impl Roll for TimeBasedRoller {
+    #[cfg(not(feature = "background_rotation"))]
+    fn roll(&self, file: &Path) -> anyhow::Result<()> {
+        let time = modified_time(file, self.timezone);
+        let archive = self.archive(file, &time)?;
+        PostRoll::current().spawn(&archive);
+        Ok(())
+    }
+
+    #[cfg(feature = "background_rotation")]
+    fn roll(&self, file: &Path) -> anyhow::Result<()> {
+        use crate::append::rolling_file::policy::compound::roll::compression::{
+            make_temp_file_name, move_file,
+        };
+
+        let time = modified_time(file, self.timezone);
+        if crate::append::rolling_file::shared::is_locked() {
+            let archive = self.archive(file, &time)?;
+            PostRoll::current().spawn(&archive);
+            return Ok(());
+        }
+
+        // rename the file
+        let temp = make_temp_file_name(file);
+        move_file(file, &temp)?;
+
+        // Wait for the state to be ready to roll
+        let (lock, cvar) = &*self.cond_pair.clone();
+        let mut ready = lock.lock();
+        if !*ready {
+            cvar.wait(&mut ready);
+        }
+        *ready = false;
+        drop(ready);
+
+        let roller = self.clone();
+        let reporter = BackgroundReporter::current();
+        let post_roll = PostRoll::current();
+        // archive in the separate thread
+        std::thread::spawn(move || {
+            reporter.enter(|| {
+                let result = {
+                    let (lock, cvar) = &*roller.cond_pair;
+                    let mut ready = lock.lock();
+                    let result = roller.archive(&temp, &time);
+                    *ready = true;
+                    cvar.notify_one();
+                    result
+                };
+
+                // The hooks run once the next roll may proceed, so that a slow
+                // or panicking hook doesn't hold it up.
+                match result {
+                    Ok(archive) => post_roll.run(&archive),
+                    Err(e) => {
+                        let e = anyhow::anyhow!("error rotating log file: {}", e);
+                        reporter.report(&ErrorEvent::new(ErrorKind::Roll, &e));
+                    }
+                }
+            })
+        });
+
+        Ok(())
+    }
+}
+
+/// A builder for the `TimeBasedRoller`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct TimeBasedRollerBuilder {
+    base: u32,
+    count: Option<u32>,
+    compression_level: Option<u32>,
+    retention: Retention,
+    timezone: Timezone,
+}
+
+impl TimeBasedRollerBuilder {
+    /// Sets the first index used for archives of a period.
+    ///
+    /// Defaults to 0.
+    pub fn base(mut self, base: u32) -> TimeBasedRollerBuilder {
+        self.base = base;
+        self
+    }
+
+    /// Sets the maximum number of archived logs to maintain.
+    ///
+    /// Defaults to unlimited.
+    pub fn count(mut self, count: u32) -> TimeBasedRollerBuilder {
+        self.count = Some(count);
+        self
+    }
+
+    /// Sets the compression level of archived log files.
+    ///
+    /// The valid levels depend on the codec selected by the pattern: 0-9 for
+    /// gzip and xz, 1-22 for zstd and 1-9 for bzip2. Defaults to the codec's
+    /// default level.
+    pub fn compression_level(mut self, level: u32) -> TimeBasedRollerBuilder {
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// Sets the maximum age of archived log files, by modification time.
+    ///
+    /// Defaults to unlimited.
+    pub fn max_age(mut self, max_age: Duration) -> TimeBasedRollerBuilder {
+        self.retention.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets the maximum total size in bytes of archived log files.
+    ///
+    /// Defaults to unlimited.
+    pub fn max_total_size(mut self, max_total_size: u64) -> TimeBasedRollerBuilder {
+        self.retention.max_total_size = Some(max_total_size);
+        self
+    }
+
+    /// Sets the amount of free disk space in bytes to preserve. Archived log
+    /// files are deleted while less space than this is available, except for
+    /// the newest, which is kept whatever the retention limits.
+    ///
+    /// Defaults to none.
+    pub fn min_free_disk(mut self, min_free_disk: u64) -> TimeBasedRollerBuilder {
+        self.retention.min_free_disk = Some(min_free_disk);
+        self
+    }
+
+    /// Sets the timezone in which archives are dated.
+    ///
+    /// Defaults to the local timezone of the host.
+    pub fn timezone(mut self, timezone: Timezone) -> TimeBasedRollerBuilder {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Constructs a new `TimeBasedRoller`.
+    ///
+    /// `pattern` is either an absolute path or lacking a leading `/`, relative
+    /// to the `cwd` of your application. Its file name must contain at least
+    /// one `{d(format)}` or `{d}` date token, and may contain `{i}` index
+    /// tokens. Environment variables of the form `$ENV{name_here}` are
+    /// resolved when the roller is built.
+    ///
+    /// If the file extension of the pattern is `.gz`, `.zst`, `.xz` or `.bz2`
+    /// and the `gzip`, `zstd`, `xz` or `bzip2` Cargo feature respectively is
+    /// enabled, the archive files will be compressed with that codec.
+    /// If the codec's feature is *not* enabled, an error will be returned.
+    pub fn build(self, pattern: &str) -> anyhow::Result<TimeBasedRoller> {
+        let pattern = PathBuf::from(expand_env_vars(pattern).as_ref());
+        let file_name = match pattern.file_name().and_then(|n| n.to_str()) {
+            Some(file_name) => file_name,
+            None => bail!("pattern does not contain a file name"),
+        };
+        // A pattern without a directory is relative to the working directory,
+        // which `read_dir` can't be given as an empty path.
+        let directory = match pattern.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+            _ => PathBuf::from("."),
+        };
+        if directory.to_string_lossy().contains("{d") || directory.to_string_lossy().contains("{i}")
+        {
+            bail!("date and index tokens are only supported in the file name of the pattern");
+        }
+
+        let tokens = parse_tokens(file_name)?;
+        if !tokens.iter().any(|t| matches!(t, Token::Date(_))) {
+            bail!("pattern does not contain a `{{d}}` date token");
+        }
+
+        Ok(TimeBasedRoller {
+            directory,
+            tokens,
+            compression: Compression::from_pattern(file_name, self.compression_level)?,
+            base: self.base,
+            count: self.count,
+            retention: self.retention,
+            timezone: self.timezone,
+            #[cfg(feature = "background_rotation")]
+            cond_pair: Arc::new((Mutex::new(true), Condvar::new())),
+        })
+    }
+}
+
+/// A deserializer for the `TimeBasedRoller`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: time_based
+///
+/// # The filename pattern for archived logs. This is either an absolute path or if lacking a leading `/`,
+/// # relative to the `cwd` of your application. The file name must contain at least one
+/// # `{d(format)}` token, replaced with the date the log file's records were written,
+/// # formatted with the `strftime`-style format. `{d}` is short for `{d(%Y-%m-%d)}`.
+/// # It may contain `{i}` tokens, replaced with an index distinguishing archives of the same period.
+/// # If the file extension of the pattern is `.gz`, `.zst`, `.xz` or `.bz2`, the archive
+/// # files will be compressed with gzip, zstd, xz or bzip2 respectively. Each codec
+/// # requires the Cargo feature of the same name.
+/// # Required.
+/// pattern: archive/app-{d(%Y-%m-%d)}.{i}.log.gz
+///
+/// # The first index used for archives of a period. Defaults to 0.
+/// base: 1
+///
+/// # The timezone in which archives are dated: `utc`, `local`, or the name of an
+/// # IANA timezone like `Europe/Berlin`, which requires the `timezones` feature.
+/// # Defaults to `local`.
+/// timezone: utc
+///
+/// # The maximum number of archived logs to maintain. Defaults to unlimited.
+/// count: 30
+///
+/// # The compression level of archived logs, if the pattern selects a codec: 0-9 for
+/// # gzip and xz, 1-22 for zstd and 1-9 for bzip2. Defaults to the codec's default level.
+/// compression_level: 9
+///
+/// # The maximum age of archived logs, by modification time. Defaults to unlimited.
+/// max_age: 30 days
+///
+/// # The maximum total size of archived logs. Defaults to unlimited.
+/// max_total_size: 1 gb
+///
+/// # Archived logs are deleted, oldest first, while less than this much disk space
+/// # is free. The newest archived log is always kept. Defaults to none.
+/// min_free_disk: 500 mb
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct TimeBasedRollerDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for TimeBasedRollerDeserializer {
+    type Trait = dyn Roll;
+
+    type Config = TimeBasedRollerConfig;
+
+    fn deserialize(
+        &self,
+        config: TimeBasedRollerConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Roll>> {
+        let mut builder = TimeBasedRoller::builder();
+        if let Some(base) = config.base {
+            builder = builder.base(base);
+        }
+        if let Some(count) = config.count {
+            builder = builder.count(count);
+        }
+        if let Some(level) = config.compression_level {
+            builder = builder.compression_level(level);
+        }
+        if let Some(max_age) = config.max_age {
+            builder = builder.max_age(max_age);
+        }
+        if let Some(max_total_size) = config.max_total_size {
+            builder = builder.max_total_size(max_total_size);
+        }
+        if let Some(min_free_disk) = config.min_free_disk {
+            builder = builder.min_free_disk(min_free_disk);
+        }
+        builder = builder.timezone(config.timezone);
+
+        Ok(Box::new(builder.build(&config.pattern)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::File,
+        io::{Read, Write},
+    };
+
+    use chrono::Local;
+
+    use super::*;
+    use crate::append::rolling_file::policy::compound::roll::hook::wait_until;
+
+    #[cfg(feature = "background_rotation")]
+    fn wait_for_roller(roller: &TimeBasedRoller) {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let _lock = roller.cond_pair.0.lock();
+    }
+
+    #[cfg(not(feature = "background_rotation"))]
+    fn wait_for_roller(_roller: &TimeBasedRoller) {}
+
+    fn read(path: &Path) -> String {
+        let mut contents = String::new();
+        File::open(path)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_tokens("app-{d(%Y-%m)}.{i}{x}.log").unwrap(),
+            [
+                Token::Literal("app-".to_owned()),
+                Token::Date("%Y-%m".to_owned()),
+                Token::Literal(".".to_owned()),
+                Token::Index,
+                Token::Literal("{x}.log".to_owned()),
+            ]
+        );
+        assert!(parse_tokens("app-{d(%Y-%m.log").is_err());
+        assert!(parse_tokens("app-{d(%Q)}.log").is_err());
+        assert!(TimeBasedRoller::builder().build("app.{i}.log").is_err());
+        assert!(TimeBasedRoller::builder().build("{d}/app.log").is_err());
+    }
+
+    #[test]
+    fn indexed() {
+        let dir = tempfile::tempdir().unwrap();
+        let pattern = dir.path().join("archive").join("app-{d}.{i}.log");
+        let roller = TimeBasedRoller::builder()
+            .base(1)
+            .build(pattern.to_str().unwrap())
+            .unwrap();
+
+        let file = dir.path().join("app.log");
+        for contents in ["first", "second"] {
+            File::create(&file)
+                .unwrap()
+                .write_all(contents.as_bytes())
+                .unwrap();
+            roller.roll(&file).unwrap();
+            wait_for_roller(&roller);
+            assert!(!file.exists());
+        }
+
+        let date = Local::now().format("%Y-%m-%d");
+        let archive = dir.path().join("archive");
+        assert_eq!(read(&archive.join(format!("app-{}.1.log", date))), "first");
+        assert_eq!(read(&archive.join(format!("app-{}.2.log", date))), "second");
+    }
+
+    #[test]
+    fn no_directory() {
+        // The archives are written to the working directory, so their names
+        // are unique to this process.
+        let prefix = format!("log4rs-no-directory-{}", std::process::id());
+        let roller = TimeBasedRoller::builder()
+            .count(2)
+            .build(&format!("{}-{{d}}.{{i}}.log", prefix))
+            .unwrap();
+        assert_eq!(roller.directory, Path::new("."));
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("app.log");
+        for contents in ["first", "second", "third"] {
+            File::create(&file)
+                .unwrap()
+                .write_all(contents.as_bytes())
+                .unwrap();
+            roller.roll(&file).unwrap();
+            wait_for_roller(&roller);
+        }
+
+        let date = Local::now().format("%Y-%m-%d");
+        let archive = |i| PathBuf::from(format!("{}-{}.{}.log", prefix, date, i));
+        let contents = [archive(0), archive(1), archive(2)].map(|path| {
+            let contents = fs::read_to_string(&path).ok();
+            let _ = fs::remove_file(&path);
+            contents
+        });
+        assert_eq!(
+            contents,
+            [None, Some("second".to_owned()), Some("third".to_owned())]
+        );
+    }
+
+    #[test]
+    fn post_roll() {
+        use std::sync::{Arc, Mutex};
+
+        let dir = tempfile::tempdir().unwrap();
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+
+        let mut post_roll = PostRoll::default();
+        post_roll.set_callback(move |path| seen2.lock().unwrap().push(read(path)));
+
+        let pattern = dir.path().join("app-{d}.{i}.log");
+        let roller = TimeBasedRoller::builder()
+            .count(1)
+            .build(pattern.to_str().unwrap())
+            .unwrap();
+
+        let file = dir.path().join("app.log");
+        for (i, contents) in ["first", "second"].iter().enumerate() {
+            File::create(&file)
+                .unwrap()
+                .write_all(contents.as_bytes())
+                .unwrap();
+            post_roll.enter(|| roller.roll(&file)).unwrap();
+            wait_until(|| seen.lock().unwrap().len() > i);
+        }
+
+        // The count keeps the new archive for the hook.
+        assert_eq!(*seen.lock().unwrap(), vec!["first", "second"]);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn appends_without_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let pattern = dir.path().join("app-{d(%Y%m%d)}.log");
+        let roller = TimeBasedRoller::builder()
+            .build(pattern.to_str().unwrap())
+            .unwrap();
+
+        let file = dir.path().join("app.log");
+        for contents in ["first\n", "second\n"] {
+            File::create(&file)
+                .unwrap()
+                .write_all(contents.as_bytes())
+                .unwrap();
+            roller.roll(&file).unwrap();
+            wait_for_roller(&roller);
+        }
+
+        let archive = dir
+            .path()
+            .join(format!("app-{}.log", Local::now().format("%Y%m%d")));
+        assert_eq!(read(&archive), "first\nsecond\n");
+    }
+
+    #[test]
+    fn retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let pattern = dir.path().join("app-{d}.{i}.log");
+        let roller = TimeBasedRoller::builder()
+            .count(2)
+            .build(pattern.to_str().unwrap())
+            .unwrap();
+
+        // Files which don't match the pattern are left alone.
+        File::create(dir.path().join("app-notes.txt")).unwrap();
+        File::create(dir.path().join("app-old.x.log")).unwrap();
+
+        let file = dir.path().join("app.log");
+        for contents in ["1", "2", "3"] {
+            File::create(&file)
+                .unwrap()
+                .write_all(contents.as_bytes())
+                .unwrap();
+            roller.roll(&file).unwrap();
+            wait_for_roller(&roller);
+        }
+
+        let date = Local::now().format("%Y-%m-%d");
+        assert!(!dir.path().join(format!("app-{}.0.log", date)).exists());
+        assert!(dir.path().join(format!("app-{}.1.log", date)).exists());
+        assert!(dir.path().join(format!("app-{}.2.log", date)).exists());
+        assert!(dir.path().join("app-notes.txt").exists());
+        assert!(dir.path().join("app-old.x.log").exists());
+    }
+
+    #[test]
+    fn max_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let pattern = dir.path().join("app-{d}.{i}.log");
+        let roller = TimeBasedRoller::builder()
+            .max_age(Duration::from_secs(24 * 60 * 60))
+            .build(pattern.to_str().unwrap())
+            .unwrap();
+
+        let file = dir.path().join("app.log");
+        let roll = |contents: &str| {
+            File::create(&file)
+                .unwrap()
+                .write_all(contents.as_bytes())
+                .unwrap();
+            roller.roll(&file).unwrap();
+            wait_for_roller(&roller);
+        };
+        let date = Local::now().format("%Y-%m-%d");
+        let first = dir.path().join(format!("app-{}.0.log", date));
+
+        roll("1");
+        let two_days_ago = SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60);
+        filetime::set_file_mtime(&first, two_days_ago.into()).unwrap();
+        roll("2");
+
+        assert!(!first.exists());
+        assert!(dir.path().join(format!("app-{}.1.log", date)).exists());
+    }
+
+    #[test]
+    fn timezone() {
+        use chrono::TimeZone;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("app.log");
+        let pattern = dir.path().join("app-{d(%Y-%m-%d %z)}.log");
+        let roll = |timezone| {
+            let roller = TimeBasedRoller::builder()
+                .timezone(timezone)
+                .build(pattern.to_str().unwrap())
+                .unwrap();
+            File::create(&file).unwrap();
+            let modified = Utc.with_ymd_and_hms(2024, 3, 31, 23, 30, 0).unwrap();
+            filetime::set_file_mtime(&file, SystemTime::from(modified).into()).unwrap();
+            roller.roll(&file).unwrap();
+            wait_for_roller(&roller);
+        };
+
+        roll(Timezone::Utc);
+        assert!(dir.path().join("app-2024-03-31 +0000.log").exists());
+
+        #[cfg(feature = "timezones")]
+        {
+            roll(Timezone::Named(chrono_tz::Asia::Tokyo));
+            assert!(dir.path().join("app-2024-04-01 +0900.log").exists());
+        }
+    }
+
+    #[test]
+    #[cfg_attr(feature = "gzip", ignore)]
+    fn unsupported_gzip() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let pattern = dir.path().join("{d}.gz");
+        assert!(TimeBasedRoller::builder()
+            .build(pattern.to_str().unwrap())
+            .is_err());
+    }
+}
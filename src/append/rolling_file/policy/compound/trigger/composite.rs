@@ -0,0 +1,331 @@
+//! The composite triggers.
+//!
+//! Requires the `composite_trigger` feature.
+
+use std::sync::Mutex;
+
+#[cfg(feature = "config_parsing")]
+use crate::append::rolling_file::policy::compound::TriggerConfig;
+use crate::append::rolling_file::{policy::compound::trigger::Trigger, LogFile};
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+
+/// Configuration for the any and all triggers.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompositeTriggerConfig {
+    triggers: Vec<TriggerConfig>,
+}
+
+#[cfg(feature = "config_parsing")]
+impl CompositeTriggerConfig {
+    fn deserialize(self, deserializers: &Deserializers) -> anyhow::Result<Vec<Box<dyn Trigger>>> {
+        if self.triggers.is_empty() {
+            anyhow::bail!("at least one trigger is required");
+        }
+
+        self.triggers
+            .into_iter()
+            .map(|t| deserializers.deserialize(&t.kind, t.config))
+            .collect()
+    }
+}
+
+/// Evaluates every trigger, returning whether each one fired.
+///
+/// All triggers are evaluated even once one has fired, so that triggers which
+/// track state, like the time trigger's next roll time or the onstartup
+/// trigger's first check, observe every check of the log file.
+fn evaluate(triggers: &[Box<dyn Trigger>], file: &LogFile) -> anyhow::Result<Vec<bool>> {
+    triggers.iter().map(|t| t.trigger(file)).collect()
+}
+
+fn any_pre_process(triggers: &[Box<dyn Trigger>]) -> bool {
+    triggers.iter().any(|t| t.is_pre_process())
+}
+
+/// A trigger which rolls the log when any of its triggers fires.
+///
+/// Every trigger is checked each time the log is checked, so a trigger which
+/// fires alongside a sibling updates its state as if it had fired alone. For
+/// example, a time trigger which passes its roll time at the same time as a
+/// size trigger fires schedules its next roll time as usual.
+///
+/// The trigger is a pre-process trigger if any of its triggers are. In that
+/// case, all of its triggers are checked before a record is written, so a
+/// post-process trigger like the size trigger sees the log file's size without
+/// the record, and the file may exceed its limit by one record.
+#[derive(Debug)]
+pub struct AnyTrigger {
+    triggers: Vec<Box<dyn Trigger>>,
+}
+
+impl AnyTrigger {
+    /// Returns a new trigger which rolls the log when any of `triggers`
+    /// fires.
+    ///
+    /// If `triggers` is empty, the log is never rolled.
+    pub fn new(triggers: Vec<Box<dyn Trigger>>) -> AnyTrigger {
+        AnyTrigger { triggers }
+    }
+}
+
+impl Trigger for AnyTrigger {
+    fn trigger(&self, file: &LogFile) -> anyhow::Result<bool> {
+        Ok(evaluate(&self.triggers, file)?.contains(&true))
+    }
+
+    fn is_pre_process(&self) -> bool {
+        any_pre_process(&self.triggers)
+    }
+}
+
+/// A trigger which rolls the log once all of its triggers have fired.
+///
+/// A trigger which fires is remembered until the log is rolled, so the
+/// triggers don't need to fire on the same check. For example, combining a
+/// daily time trigger with a size trigger rolls the log at the first check
+/// after midnight at which the file is over the size limit, even if it only
+/// grows past the limit later in the day. Once the log rolls, every trigger
+/// has to fire again before the next roll.
+///
+/// The trigger is a pre-process trigger if any of its triggers are, with the
+/// same consequences as for the `AnyTrigger`.
+#[derive(Debug)]
+pub struct AllTrigger {
+    triggers: Vec<Box<dyn Trigger>>,
+    fired: Mutex<Vec<bool>>,
+}
+
+impl AllTrigger {
+    /// Returns a new trigger which rolls the log once all of `triggers` have
+    /// fired.
+    ///
+    /// If `triggers` is empty, the log is never rolled.
+    pub fn new(triggers: Vec<Box<dyn Trigger>>) -> AllTrigger {
+        AllTrigger {
+            fired: Mutex::new(vec![false; triggers.len()]),
+            triggers,
+        }
+    }
+}
+
+impl Trigger for AllTrigger {
+    fn trigger(&self, file: &LogFile) -> anyhow::Result<bool> {
+        let results = evaluate(&self.triggers, file)?;
+
+        let mut fired = self.fired.lock().unwrap_or_else(|e| e.into_inner());
+        for (fired, result) in fired.iter_mut().zip(results) {
+            *fired |= result;
+        }
+
+        let is_trigger = !fired.is_empty() && fired.iter().all(|&f| f);
+        if is_trigger {
+            fired.iter_mut().for_each(|f| *f = false);
+        }
+        Ok(is_trigger)
+    }
+
+    fn is_pre_process(&self) -> bool {
+        any_pre_process(&self.triggers)
+    }
+}
+
+/// A deserializer for the `AnyTrigger`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: any
+///
+/// # The triggers, each configured as it would be on its own. The log is rolled
+/// # when any of them fires. Required.
+/// triggers:
+///   - kind: time
+///     interval: 1 day
+///   - kind: size
+///     limit: 100 mb
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct AnyTriggerDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for AnyTriggerDeserializer {
+    type Trait = dyn Trigger;
+
+    type Config = CompositeTriggerConfig;
+
+    fn deserialize(
+        &self,
+        config: CompositeTriggerConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Trigger>> {
+        Ok(Box::new(AnyTrigger::new(
+            config.deserialize(deserializers)?,
+        )))
+    }
+}
+
+/// A deserializer for the `AllTrigger`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: all
+///
+/// # The triggers, each configured as it would be on its own. The log is rolled
+/// # once all of them have fired since the last roll. Required.
+/// triggers:
+///   - kind: time
+///     interval: 1 day
+///   - kind: size
+///     limit: 1 mb
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct AllTriggerDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for AllTriggerDeserializer {
+    type Trait = dyn Trigger;
+
+    type Config = CompositeTriggerConfig;
+
+    fn deserialize(
+        &self,
+        config: CompositeTriggerConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Trigger>> {
+        Ok(Box::new(AllTrigger::new(
+            config.deserialize(deserializers)?,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        path::Path,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use super::*;
+
+    /// A trigger which fires when told to, counting how often it is checked.
+    #[derive(Debug, Default)]
+    struct Manual {
+        pre_process: bool,
+        fire: Arc<AtomicBool>,
+        checks: Arc<AtomicUsize>,
+    }
+
+    impl Trigger for Manual {
+        fn trigger(&self, _: &LogFile) -> anyhow::Result<bool> {
+            self.checks.fetch_add(1, Ordering::SeqCst);
+            Ok(self.fire.swap(false, Ordering::SeqCst))
+        }
+
+        fn is_pre_process(&self) -> bool {
+            self.pre_process
+        }
+    }
+
+    fn manual(pre_process: bool) -> (Box<dyn Trigger>, Arc<AtomicBool>, Arc<AtomicUsize>) {
+        let trigger = Manual {
+            pre_process,
+            ..Manual::default()
+        };
+        let (fire, checks) = (trigger.fire.clone(), trigger.checks.clone());
+        (Box::new(trigger), fire, checks)
+    }
+
+    fn check(trigger: &dyn Trigger) -> bool {
+        let file = LogFile {
+            writer: &mut None,
+            path: Path::new("log"),
+            len: 0,
+        };
+        trigger.trigger(&file).unwrap()
+    }
+
+    #[test]
+    fn any() {
+        let (a, fire_a, checks_a) = manual(false);
+        let (b, fire_b, checks_b) = manual(true);
+        let trigger = AnyTrigger::new(vec![a, b]);
+        assert!(trigger.is_pre_process());
+
+        assert!(!check(&trigger));
+        fire_a.store(true, Ordering::SeqCst);
+        assert!(check(&trigger));
+        fire_a.store(true, Ordering::SeqCst);
+        fire_b.store(true, Ordering::SeqCst);
+        assert!(check(&trigger));
+        assert!(!check(&trigger));
+
+        // Siblings are checked even when an earlier trigger fires.
+        assert_eq!(checks_a.load(Ordering::SeqCst), 4);
+        assert_eq!(checks_b.load(Ordering::SeqCst), 4);
+
+        assert!(!check(&AnyTrigger::new(vec![])));
+    }
+
+    #[test]
+    fn all() {
+        let (a, fire_a, _) = manual(false);
+        let (b, fire_b, _) = manual(false);
+        let trigger = AllTrigger::new(vec![a, b]);
+        assert!(!trigger.is_pre_process());
+
+        fire_a.store(true, Ordering::SeqCst);
+        assert!(!check(&trigger));
+        assert!(!check(&trigger));
+        fire_b.store(true, Ordering::SeqCst);
+        assert!(check(&trigger));
+
+        // Both have to fire again after a roll.
+        fire_b.store(true, Ordering::SeqCst);
+        assert!(!check(&trigger));
+        fire_a.store(true, Ordering::SeqCst);
+        assert!(check(&trigger));
+
+        assert!(!check(&AllTrigger::new(vec![])));
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "size_trigger"))]
+    fn deserialize() {
+        let config = "
+kind: any
+triggers:
+  - kind: size
+    limit: 10
+  - kind: all
+    triggers:
+      - kind: size
+        limit: 5
+";
+        let config = ::serde_yaml::from_str::<TriggerConfig>(config).unwrap();
+        let trigger = Deserializers::default()
+            .deserialize::<dyn Trigger>(&config.kind, config.config)
+            .unwrap();
+        assert!(!trigger.is_pre_process());
+
+        let file = LogFile {
+            writer: &mut None,
+            path: Path::new("log"),
+            len: 6,
+        };
+        assert!(trigger.trigger(&file).unwrap());
+
+        let config = ::serde_yaml::from_str::<TriggerConfig>("{kind: all, triggers: []}").unwrap();
+        assert!(Deserializers::default()
+            .deserialize::<dyn Trigger>(&config.kind, config.config)
+            .is_err());
+    }
+}
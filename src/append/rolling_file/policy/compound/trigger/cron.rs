@@ -0,0 +1,549 @@
+//! The cron trigger.
+//!
+//! Requires the `cron_trigger` feature.
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+};
+
+use crate::append::rolling_file::{
+    policy::compound::trigger::{file_start_time, timezone::now, Timezone, Trigger},
+    LogFile,
+};
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+
+/// Configuration for the cron trigger.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CronTriggerConfig {
+    schedule: String,
+    #[serde(default)]
+    timezone: Timezone,
+}
+
+/// A cron schedule, with one bit set per matching value of each field.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+struct Schedule {
+    seconds: u64,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How far ahead to look for a matching date. A schedule for the 29th of
+/// February may not match for eight years.
+const MAX_DAYS: u32 = 366 * 8;
+
+fn parse_value(s: &str, names: &[&str], offset: u32) -> anyhow::Result<u32> {
+    if let Some(i) = names.iter().position(|n| n.eq_ignore_ascii_case(s)) {
+        return Ok(i as u32 + offset);
+    }
+    s.parse()
+        .map_err(|_| anyhow!("invalid value `{}` in cron schedule", s))
+}
+
+/// Parses a field into a bitset, returning whether it is restricted, that
+/// is, doesn't start with `*` or `?`.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> anyhow::Result<(u64, bool)> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, Some(step)),
+                _ => bail!("invalid step `{}` in cron schedule", step),
+            },
+            None => (part, None),
+        };
+
+        let (start, end) = if range == "*" || range == "?" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, names, min)?,
+                parse_value(end, names, min)?,
+            )
+        } else {
+            let value = parse_value(range, names, min)?;
+            (value, if step.is_some() { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            bail!(
+                "`{}` is out of range in cron schedule, expected values between {} and {}",
+                part,
+                min,
+                max
+            );
+        }
+
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok((bits, !field.starts_with('*') && !field.starts_with('?')))
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Schedule> {
+        let s = match s.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            s => s,
+        };
+
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let fields = match fields.len() {
+            5 => {
+                let mut with_seconds = vec!["0"];
+                with_seconds.extend(fields);
+                with_seconds
+            }
+            6 => fields,
+            n => bail!("cron schedule must have 5 or 6 fields, found {}", n),
+        };
+
+        let (seconds, _) = parse_field(fields[0], 0, 59, &[])?;
+        let (minutes, _) = parse_field(fields[1], 0, 59, &[])?;
+        let (hours, _) = parse_field(fields[2], 0, 23, &[])?;
+        let (days, days_restricted) = parse_field(fields[3], 1, 31, &[])?;
+        let (months, _) = parse_field(fields[4], 1, 12, MONTHS)?;
+        let (mut weekdays, weekdays_restricted) = parse_field(fields[5], 0, 7, WEEKDAYS)?;
+        // Both 0 and 7 are Sunday.
+        if weekdays & 1 << 7 != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+
+        Ok(Schedule {
+            seconds,
+            minutes,
+            hours,
+            days,
+            months,
+            weekdays,
+            days_restricted,
+            weekdays_restricted,
+        })
+    }
+}
+
+fn matches(bits: u64, value: u32) -> bool {
+    bits & 1 << value != 0
+}
+
+/// Returns the set values of `bits` which are at least `min`.
+fn values(bits: u64, min: u32) -> impl Iterator<Item = u32> {
+    (min..64).filter(move |&v| matches(bits, v))
+}
+
+impl Schedule {
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if !matches(self.months, date.month()) {
+            return false;
+        }
+        let day = matches(self.days, date.day());
+        let weekday = matches(self.weekdays, date.weekday().num_days_from_sunday());
+        // As in cron, a date matches either restricted day field.
+        if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    fn first_time_from(&self, from: NaiveTime) -> Option<NaiveTime> {
+        for hour in values(self.hours, from.hour()) {
+            let min_minute = if hour == from.hour() {
+                from.minute()
+            } else {
+                0
+            };
+            for minute in values(self.minutes, min_minute) {
+                let min_second = if hour == from.hour() && minute == from.minute() {
+                    from.second()
+                } else {
+                    0
+                };
+                if let Some(second) = values(self.seconds, min_second).next() {
+                    return NaiveTime::from_hms_opt(hour, minute, second);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the first wall clock time matching the schedule after `after`.
+    fn next_local(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let from = after.with_nanosecond(0)? + chrono::Duration::seconds(1);
+        let mut date = from.date();
+        let mut time = from.time();
+        for _ in 0..MAX_DAYS {
+            if self.matches_date(date) {
+                if let Some(time) = self.first_time_from(time) {
+                    return Some(date.and_time(time));
+                }
+            }
+            date = date.succ_opt()?;
+            time = NaiveTime::MIN;
+        }
+        None
+    }
+
+    /// Returns the first instant matching the schedule after `after`.
+    ///
+    /// A matching wall clock time which occurs twice because of a daylight
+    /// saving time transition only matches its first occurrence, and one
+    /// which is skipped matches the instant of the transition.
+    fn next(&self, timezone: Timezone, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut local = timezone.to_local(after);
+        loop {
+            local = self.next_local(local)?;
+            let time = timezone.resolve(&local);
+            if time > after {
+                return Some(time);
+            }
+        }
+    }
+}
+
+/// A trigger which rolls the log on a cron schedule.
+///
+/// The schedule has the five standard cron fields, minute, hour, day of
+/// month, month and day of week, optionally preceded by a seconds field. Each
+/// field is `*`, a value, a range `a-b`, a step `*/n` or `a-b/n`, or a comma
+/// separated list of those. Months and days of the week may be given by their
+/// three letter English names, and both 0 and 7 are Sunday. `?` is accepted
+/// as a synonym for `*`. As in cron, if both the day of month and the day of
+/// week are restricted, a day matching either one matches. The `@yearly`,
+/// `@monthly`, `@weekly`, `@daily` and `@hourly` shorthands are also
+/// supported.
+///
+/// The schedule is evaluated on the wall clock of the trigger's timezone.
+/// A time which occurs twice when clocks are set back only rolls the log at
+/// its first occurrence, and a time which is skipped when clocks are set
+/// forward rolls the log at the moment of the transition.
+///
+/// As with the `TimeTrigger`, the first time the trigger is checked, the roll
+/// time is recomputed from the time the log file was created, so that a log
+/// file left over from an earlier run is rolled if the schedule matched since.
+#[derive(Debug)]
+pub struct CronTrigger {
+    schedule: Schedule,
+    timezone: Timezone,
+    next_roll_time: RwLock<Option<DateTime<Utc>>>,
+    file_checked: AtomicBool,
+}
+
+impl CronTrigger {
+    /// Returns a new trigger which rolls the log on the cron `schedule` in
+    /// `timezone`.
+    ///
+    /// Fails if the schedule is invalid or never matches, like `0 0 30 2 *`.
+    pub fn new(schedule: &str, timezone: Timezone) -> anyhow::Result<CronTrigger> {
+        let expr = schedule;
+        let schedule = schedule.parse::<Schedule>()?;
+        let next_roll_time = match schedule.next(timezone, now()) {
+            Some(next_roll_time) => next_roll_time,
+            None => bail!("cron schedule `{}` never matches", expr),
+        };
+        Ok(CronTrigger {
+            schedule,
+            timezone,
+            next_roll_time: RwLock::new(Some(next_roll_time)),
+            file_checked: AtomicBool::new(false),
+        })
+    }
+}
+
+impl Trigger for CronTrigger {
+    fn trigger(&self, file: &LogFile) -> anyhow::Result<bool> {
+        let current = now();
+        let mut next_roll_time = self.next_roll_time.write().unwrap();
+        if !self.file_checked.swap(true, Ordering::Relaxed) {
+            if let Some(start) = file_start_time(file).filter(|&t| t < current) {
+                *next_roll_time = self.schedule.next(self.timezone, start);
+            }
+        }
+        let is_trigger = next_roll_time.map_or(false, |t| current >= t);
+        if is_trigger {
+            *next_roll_time = self.schedule.next(self.timezone, current);
+        }
+        Ok(is_trigger)
+    }
+
+    fn is_pre_process(&self) -> bool {
+        true
+    }
+}
+
+/// A deserializer for the `CronTrigger`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: cron
+///
+/// # The cron schedule, with five fields (minute, hour, day of month, month, day of week)
+/// # or six fields (with seconds first). Required.
+/// schedule: "0 0,12 * * MON-FRI"
+///
+/// # The timezone in which the schedule is evaluated: `utc`, `local`, or the name of an
+/// # IANA timezone like `Europe/Berlin`, which requires the `timezones` feature.
+/// # Defaults to `local`.
+/// timezone: utc
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct CronTriggerDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for CronTriggerDeserializer {
+    type Trait = dyn Trigger;
+
+    type Config = CronTriggerConfig;
+
+    fn deserialize(
+        &self,
+        config: CronTriggerConfig,
+        _: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Trigger>> {
+        Ok(Box::new(CronTrigger::new(
+            &config.schedule,
+            config.timezone,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+    use mock_instant::MockClock;
+    use std::{path::Path, time::Duration};
+
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap())
+    }
+
+    fn set_time(time: DateTime<Utc>) {
+        MockClock::set_system_time(Duration::from_secs(time.timestamp() as u64));
+    }
+
+    fn next_times(schedule: &str, timezone: Timezone, after: &str, n: usize) -> Vec<DateTime<Utc>> {
+        let schedule = schedule.parse::<Schedule>().unwrap();
+        let mut time = utc(after);
+        (0..n)
+            .map(|_| {
+                time = schedule.next(timezone, time).unwrap();
+                time
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse() {
+        assert!("* * * *".parse::<Schedule>().is_err());
+        assert!("60 * * * *".parse::<Schedule>().is_err());
+        assert!("* * * * 8".parse::<Schedule>().is_err());
+        assert!("* * 0 * *".parse::<Schedule>().is_err());
+        assert!("*/0 * * * *".parse::<Schedule>().is_err());
+        assert!("5-1 * * * *".parse::<Schedule>().is_err());
+        assert!("* * * FOO *".parse::<Schedule>().is_err());
+
+        // Schedules which never match are rejected by the trigger.
+        for schedule in ["0 0 31 2 *", "0 0 30 2 *", "0 0 31 apr,jun,sep,nov *"] {
+            let err = CronTrigger::new(schedule, Timezone::Utc).unwrap_err();
+            assert!(err.to_string().contains("never matches"), "{}", schedule);
+        }
+        assert!(CronTrigger::new("0 0 29 2 *", Timezone::Utc).is_ok());
+
+        let schedule = "0 0,12 * jan-MAR mon-fri".parse::<Schedule>().unwrap();
+        assert_eq!(schedule.seconds, 1);
+        assert_eq!(schedule.hours, 1 | 1 << 12);
+        assert_eq!(schedule.months, 0b1110);
+        assert_eq!(schedule.weekdays, 0b0111110);
+        assert!(!schedule.days_restricted);
+        assert!(schedule.weekdays_restricted);
+
+        let schedule = "*/20 1-10/4 * * * 7".parse::<Schedule>().unwrap();
+        assert_eq!(schedule.seconds, 1 | 1 << 20 | 1 << 40);
+        assert_eq!(schedule.minutes, 1 << 1 | 1 << 5 | 1 << 9);
+        assert_eq!(schedule.weekdays, 1);
+
+        assert_eq!(
+            "@daily".parse::<Schedule>().unwrap(),
+            "0 0 * * *".parse::<Schedule>().unwrap()
+        );
+    }
+
+    #[test]
+    fn next() {
+        // Twice a day on weekdays. 2024-03-01 is a Friday.
+        assert_eq!(
+            next_times(
+                "0 0,12 * * MON-FRI",
+                Timezone::Utc,
+                "2024-03-01 06:00:00",
+                3
+            ),
+            [
+                utc("2024-03-01 12:00:00"),
+                utc("2024-03-04 00:00:00"),
+                utc("2024-03-04 12:00:00"),
+            ]
+        );
+        // Either day field matches when both are restricted.
+        assert_eq!(
+            next_times("0 0 1 * SUN", Timezone::Utc, "2024-03-01 06:00:00", 3),
+            [
+                utc("2024-03-03 00:00:00"),
+                utc("2024-03-10 00:00:00"),
+                utc("2024-03-17 00:00:00"),
+            ]
+        );
+        assert_eq!(
+            next_times("@monthly", Timezone::Utc, "2024-12-31 23:59:59", 2),
+            [utc("2025-01-01 00:00:00"), utc("2025-02-01 00:00:00")]
+        );
+        assert_eq!(
+            next_times("0 0 29 2 *", Timezone::Utc, "2024-03-01 00:00:00", 1),
+            [utc("2028-02-29 00:00:00")]
+        );
+        assert!("0 0 30 2 *"
+            .parse::<Schedule>()
+            .unwrap()
+            .next(Timezone::Utc, utc("2024-01-01 00:00:00"))
+            .is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "timezones")]
+    fn dst() {
+        let berlin = Timezone::Named(chrono_tz::Europe::Berlin);
+
+        // 02:30 doesn't exist on the 31st of March, so the log rolls when
+        // clocks go forward.
+        assert_eq!(
+            next_times("30 2 * * *", berlin, "2024-03-30 12:00:00", 3),
+            [
+                utc("2024-03-31 01:00:00"),
+                utc("2024-04-01 00:30:00"),
+                utc("2024-04-02 00:30:00"),
+            ]
+        );
+
+        // 02:30 occurs twice on the 27th of October, and only the first
+        // occurrence matches.
+        assert_eq!(
+            next_times("30 2 * * *", berlin, "2024-10-26 12:00:00", 2),
+            [utc("2024-10-27 00:30:00"), utc("2024-10-28 01:30:00")]
+        );
+        // Midnight on either side of the 25 hour day.
+        assert_eq!(
+            next_times("@daily", berlin, "2024-10-26 12:00:00", 2),
+            [utc("2024-10-26 22:00:00"), utc("2024-10-27 23:00:00")]
+        );
+        // Hourly rolls skip the repeated hour.
+        assert_eq!(
+            next_times("@hourly", berlin, "2024-10-26 23:30:00", 3),
+            [
+                utc("2024-10-27 00:00:00"),
+                utc("2024-10-27 02:00:00"),
+                utc("2024-10-27 03:00:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn trigger() {
+        let file = LogFile {
+            writer: &mut None,
+            path: Path::new("log"),
+            len: 0,
+        };
+
+        set_time(utc("2024-03-01 11:59:00"));
+        let trigger = CronTrigger::new("0 0,12 * * *", Timezone::Utc).unwrap();
+        assert!(trigger.is_pre_process());
+        assert!(!trigger.trigger(&file).unwrap());
+
+        MockClock::advance_system_time(Duration::from_secs(60));
+        assert!(trigger.trigger(&file).unwrap());
+        assert!(!trigger.trigger(&file).unwrap());
+
+        // A roll missed while nothing was logged happens once.
+        MockClock::advance_system_time(Duration::from_secs(36 * 60 * 60));
+        assert!(trigger.trigger(&file).unwrap());
+        assert!(!trigger.trigger(&file).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "timezones")]
+    fn trigger_dst() {
+        let file = LogFile {
+            writer: &mut None,
+            path: Path::new("log"),
+            len: 0,
+        };
+        let berlin = Timezone::Named(chrono_tz::Europe::Berlin);
+
+        set_time(utc("2024-03-30 23:00:00"));
+        let trigger = CronTrigger::new("30 2 * * *", berlin).unwrap();
+
+        set_time(utc("2024-03-31 00:59:59"));
+        assert!(!trigger.trigger(&file).unwrap());
+        set_time(utc("2024-03-31 01:00:00"));
+        assert!(trigger.trigger(&file).unwrap());
+        set_time(utc("2024-03-31 01:30:00"));
+        assert!(!trigger.trigger(&file).unwrap());
+    }
+
+    #[test]
+    fn resumes_file_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        std::fs::write(&path, b"old").unwrap();
+        let file = LogFile {
+            writer: &mut None,
+            path: &path,
+            len: 3,
+        };
+        let started = file_start_time(&file).unwrap();
+        let next = "@hourly"
+            .parse::<Schedule>()
+            .unwrap()
+            .next(Timezone::Utc, started)
+            .unwrap();
+
+        set_time(next - chrono::Duration::seconds(1));
+        let trigger = CronTrigger::new("@hourly", Timezone::Utc).unwrap();
+        assert!(!trigger.trigger(&file).unwrap());
+
+        set_time(next + chrono::Duration::seconds(1));
+        let trigger = CronTrigger::new("@hourly", Timezone::Utc).unwrap();
+        assert!(trigger.trigger(&file).unwrap());
+        assert!(!trigger.trigger(&file).unwrap());
+    }
+}
@@ -15,6 +15,45 @@ pub mod time;
 #[cfg(feature = "onstartup_trigger")]
 pub mod onstartup;
 
+#[cfg(feature = "composite_trigger")]
+pub mod composite;
+
+#[cfg(feature = "cron_trigger")]
+pub mod cron;
+
+#[cfg(any(
+    feature = "time_trigger",
+    feature = "cron_trigger",
+    feature = "time_based_roller"
+))]
+// The time-based roller only converts instants to its timezone.
+#[cfg_attr(
+    not(any(feature = "time_trigger", feature = "cron_trigger")),
+    allow(dead_code)
+)]
+mod timezone;
+#[cfg(any(
+    feature = "time_trigger",
+    feature = "cron_trigger",
+    feature = "time_based_roller"
+))]
+pub use self::timezone::Timezone;
+
+/// Returns when the log file was started, if it has any contents.
+///
+/// This is its creation time where the platform records it, and otherwise its
+/// last modification time. Time-based triggers use it to tell whether a log
+/// file left over from a previous run belongs to an earlier period.
+#[cfg(any(feature = "time_trigger", feature = "cron_trigger"))]
+fn file_start_time(file: &LogFile) -> Option<chrono::DateTime<chrono::Utc>> {
+    if file.len_estimate() == 0 {
+        return None;
+    }
+    let metadata = std::fs::metadata(file.path()).ok()?;
+    let time = metadata.created().or_else(|_| metadata.modified()).ok()?;
+    Some(time.into())
+}
+
 /// A trait which identifies if the active log file should be rolled over.
 pub trait Trigger: fmt::Debug + Send + Sync + 'static {
     /// Determines if the active log file should be rolled over.
@@ -2,19 +2,21 @@
 //!
 //! Requires the `time_trigger` feature.
 
-#[cfg(test)]
-use chrono::NaiveDateTime;
-use chrono::{DateTime, Datelike, Duration, Local, TimeZone, Timelike};
-#[cfg(test)]
-use mock_instant::{SystemTime, UNIX_EPOCH};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
 use rand::Rng;
 #[cfg(feature = "config_parsing")]
 use serde::de;
 #[cfg(feature = "config_parsing")]
 use std::fmt;
-use std::sync::RwLock;
-
-use crate::append::rolling_file::{policy::compound::trigger::Trigger, LogFile};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    RwLock,
+};
+
+use crate::append::rolling_file::{
+    policy::compound::trigger::{file_start_time, timezone::now, Timezone, Trigger},
+    LogFile,
+};
 #[cfg(feature = "config_parsing")]
 use crate::config::{Deserialize, Deserializers};
 
@@ -28,6 +30,8 @@ pub struct TimeTriggerConfig {
     modulate: bool,
     #[serde(default)]
     max_random_delay: u64,
+    #[serde(default)]
+    timezone: Timezone,
 }
 
 #[cfg(not(feature = "config_parsing"))]
@@ -37,13 +41,29 @@ pub struct TimeTriggerConfig {
     interval: TimeTriggerInterval,
     modulate: bool,
     max_random_delay: u64,
+    timezone: Timezone,
 }
 
 /// A trigger which rolls the log once it has passed a certain time.
+///
+/// Day, week, month and year intervals end at midnight on the wall clock of
+/// the configured timezone, which defaults to the local timezone of the host,
+/// so a daily interval spans 23 or 25 hours on days with a daylight saving
+/// time transition. If midnight is skipped by a transition, the interval ends
+/// at the transition. Second, minute and hour intervals are measured in
+/// elapsed time from the start of the current unit.
+///
+/// The first time the trigger is checked, the roll time is recomputed from
+/// the time the log file was created, or last modified where the platform
+/// doesn't record creation times. A log file left over from an earlier run
+/// which belongs to an earlier period is therefore rolled before the first
+/// record is written to it, and restarting the application doesn't postpone
+/// the next roll.
 #[derive(Debug)]
 pub struct TimeTrigger {
     config: TimeTriggerConfig,
-    next_roll_time: RwLock<DateTime<Local>>,
+    next_roll_time: RwLock<DateTime<Utc>>,
+    file_checked: AtomicBool,
 }
 
 /// The TimeTrigger supports the following units (case insensitive):
@@ -176,127 +196,104 @@ impl TimeTrigger {
     /// Returns a new trigger which rolls the log once it has passed the
     /// specified time.
     pub fn new(config: TimeTriggerConfig) -> TimeTrigger {
-        #[cfg(test)]
-        let current = {
-            let now: std::time::Duration = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("system time before Unix epoch");
-            NaiveDateTime::from_timestamp_opt(now.as_secs() as i64, now.subsec_nanos())
-                .unwrap()
-                .and_local_timezone(Local)
-                .unwrap()
-        };
+        TimeTrigger {
+            config,
+            next_roll_time: RwLock::new(TimeTrigger::next_roll_time(config, now())),
+            file_checked: AtomicBool::new(false),
+        }
+    }
 
-        #[cfg(not(test))]
-        let current = Local::now();
-        let next_time = TimeTrigger::get_next_time(current, config.interval, config.modulate);
-        let next_roll_time = if config.max_random_delay > 0 {
+    fn next_roll_time(config: TimeTriggerConfig, current: DateTime<Utc>) -> DateTime<Utc> {
+        let next_time =
+            TimeTrigger::get_next_time(current, config.interval, config.modulate, config.timezone);
+        if config.max_random_delay > 0 {
             let random_delay = rand::thread_rng().gen_range(0..config.max_random_delay);
             next_time + Duration::seconds(random_delay as i64)
         } else {
             next_time
-        };
-
-        TimeTrigger {
-            config,
-            next_roll_time: RwLock::new(next_roll_time),
         }
     }
 
     fn get_next_time(
-        current: DateTime<Local>,
+        current: DateTime<Utc>,
         interval: TimeTriggerInterval,
         modulate: bool,
-    ) -> DateTime<Local> {
-        let year = current.year();
+        timezone: Timezone,
+    ) -> DateTime<Utc> {
+        let midnight = |date: NaiveDate| timezone.resolve(&date.and_hms_opt(0, 0, 0).unwrap());
+        let local = timezone.to_local(current);
+
+        let year = local.year();
         if let TimeTriggerInterval::Year(n) = interval {
             let n = n as i32;
             let increment = if modulate { n - year % n } else { n };
             let year_new = year + increment;
-            return Local.with_ymd_and_hms(year_new, 1, 1, 0, 0, 0).unwrap();
+            return midnight(NaiveDate::from_ymd_opt(year_new, 1, 1).unwrap());
         }
 
         if let TimeTriggerInterval::Month(n) = interval {
-            let month0 = current.month0();
+            let month0 = local.month0();
             let n = n as u32;
             let increment = if modulate { n - month0 % n } else { n };
             let num_months = (year as u32) * 12 + month0;
             let num_months_new = num_months + increment;
             let year_new = (num_months_new / 12) as i32;
             let month_new = (num_months_new) % 12 + 1;
-            return Local
-                .with_ymd_and_hms(year_new, month_new, 1, 0, 0, 0)
-                .unwrap();
+            return midnight(NaiveDate::from_ymd_opt(year_new, month_new, 1).unwrap());
         }
 
-        let month = current.month();
-        let day = current.day();
+        let date = local.date();
         if let TimeTriggerInterval::Week(n) = interval {
-            let week0 = current.iso_week().week0() as i64;
-            let weekday = current.weekday().num_days_from_monday() as i64; // Monday is the first day of the week
-            let time = Local.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap();
+            let week0 = local.iso_week().week0() as i64;
+            let weekday = local.weekday().num_days_from_monday() as i64; // Monday is the first day of the week
             let increment = if modulate { n - week0 % n } else { n };
-            return time + Duration::weeks(increment) - Duration::days(weekday);
+            return midnight(date + Duration::weeks(increment) - Duration::days(weekday));
         }
 
         if let TimeTriggerInterval::Day(n) = interval {
-            let ordinal0 = current.ordinal0() as i64;
-            let time = Local.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap();
+            let ordinal0 = local.ordinal0() as i64;
             let increment = if modulate { n - ordinal0 % n } else { n };
-            return time + Duration::days(increment);
+            return midnight(date + Duration::days(increment));
         }
 
-        let hour = current.hour();
+        // Shorter intervals are measured in elapsed time, so that they keep
+        // their length across daylight saving time transitions.
+        let current = current.with_nanosecond(0).unwrap();
+        let sec = local.second();
+        let min = local.minute();
+        let hour = local.hour();
         if let TimeTriggerInterval::Hour(n) = interval {
-            let time = Local
-                .with_ymd_and_hms(year, month, day, hour, 0, 0)
-                .unwrap();
+            let time = current - Duration::minutes(min as i64) - Duration::seconds(sec as i64);
             let increment = if modulate { n - (hour as i64) % n } else { n };
             return time + Duration::hours(increment);
         }
 
-        let min = current.minute();
         if let TimeTriggerInterval::Minute(n) = interval {
-            let time = Local
-                .with_ymd_and_hms(year, month, day, hour, min, 0)
-                .unwrap();
+            let time = current - Duration::seconds(sec as i64);
             let increment = if modulate { n - (min as i64) % n } else { n };
             return time + Duration::minutes(increment);
         }
 
-        let sec = current.second();
         if let TimeTriggerInterval::Second(n) = interval {
-            let time = Local
-                .with_ymd_and_hms(year, month, day, hour, min, sec)
-                .unwrap();
             let increment = if modulate { n - (sec as i64) % n } else { n };
-            return time + Duration::seconds(increment);
+            return current + Duration::seconds(increment);
         }
         panic!("Should not reach here!");
     }
 }
 
 impl Trigger for TimeTrigger {
-    fn trigger(&self, _file: &LogFile) -> anyhow::Result<bool> {
-        #[cfg(test)]
-        let current = {
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("system time before Unix epoch");
-            NaiveDateTime::from_timestamp_opt(now.as_secs() as i64, now.subsec_nanos())
-                .unwrap()
-                .and_local_timezone(Local)
-                .unwrap()
-        };
-
-        #[cfg(not(test))]
-        let current: DateTime<Local> = Local::now();
+    fn trigger(&self, file: &LogFile) -> anyhow::Result<bool> {
+        let current = now();
         let mut next_roll_time = self.next_roll_time.write().unwrap();
+        if !self.file_checked.swap(true, Ordering::Relaxed) {
+            if let Some(start) = file_start_time(file).filter(|&t| t < current) {
+                *next_roll_time = TimeTrigger::next_roll_time(self.config, start);
+            }
+        }
         let is_trigger = current >= *next_roll_time;
         if is_trigger {
-            let tmp = TimeTrigger::new(self.config);
-            let time_new = tmp.next_roll_time.read().unwrap();
-            *next_roll_time = *time_new;
+            *next_roll_time = TimeTrigger::next_roll_time(self.config, current);
         }
         Ok(is_trigger)
     }
@@ -317,6 +314,18 @@ impl Trigger for TimeTrigger {
 /// # "second(s)", "minute(s)", "hour(s)", "day(s)", "week(s)", "month(s)", "year(s)". The unit defaults to
 /// # second if not specified.
 /// interval: 7 day
+///
+/// # Whether the interval is aligned to its unit, so that for example a 4 hour interval
+/// # ends at midnight, 4 am, 8 am and so on. Defaults to false.
+/// modulate: false
+///
+/// # The maximum number of seconds to randomly delay a roll by. Defaults to 0.
+/// max_random_delay: 0
+///
+/// # The timezone whose wall clock determines when day, week, month and year intervals
+/// # end: `utc`, `local`, or the name of an IANA timezone like `Europe/Berlin`, which
+/// # requires the `timezones` feature. Defaults to `local`.
+/// timezone: utc
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
@@ -359,6 +368,7 @@ mod test {
             interval,
             modulate,
             max_random_delay: 0,
+            timezone: Timezone::Local,
         };
 
         let trigger = TimeTrigger::new(config);
@@ -382,7 +392,7 @@ mod test {
         let month_in_milli = day_in_milli * 31;
         let year_in_milli = day_in_milli * 365;
 
-        let test_list = vec![
+        let test_list = [
             (TimeTriggerInterval::Second(1), second_in_milli),
             (TimeTriggerInterval::Minute(1), minute_in_milli),
             (TimeTriggerInterval::Hour(1), hour_in_milli),
@@ -408,7 +418,7 @@ mod test {
             );
         }
 
-        let test_list = vec![
+        let test_list = [
             (TimeTriggerInterval::Second(3), 3 * second_in_milli),
             (TimeTriggerInterval::Minute(3), 3 * minute_in_milli),
             (TimeTriggerInterval::Hour(3), 3 * hour_in_milli),
@@ -432,7 +442,7 @@ mod test {
     #[test]
     #[cfg(feature = "yaml_format")]
     fn test_serde() {
-        let test_error = vec![
+        let test_error = [
             "abc",   // // str none none
             "",      // none
             "5 das", // bad unit
@@ -441,7 +451,7 @@ mod test {
         ];
 
         for interval in test_error.iter() {
-            let error = ::serde_yaml::from_str::<TimeTriggerInterval>(&interval);
+            let error = ::serde_yaml::from_str::<TimeTriggerInterval>(interval);
             assert!(error.is_err());
         }
 
@@ -471,7 +481,7 @@ mod test {
             ("1 years", TimeTriggerInterval::Year(1)),
         ];
         for (interval, expected) in test_ok.iter() {
-            let interval = format!("{}", interval);
+            let interval = interval.to_string();
             let interval = ::serde_yaml::from_str::<TimeTriggerInterval>(&interval).unwrap();
             assert_eq!(interval, *expected);
         }
@@ -489,8 +499,187 @@ mod test {
             interval: TimeTriggerInterval::Minute(2),
             modulate: true,
             max_random_delay: 0,
+            timezone: Timezone::Local,
         };
         let trigger = TimeTrigger::new(config);
         assert!(trigger.is_pre_process());
     }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        use chrono::TimeZone;
+
+        Utc.from_utc_datetime(
+            &chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap(),
+        )
+    }
+
+    /// Returns the next `n` roll times after `after`.
+    fn next_times(
+        interval: TimeTriggerInterval,
+        timezone: Timezone,
+        after: &str,
+        n: usize,
+    ) -> Vec<DateTime<Utc>> {
+        let mut time = utc(after);
+        (0..n)
+            .map(|_| {
+                time = TimeTrigger::get_next_time(time, interval, false, timezone);
+                time
+            })
+            .collect()
+    }
+
+    #[test]
+    fn timezone() {
+        assert_eq!(
+            next_times(
+                TimeTriggerInterval::Day(1),
+                Timezone::Utc,
+                "2024-03-30 12:00:00",
+                2
+            ),
+            [utc("2024-03-31 00:00:00"), utc("2024-04-01 00:00:00")]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "timezones")]
+    fn dst() {
+        let berlin = Timezone::Named(chrono_tz::Europe::Berlin);
+
+        // A 23 hour day.
+        assert_eq!(
+            next_times(
+                TimeTriggerInterval::Day(1),
+                berlin,
+                "2024-03-30 12:00:00",
+                2
+            ),
+            [utc("2024-03-30 23:00:00"), utc("2024-03-31 22:00:00")]
+        );
+        // A 25 hour day.
+        assert_eq!(
+            next_times(
+                TimeTriggerInterval::Day(1),
+                berlin,
+                "2024-10-26 12:00:00",
+                2
+            ),
+            [utc("2024-10-26 22:00:00"), utc("2024-10-27 23:00:00")]
+        );
+        // Weeks and months end at local midnight too.
+        assert_eq!(
+            next_times(
+                TimeTriggerInterval::Week(1),
+                berlin,
+                "2024-03-27 12:00:00",
+                2
+            ),
+            [utc("2024-03-31 22:00:00"), utc("2024-04-07 22:00:00")]
+        );
+        assert_eq!(
+            next_times(
+                TimeTriggerInterval::Month(1),
+                berlin,
+                "2024-10-15 12:00:00",
+                1
+            ),
+            [utc("2024-10-31 23:00:00")]
+        );
+        // Hours keep their length through the repeated hour.
+        assert_eq!(
+            next_times(
+                TimeTriggerInterval::Hour(1),
+                berlin,
+                "2024-10-27 00:30:00",
+                2
+            ),
+            [utc("2024-10-27 01:00:00"), utc("2024-10-27 02:00:00")]
+        );
+
+        // Midnight is skipped in Santiago when clocks go forward, so the day
+        // ends at the transition.
+        let santiago = Timezone::Named(chrono_tz::America::Santiago);
+        assert_eq!(
+            next_times(
+                TimeTriggerInterval::Day(1),
+                santiago,
+                "2024-09-07 12:00:00",
+                1
+            ),
+            [utc("2024-09-08 04:00:00")]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "timezones")]
+    fn trigger_dst() {
+        let file = tempfile::tempdir().unwrap();
+        let logfile = LogFile {
+            writer: &mut None,
+            path: file.path(),
+            len: 0,
+        };
+
+        MockClock::set_system_time(Duration::from_secs(
+            utc("2024-10-26 12:00:00").timestamp() as u64
+        ));
+        let trigger = TimeTrigger::new(TimeTriggerConfig {
+            interval: TimeTriggerInterval::Day(1),
+            modulate: false,
+            max_random_delay: 0,
+            timezone: Timezone::Named(chrono_tz::Europe::Berlin),
+        });
+
+        MockClock::advance_system_time(Duration::from_secs(10 * 60 * 60 - 1));
+        assert!(!trigger.trigger(&logfile).unwrap());
+        MockClock::advance_system_time(Duration::from_secs(1));
+        assert!(trigger.trigger(&logfile).unwrap());
+
+        // The next day is 25 hours long.
+        MockClock::advance_system_time(Duration::from_secs(24 * 60 * 60));
+        assert!(!trigger.trigger(&logfile).unwrap());
+        MockClock::advance_system_time(Duration::from_secs(60 * 60));
+        assert!(trigger.trigger(&logfile).unwrap());
+    }
+
+    #[test]
+    fn resumes_file_period() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        std::fs::write(&path, b"old").unwrap();
+        let logfile = LogFile {
+            writer: &mut None,
+            path: &path,
+            len: 3,
+        };
+        let config = TimeTriggerConfig {
+            interval: TimeTriggerInterval::Day(1),
+            modulate: false,
+            max_random_delay: 0,
+            timezone: Timezone::Utc,
+        };
+        let started = file_start_time(&logfile).unwrap();
+        let next = TimeTrigger::get_next_time(started, config.interval, false, config.timezone);
+
+        // Restarted in the same period as the file was started.
+        MockClock::set_system_time(Duration::from_secs(next.timestamp() as u64 - 1));
+        let trigger = TimeTrigger::new(config);
+        assert!(!trigger.trigger(&logfile).unwrap());
+
+        // Restarted in a later period.
+        MockClock::set_system_time(Duration::from_secs(next.timestamp() as u64 + 60));
+        let trigger = TimeTrigger::new(config);
+        assert!(trigger.trigger(&logfile).unwrap());
+        assert!(!trigger.trigger(&logfile).unwrap());
+
+        // An empty file is never rolled on startup.
+        let empty = LogFile {
+            writer: &mut None,
+            path: &path,
+            len: 0,
+        };
+        let trigger = TimeTrigger::new(config);
+        assert!(!trigger.trigger(&empty).unwrap());
+    }
 }
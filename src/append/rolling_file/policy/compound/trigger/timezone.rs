@@ -0,0 +1,221 @@
+//! Timezones for time-based triggers and rollers.
+
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, FixedOffset, LocalResult, NaiveDateTime, Offset, TimeZone, Utc};
+#[cfg(feature = "config_parsing")]
+use serde::de;
+
+/// The timezone in which a trigger computes its roll times, or in which a
+/// roller dates its archives.
+///
+/// Roll times are computed on the wall clock of the timezone, so a daily
+/// trigger rolls at midnight in that zone, including on days which are 23 or
+/// 25 hours long because of a daylight saving time transition.
+///
+/// A wall clock time which occurs twice, when clocks are set back, resolves
+/// to its first occurrence. A wall clock time which is skipped, when clocks
+/// are set forward, resolves to the instant of the transition.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Timezone {
+    /// Coordinated Universal Time.
+    Utc,
+    /// The local timezone of the host.
+    #[default]
+    Local,
+    /// A named timezone from the IANA timezone database.
+    ///
+    /// Requires the `timezones` feature.
+    #[cfg(feature = "timezones")]
+    Named(chrono_tz::Tz),
+}
+
+impl Timezone {
+    /// Returns the wall clock time of an instant.
+    pub(crate) fn to_local(self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Timezone::Utc => time.naive_utc(),
+            Timezone::Local => time.with_timezone(&chrono::Local).naive_local(),
+            #[cfg(feature = "timezones")]
+            Timezone::Named(tz) => time.with_timezone(&tz).naive_local(),
+        }
+    }
+
+    /// Returns an instant with the offset of the timezone at that instant.
+    #[cfg_attr(not(feature = "time_based_roller"), allow(dead_code))]
+    pub(crate) fn to_fixed(self, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+        let offset = match self {
+            Timezone::Utc => Utc.fix(),
+            Timezone::Local => *time.with_timezone(&chrono::Local).offset(),
+            #[cfg(feature = "timezones")]
+            Timezone::Named(tz) => time.with_timezone(&tz).offset().fix(),
+        };
+        time.with_timezone(&offset)
+    }
+
+    fn instants(self, time: &NaiveDateTime) -> LocalResult<DateTime<Utc>> {
+        match self {
+            Timezone::Utc => LocalResult::Single(Utc.from_utc_datetime(time)),
+            Timezone::Local => chrono::Local
+                .from_local_datetime(time)
+                .map(|t| t.with_timezone(&Utc)),
+            #[cfg(feature = "timezones")]
+            Timezone::Named(tz) => tz.from_local_datetime(time).map(|t| t.with_timezone(&Utc)),
+        }
+    }
+
+    /// Returns the instant of a wall clock time.
+    ///
+    /// An ambiguous time resolves to its first occurrence, and a skipped time
+    /// to the first instant whose wall clock time is later.
+    pub(crate) fn resolve(self, time: &NaiveDateTime) -> DateTime<Utc> {
+        match self.instants(time) {
+            LocalResult::Single(t) => t,
+            LocalResult::Ambiguous(earliest, _) => earliest,
+            LocalResult::None => self.first_instant_after(time),
+        }
+    }
+
+    /// Returns the first instant whose wall clock time is at or after `time`,
+    /// to the second.
+    fn first_instant_after(self, time: &NaiveDateTime) -> DateTime<Utc> {
+        // No timezone is offset from UTC by a day or more, so the instant is
+        // between these bounds.
+        let time_secs = Utc.from_utc_datetime(time).timestamp();
+        let mut lo = time_secs - 24 * 60 * 60;
+        let mut hi = time_secs + 24 * 60 * 60;
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            let local = self.to_local(Utc.timestamp_opt(mid, 0).unwrap());
+            if local >= *time {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        Utc.timestamp_opt(hi, 0).unwrap()
+    }
+}
+
+impl fmt::Display for Timezone {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Timezone::Utc => fmt.write_str("utc"),
+            Timezone::Local => fmt.write_str("local"),
+            #[cfg(feature = "timezones")]
+            Timezone::Named(tz) => fmt.write_str(tz.name()),
+        }
+    }
+}
+
+impl FromStr for Timezone {
+    type Err = anyhow::Error;
+
+    /// Parses `utc`, `local` (case insensitive), or the name of an IANA
+    /// timezone like `Europe/Berlin`.
+    fn from_str(s: &str) -> anyhow::Result<Timezone> {
+        if s.eq_ignore_ascii_case("utc") {
+            return Ok(Timezone::Utc);
+        }
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(Timezone::Local);
+        }
+
+        #[cfg(feature = "timezones")]
+        {
+            s.parse::<chrono_tz::Tz>()
+                .map(Timezone::Named)
+                .map_err(|e| anyhow::anyhow!("invalid timezone `{}`: {}", s, e))
+        }
+        #[cfg(not(feature = "timezones"))]
+        {
+            anyhow::bail!(
+                "invalid timezone `{}`: named timezones require the `timezones` feature",
+                s
+            )
+        }
+    }
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for Timezone {
+    fn deserialize<D>(d: D) -> Result<Timezone, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let s = String::deserialize(d)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Returns the current time, which is mocked in tests.
+#[cfg(not(test))]
+pub(crate) fn now() -> DateTime<Utc> {
+    Utc::now()
+}
+
+#[cfg(test)]
+pub(crate) fn now() -> DateTime<Utc> {
+    use mock_instant::{SystemTime, UNIX_EPOCH};
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time before Unix epoch");
+    Utc.timestamp_opt(now.as_secs() as i64, now.subsec_nanos())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn naive(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&naive(s))
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("UTC".parse::<Timezone>().unwrap(), Timezone::Utc);
+        assert_eq!("local".parse::<Timezone>().unwrap(), Timezone::Local);
+        assert!("Nowhere/Special".parse::<Timezone>().is_err());
+        #[cfg(feature = "timezones")]
+        assert_eq!(
+            "Europe/Berlin".parse::<Timezone>().unwrap(),
+            Timezone::Named(chrono_tz::Europe::Berlin)
+        );
+        #[cfg(not(feature = "timezones"))]
+        assert!("Europe/Berlin".parse::<Timezone>().is_err());
+    }
+
+    #[test]
+    fn utc_resolve() {
+        let time = naive("2024-03-31 02:30:00");
+        assert_eq!(Timezone::Utc.resolve(&time), utc("2024-03-31 02:30:00"));
+        assert_eq!(Timezone::Utc.to_local(utc("2024-03-31 02:30:00")), time);
+    }
+
+    #[test]
+    #[cfg(feature = "timezones")]
+    fn dst_resolve() {
+        let berlin = Timezone::Named(chrono_tz::Europe::Berlin);
+
+        // Clocks go forward from 02:00 to 03:00 CET.
+        assert_eq!(
+            berlin.resolve(&naive("2024-03-31 02:30:00")),
+            utc("2024-03-31 01:00:00")
+        );
+        // Clocks go back from 03:00 to 02:00 CEST.
+        assert_eq!(
+            berlin.resolve(&naive("2024-10-27 02:30:00")),
+            utc("2024-10-27 00:30:00")
+        );
+        assert_eq!(
+            berlin.resolve(&naive("2024-10-27 12:00:00")),
+            utc("2024-10-27 11:00:00")
+        );
+    }
+}
@@ -16,6 +16,18 @@ pub trait Policy: Sync + Send + 'static + fmt::Debug {
     /// This method is called after each log event. It is provided a reference
     /// to the current log file.
     fn process(&self, log: &mut LogFile) -> anyhow::Result<()>;
+    /// Rolls the current log file unconditionally.
+    ///
+    /// This method is called when a roll is requested manually, for example
+    /// through `Handle::roll`. It must roll the log the same way `process`
+    /// does when it decides to, by calling `LogFile::roll` and then
+    /// processing the old file.
+    ///
+    /// The default implementation returns an error.
+    fn roll(&self, log: &mut LogFile) -> anyhow::Result<()> {
+        let _ = log;
+        anyhow::bail!("the rolling policy does not support manual rolls")
+    }
     /// Return the config `Trigger.is_pre_process` value
     fn is_pre_process(&self) -> bool;
 }
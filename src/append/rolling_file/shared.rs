@@ -0,0 +1,111 @@
+//! Advisory locking of log files shared between processes.
+
+use std::{
+    cell::Cell,
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::append::permissions::Permissions;
+
+thread_local! {
+    static LOCKED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns whether the current thread holds the lock of a shared log file.
+///
+/// Rollers rotate on the calling thread while it does, since rotating in the
+/// background would escape the lock.
+// Only used by components behind optional features.
+#[allow(dead_code)]
+pub(crate) fn is_locked() -> bool {
+    LOCKED.with(Cell::get)
+}
+
+/// A lock file next to a shared log file.
+///
+/// The log file itself can't be locked, since it is renamed when it rolls.
+#[derive(Debug)]
+pub(crate) struct SharedLock {
+    file: File,
+}
+
+impl SharedLock {
+    /// Opens the lock file of the log file at `path`, `<path>.lock`.
+    ///
+    /// Only supported on Unix.
+    pub(crate) fn open(path: &Path, permissions: &Permissions) -> io::Result<SharedLock> {
+        if cfg!(not(unix)) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "shared log files are only supported on Unix",
+            ));
+        }
+
+        let mut name = path.as_os_str().to_owned();
+        name.push(".lock");
+        let mut options = OpenOptions::new();
+        options.write(true).create(true);
+        let file = permissions.open(&PathBuf::from(name), &options)?;
+        Ok(SharedLock { file })
+    }
+
+    /// Blocks until this process holds the lock exclusively.
+    pub(crate) fn lock(&self) -> io::Result<SharedGuard<'_>> {
+        flock(&self.file, true)?;
+        LOCKED.with(|l| l.set(true));
+        Ok(SharedGuard(self))
+    }
+}
+
+/// Releases the lock when dropped.
+pub(crate) struct SharedGuard<'a>(&'a SharedLock);
+
+impl Drop for SharedGuard<'_> {
+    fn drop(&mut self) {
+        LOCKED.with(|l| l.set(false));
+        let _ = flock(&self.0.file, false);
+    }
+}
+
+#[cfg(unix)]
+fn flock(file: &File, lock: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let operation = if lock { libc::LOCK_EX } else { libc::LOCK_UN };
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn flock(_: &File, _: bool) -> io::Result<()> {
+    unreachable!("shared log files are only supported on Unix")
+}
+
+/// Returns whether `path` still refers to the open `file`.
+///
+/// It doesn't once another process has rolled the log.
+#[cfg(unix)]
+pub(crate) fn is_current(file: &File, path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let open = file.metadata()?;
+    match std::fs::metadata(path) {
+        Ok(current) => Ok(open.dev() == current.dev() && open.ino() == current.ino()),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn is_current(_: &File, _: &Path) -> io::Result<bool> {
+    unreachable!("shared log files are only supported on Unix")
+}
@@ -0,0 +1,728 @@
+//! The routing appender.
+//!
+//! Requires the `routing_appender` feature.
+
+use derivative::Derivative;
+use log::Record;
+#[cfg(feature = "config_parsing")]
+use serde::de;
+#[cfg(feature = "config_parsing")]
+use serde_value::Value;
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "config_parsing")]
+use crate::config::{Deserialize, Deserializers};
+use crate::{
+    append::{self, Append},
+    encode::{pattern::PatternEncoder, writer::simple::SimpleWriter, Encode},
+};
+
+/// The placeholder replaced with the routing key in the template
+/// configuration.
+pub const KEY_PLACEHOLDER: &str = "$KEY";
+
+/// The routing appender's configuration.
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoutingAppenderConfig {
+    pattern: String,
+    max_appenders: Option<usize>,
+    #[serde(deserialize_with = "crate::config::de_duration", default)]
+    idle_timeout: Option<Duration>,
+    default_key: Option<String>,
+    appender: Template,
+}
+
+#[cfg(feature = "config_parsing")]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+struct Template {
+    kind: String,
+    config: Value,
+}
+
+#[cfg(feature = "config_parsing")]
+impl<'de> serde::Deserialize<'de> for Template {
+    fn deserialize<D>(d: D) -> Result<Template, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let mut map = BTreeMap::<Value, Value>::deserialize(d)?;
+
+        let kind = match map.remove(&Value::String("kind".to_owned())) {
+            Some(kind) => kind.deserialize_into().map_err(|e| e.to_error())?,
+            None => return Err(de::Error::missing_field("kind")),
+        };
+
+        Ok(Template {
+            kind,
+            config: Value::Map(map),
+        })
+    }
+}
+
+/// Replaces every occurrence of `$KEY` in the strings of a template
+/// configuration.
+#[cfg(feature = "config_parsing")]
+fn substitute_key(value: &Value, key: &str) -> Value {
+    match *value {
+        Value::String(ref s) => Value::String(s.replace(KEY_PLACEHOLDER, key)),
+        Value::Seq(ref seq) => Value::Seq(seq.iter().map(|v| substitute_key(v, key)).collect()),
+        Value::Map(ref map) => Value::Map(
+            map.iter()
+                .map(|(k, v)| (k.clone(), substitute_key(v, key)))
+                .collect(),
+        ),
+        Value::Option(Some(ref v)) => Value::Option(Some(Box::new(substitute_key(v, key)))),
+        Value::Newtype(ref v) => Value::Newtype(Box::new(substitute_key(v, key))),
+        ref other => other.clone(),
+    }
+}
+
+/// Makes a routing key safe to embed in a file path.
+///
+/// ASCII letters, digits, `-` and `_` are kept, and every other byte is
+/// replaced with `%` and its value in two hex digits. Since `%` is escaped as
+/// well, distinct keys never map to the same string.
+fn sanitize_key(key: &[u8]) -> String {
+    let mut sanitized = String::with_capacity(key.len());
+    for &b in key {
+        if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' {
+            sanitized.push(b as char);
+        } else {
+            sanitized.push_str(&format!("%{:02X}", b));
+        }
+    }
+    sanitized
+}
+
+type Factory = dyn Fn(&str) -> anyhow::Result<Box<dyn Append>> + Send + Sync;
+
+#[derive(Debug)]
+struct Child {
+    appender: Arc<dyn Append>,
+    last_used: Instant,
+}
+
+#[derive(Debug)]
+struct Children {
+    children: HashMap<String, Child>,
+    last_sweep: Instant,
+}
+
+/// An appender which routes each record to a child appender selected by a
+/// key.
+///
+/// The key is computed for each record by rendering a pattern, for example
+/// `{X(tenant)}` to route on the `tenant` entry of the MDC. Child appenders
+/// are created lazily the first time a key is seen. At most `max_appenders`
+/// children are kept alive; when a new key arrives and the limit is reached,
+/// the least recently used child is closed. Children which have not received
+/// a record within the idle timeout are closed as well, when the appender
+/// next receives a record or is flushed; a router which receives no records
+/// at all keeps its idle children open until then.
+///
+/// Rolling the appender rolls each of its current children which supports
+/// rolling, and reopening it reopens all of them.
+///
+/// Keys are sanitized before they are handed to the factory: every byte
+/// other than ASCII letters, digits, `-` and `_` is replaced with `%` and its
+/// value in hex, so that a key can safely be used as part of a file path and
+/// distinct keys get distinct paths. Records whose key is empty are routed
+/// with the default key instead.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct RoutingAppender {
+    router: PatternEncoder,
+    #[derivative(Debug = "ignore")]
+    factory: Box<Factory>,
+    max_appenders: usize,
+    idle_timeout: Option<Duration>,
+    default_key: String,
+    children: Mutex<Children>,
+}
+
+impl RoutingAppender {
+    /// Creates a new `RoutingAppender` builder.
+    pub fn builder() -> RoutingAppenderBuilder {
+        RoutingAppenderBuilder {
+            max_appenders: 100,
+            idle_timeout: None,
+            default_key: "default".to_owned(),
+        }
+    }
+
+    /// Returns the number of child appenders currently alive.
+    pub fn active_appenders(&self) -> usize {
+        self.children.lock().unwrap().children.len()
+    }
+
+    /// Returns the current children with their keys, so they can be used
+    /// without holding the lock.
+    fn snapshot(&self) -> Vec<(String, Arc<dyn Append>)> {
+        self.children
+            .lock()
+            .unwrap()
+            .children
+            .iter()
+            .map(|(key, child)| (key.clone(), child.appender.clone()))
+            .collect()
+    }
+
+    fn route(&self, record: &Record) -> anyhow::Result<String> {
+        let mut buf = vec![];
+        self.router.encode(&mut SimpleWriter(&mut buf), record)?;
+        if buf.is_empty() {
+            Ok(self.default_key.clone())
+        } else {
+            Ok(sanitize_key(&buf))
+        }
+    }
+
+    /// Removes the children other than `key`'s which have been idle for the
+    /// idle timeout, at most once per half of the timeout.
+    fn sweep(
+        &self,
+        children: &mut Children,
+        now: Instant,
+        key: Option<&str>,
+        evicted: &mut Vec<Child>,
+    ) {
+        let idle_timeout = match self.idle_timeout {
+            Some(idle_timeout) => idle_timeout,
+            None => return,
+        };
+        if now.duration_since(children.last_sweep) < idle_timeout / 2 {
+            return;
+        }
+
+        children.last_sweep = now;
+        let idle = children
+            .children
+            .iter()
+            .filter(|(k, c)| Some(&***k) != key && now.duration_since(c.last_used) >= idle_timeout)
+            .map(|(k, _)| k.clone())
+            .collect::<Vec<_>>();
+        for k in idle {
+            evicted.extend(children.children.remove(&k));
+        }
+    }
+
+    fn child(&self, key: &str) -> anyhow::Result<Arc<dyn Append>> {
+        let mut evicted = vec![];
+
+        let existing = {
+            let mut children = self.children.lock().unwrap();
+            let now = Instant::now();
+            self.sweep(&mut children, now, Some(key), &mut evicted);
+            children.children.get_mut(key).map(|child| {
+                child.last_used = now;
+                child.appender.clone()
+            })
+        };
+
+        let appender = match existing {
+            Some(appender) => appender,
+            None => {
+                // The factory may open files, so records for other keys
+                // aren't held up while it runs.
+                let created: Arc<dyn Append> = Arc::from((self.factory)(key)?);
+
+                let mut children = self.children.lock().unwrap();
+                let now = Instant::now();
+                match children.children.get_mut(key) {
+                    // Another thread created the child first; the one created
+                    // here is dropped.
+                    Some(child) => {
+                        child.last_used = now;
+                        child.appender.clone()
+                    }
+                    None => {
+                        while !children.children.is_empty()
+                            && children.children.len() >= self.max_appenders
+                        {
+                            let lru = children
+                                .children
+                                .iter()
+                                .min_by_key(|(_, c)| c.last_used)
+                                .map(|(k, _)| k.clone())
+                                .unwrap();
+                            evicted.extend(children.children.remove(&lru));
+                        }
+
+                        children.children.insert(
+                            key.to_owned(),
+                            Child {
+                                appender: created.clone(),
+                                last_used: now,
+                            },
+                        );
+                        created
+                    }
+                }
+            }
+        };
+
+        close(evicted);
+        Ok(appender)
+    }
+}
+
+/// Closes evicted children outside of the lock. Their files are closed once
+/// the last in-flight `append` releases them.
+fn close(evicted: Vec<Child>) {
+    for child in evicted {
+        child.appender.flush();
+    }
+}
+
+impl Append for RoutingAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let key = self.route(record)?;
+        self.child(&key)?.append(record)
+    }
+
+    fn flush(&self) {
+        let mut evicted = vec![];
+        {
+            let mut children = self.children.lock().unwrap();
+            self.sweep(&mut children, Instant::now(), None, &mut evicted);
+        }
+        close(evicted);
+
+        for (_, child) in self.snapshot() {
+            child.flush();
+        }
+    }
+
+    fn roll(&self) -> anyhow::Result<()> {
+        let children = self.snapshot();
+        append::roll_all(children.iter().map(|(key, child)| (&**key, &**child)))
+    }
+
+    fn reopen(&self) -> anyhow::Result<()> {
+        let children = self.snapshot();
+        append::reopen_all(children.iter().map(|(key, child)| (&**key, &**child)))
+    }
+}
+
+/// A builder for `RoutingAppender`s.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RoutingAppenderBuilder {
+    max_appenders: usize,
+    idle_timeout: Option<Duration>,
+    default_key: String,
+}
+
+impl RoutingAppenderBuilder {
+    /// Sets the maximum number of child appenders kept alive at once.
+    ///
+    /// Defaults to 100.
+    pub fn max_appenders(mut self, max_appenders: usize) -> RoutingAppenderBuilder {
+        self.max_appenders = max_appenders;
+        self
+    }
+
+    /// Sets how long a child appender may go without records before it is
+    /// closed.
+    ///
+    /// By default, children are only closed to respect `max_appenders`.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> RoutingAppenderBuilder {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Sets the key of records whose pattern renders to an empty string.
+    ///
+    /// It is sanitized like any other key, so records with an empty key share
+    /// a child with those whose key is `default_key`. Defaults to `default`.
+    pub fn default_key(mut self, default_key: &str) -> RoutingAppenderBuilder {
+        self.default_key = default_key.to_owned();
+        self
+    }
+
+    /// Consumes the `RoutingAppenderBuilder`, producing a `RoutingAppender`.
+    ///
+    /// `pattern` is rendered with the `PatternEncoder` syntax for each record
+    /// to compute its key. `factory` is called with the sanitized key to
+    /// create the child appender the first time a key is seen.
+    pub fn build<F>(self, pattern: &str, factory: F) -> RoutingAppender
+    where
+        F: Fn(&str) -> anyhow::Result<Box<dyn Append>> + Send + Sync + 'static,
+    {
+        RoutingAppender {
+            router: PatternEncoder::new(pattern),
+            factory: Box::new(factory),
+            max_appenders: self.max_appenders,
+            idle_timeout: self.idle_timeout,
+            default_key: sanitize_key(self.default_key.as_bytes()),
+            children: Mutex::new(Children {
+                children: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+}
+
+/// A deserializer for the `RoutingAppender`.
+///
+/// # Configuration
+///
+/// ```yaml
+/// kind: routing
+///
+/// # The pattern used to compute the routing key of each record, using the
+/// # `PatternEncoder` syntax. Bytes other than ASCII letters, digits, `-` and
+/// # `_` are replaced with `%` and their value in hex in the key, e.g. `a.b`
+/// # becomes `a%2Eb`. Required.
+/// pattern: "{X(tenant)(unknown)}"
+///
+/// # The key of records whose pattern renders to an empty string. Defaults to
+/// # `default`.
+/// default_key: unknown
+///
+/// # The maximum number of child appenders kept alive at once. When a new key
+/// # arrives and the limit is reached, the least recently used child is
+/// # closed. Defaults to 100.
+/// max_appenders: 100
+///
+/// # How long a child appender may go without records before it is closed.
+/// # Defaults to never.
+/// idle_timeout: 10 minutes
+///
+/// # The template used to create child appenders. Every occurrence of `$KEY`
+/// # in its string values is replaced with the routing key. Required.
+/// appender:
+///   kind: rolling_file
+///   path: log/$KEY/app.log
+///   policy:
+///     trigger:
+///       kind: size
+///       limit: 10 mb
+///     roller:
+///       kind: fixed_window
+///       pattern: log/$KEY/app.{}.log
+///       count: 5
+/// ```
+#[cfg(feature = "config_parsing")]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct RoutingAppenderDeserializer;
+
+#[cfg(feature = "config_parsing")]
+impl Deserialize for RoutingAppenderDeserializer {
+    type Trait = dyn Append;
+
+    type Config = RoutingAppenderConfig;
+
+    fn deserialize(
+        &self,
+        config: RoutingAppenderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Append>> {
+        let mut builder = RoutingAppender::builder();
+        if let Some(max_appenders) = config.max_appenders {
+            builder = builder.max_appenders(max_appenders);
+        }
+        if let Some(idle_timeout) = config.idle_timeout {
+            builder = builder.idle_timeout(idle_timeout);
+        }
+        if let Some(default_key) = &config.default_key {
+            builder = builder.default_key(default_key);
+        }
+
+        let deserializers = deserializers.clone();
+        let template = config.appender;
+        Ok(Box::new(builder.build(&config.pattern, move |key| {
+            deserializers.deserialize(&template.kind, substitute_key(&template.config, key))
+        })))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc, Mutex,
+        },
+        thread,
+    };
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct KeyedAppender {
+        key: String,
+        seen: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Append for KeyedAppender {
+        fn append(&self, record: &Record) -> anyhow::Result<()> {
+            self.seen
+                .lock()
+                .unwrap()
+                .push(format!("{}: {}", self.key, record.args()));
+            Ok(())
+        }
+
+        fn flush(&self) {}
+
+        fn roll(&self) -> anyhow::Result<()> {
+            self.seen
+                .lock()
+                .unwrap()
+                .push(format!("{}: rolled", self.key));
+            Ok(())
+        }
+
+        fn reopen(&self) -> anyhow::Result<()> {
+            self.seen
+                .lock()
+                .unwrap()
+                .push(format!("{}: reopened", self.key));
+            Ok(())
+        }
+    }
+
+    fn routing(builder: RoutingAppenderBuilder) -> (RoutingAppender, Arc<Mutex<Vec<String>>>) {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+        let appender = builder.build("{X(tenant)(none)}", move |key| {
+            Ok(Box::new(KeyedAppender {
+                key: key.to_owned(),
+                seen: seen2.clone(),
+            }))
+        });
+        (appender, seen)
+    }
+
+    fn log_as(appender: &RoutingAppender, tenant: Option<&str>, message: &str) {
+        match tenant {
+            Some(tenant) => log_mdc::insert("tenant", tenant),
+            None => log_mdc::remove("tenant"),
+        };
+        appender
+            .append(&Record::builder().args(format_args!("{}", message)).build())
+            .unwrap();
+        log_mdc::remove("tenant");
+    }
+
+    #[test]
+    fn routes_by_key() {
+        let (appender, seen) = routing(RoutingAppender::builder());
+
+        log_as(&appender, Some("acme"), "one");
+        log_as(&appender, Some("globex"), "two");
+        log_as(&appender, Some("acme"), "three");
+        log_as(&appender, None, "four");
+        log_as(&appender, Some("../etc"), "five");
+
+        assert_eq!(appender.active_appenders(), 4);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                "acme: one",
+                "globex: two",
+                "acme: three",
+                "none: four",
+                "%2E%2E%2Fetc: five"
+            ]
+        );
+    }
+
+    #[test]
+    fn distinct_keys() {
+        let (appender, seen) = routing(RoutingAppender::builder());
+
+        for key in ["a.b", "a/b", "a b", "a_b", "a%2Eb", "\u{e9}"] {
+            log_as(&appender, Some(key), key);
+        }
+
+        assert_eq!(appender.active_appenders(), 6);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                "a%2Eb: a.b",
+                "a%2Fb: a/b",
+                "a%20b: a b",
+                "a_b: a_b",
+                "a%252Eb: a%2Eb",
+                "%C3%A9: \u{e9}",
+            ]
+        );
+    }
+
+    #[test]
+    fn default_key() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+        let appender =
+            RoutingAppender::builder()
+                .default_key("no tenant")
+                .build("{X(tenant)}", move |key| {
+                    Ok(Box::new(KeyedAppender {
+                        key: key.to_owned(),
+                        seen: seen2.clone(),
+                    }))
+                });
+
+        log_as(&appender, None, "one");
+        log_as(&appender, Some(""), "two");
+        log_as(&appender, Some("acme"), "three");
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["no%20tenant: one", "no%20tenant: two", "acme: three"]
+        );
+    }
+
+    #[test]
+    fn max_appenders() {
+        let (appender, _) = routing(RoutingAppender::builder().max_appenders(2));
+
+        log_as(&appender, Some("a"), "");
+        log_as(&appender, Some("b"), "");
+        log_as(&appender, Some("a"), "");
+        log_as(&appender, Some("c"), "");
+
+        assert_eq!(appender.active_appenders(), 2);
+        let children = appender.children.lock().unwrap();
+        assert!(children.children.contains_key("a"));
+        assert!(!children.children.contains_key("b"));
+        assert!(children.children.contains_key("c"));
+    }
+
+    #[test]
+    fn idle_timeout() {
+        let (appender, _) =
+            routing(RoutingAppender::builder().idle_timeout(Duration::from_secs(0)));
+
+        log_as(&appender, Some("a"), "");
+        log_as(&appender, Some("b"), "");
+
+        assert_eq!(appender.active_appenders(), 1);
+        assert!(appender.children.lock().unwrap().children.contains_key("b"));
+
+        // A router which receives no records closes idle children on flush.
+        appender.flush();
+        assert_eq!(appender.active_appenders(), 0);
+    }
+
+    #[test]
+    fn factory_runs_outside_lock() {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let started_tx = Mutex::new(started_tx);
+        let blocked = AtomicBool::new(false);
+        let release_rx = Mutex::new(release_rx);
+        let created = Arc::new(Mutex::new(vec![]));
+        let created2 = created.clone();
+        let appender = RoutingAppender::builder().build("{X(tenant)(none)}", move |key| {
+            if key == "slow" && !blocked.swap(true, Ordering::SeqCst) {
+                started_tx.lock().unwrap().send(()).unwrap();
+                release_rx.lock().unwrap().recv().unwrap();
+            }
+            created2.lock().unwrap().push(key.to_owned());
+            Ok(Box::new(KeyedAppender {
+                key: key.to_owned(),
+                seen: Arc::default(),
+            }))
+        });
+
+        let appender = &appender;
+        thread::scope(|s| {
+            let slow = s.spawn(|| log_as(appender, Some("slow"), ""));
+            let racer = s.spawn(move || {
+                started_rx.recv().unwrap();
+                // Records for other keys aren't blocked by the slow factory.
+                log_as(appender, Some("fast"), "");
+                // Neither is another record for the same key, which creates
+                // a second child of which only one is kept.
+                log_as(appender, Some("slow"), "");
+            });
+            racer.join().unwrap();
+            release_tx.send(()).unwrap();
+            slow.join().unwrap();
+        });
+
+        assert_eq!(*created.lock().unwrap(), ["fast", "slow", "slow"]);
+        assert_eq!(appender.active_appenders(), 2);
+    }
+
+    #[test]
+    fn reopen() {
+        let (appender, seen) = routing(RoutingAppender::builder());
+
+        log_as(&appender, Some("a"), "one");
+        log_as(&appender, Some("b"), "two");
+        appender.reopen().unwrap();
+
+        let mut seen = seen.lock().unwrap().split_off(2);
+        seen.sort();
+        assert_eq!(seen, ["a: reopened", "b: reopened"]);
+    }
+
+    #[test]
+    fn roll() {
+        let (appender, seen) = routing(RoutingAppender::builder());
+
+        log_as(&appender, Some("a"), "one");
+        log_as(&appender, Some("b"), "two");
+        appender.roll().unwrap();
+
+        let mut seen = seen.lock().unwrap().split_off(2);
+        seen.sort();
+        assert_eq!(seen, ["a: rolled", "b: rolled"]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "file_appender"))]
+    fn deserialize() {
+        use crate::config::{Deserializers, RawConfig};
+        use std::fs;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = format!(
+            "
+appenders:
+    tenants:
+        kind: routing
+        pattern: '{{X(tenant)}}'
+        max_appenders: 10
+        idle_timeout: 5 minutes
+        default_key: unknown
+        appender:
+            kind: file
+            path: {0}/$KEY/app.log
+            encoder:
+                pattern: '{{m}}'
+",
+            dir.path().display()
+        );
+        let config = ::serde_yaml::from_str::<RawConfig>(&config).unwrap();
+        let (appenders, errors) = config.appenders_lossy(&Deserializers::new());
+        assert!(errors.is_empty());
+
+        log_mdc::insert("tenant", "acme");
+        appenders[0]
+            .appender()
+            .append(&Record::builder().args(format_args!("hello")).build())
+            .unwrap();
+        log_mdc::remove("tenant");
+        appenders[0]
+            .appender()
+            .append(&Record::builder().args(format_args!("anonymous")).build())
+            .unwrap();
+
+        let contents = fs::read_to_string(dir.path().join("acme").join("app.log")).unwrap();
+        assert_eq!(contents, "hello");
+        let contents = fs::read_to_string(dir.path().join("unknown").join("app.log")).unwrap();
+        assert_eq!(contents, "anonymous");
+    }
+}
@@ -1,14 +1,15 @@
 use std::{
     fs,
     path::{Path, PathBuf},
+    sync::Arc,
     thread,
     time::{Duration, SystemTime},
 };
 
 use thiserror::Error;
 
-use super::{init_config, Config, Deserializers, Handle, RawConfig};
-use crate::handle_error;
+use super::{Config, Deserializers, Handle, RawConfig};
+use crate::error::{self, ErrorEvent, ErrorHandler, ErrorKind, StderrErrorHandler};
 
 /// Initializes the global logger as a log4rs logger configured via a file.
 ///
@@ -23,7 +24,37 @@ pub fn init_file<P>(path: P, deserializers: Deserializers) -> anyhow::Result<()>
 where
     P: AsRef<Path>,
 {
-    let path = path.as_ref().to_path_buf();
+    init_file_inner(
+        path.as_ref(),
+        deserializers,
+        Arc::from(error::default_error_handler()),
+    )
+}
+
+/// Initializes the global logger as a log4rs logger configured via a file,
+/// with the provided `ErrorHandler`.
+///
+/// This behaves like `init_file`, except that nonfatal errors encountered
+/// when processing or reloading the configuration, as well as errors from
+/// appenders, are passed to `err_handler`.
+pub fn init_file_with_error_handler<P, H>(
+    path: P,
+    deserializers: Deserializers,
+    err_handler: H,
+) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+    H: ErrorHandler,
+{
+    init_file_inner(path.as_ref(), deserializers, Arc::new(err_handler))
+}
+
+fn init_file_inner(
+    path: &Path,
+    deserializers: Deserializers,
+    err_handler: Arc<dyn ErrorHandler>,
+) -> anyhow::Result<()> {
+    let path = path.to_path_buf();
     let format = Format::from_path(&path)?;
     let source = read_config(&path)?;
     // An Err here could come because mtime isn't available, so don't bail
@@ -31,25 +62,25 @@ where
     let config = format.parse(&source)?;
 
     let refresh_rate = config.refresh_rate();
-    let config = deserialize(&config, &deserializers);
-
-    match init_config(config) {
-        Ok(handle) => {
-            if let Some(refresh_rate) = refresh_rate {
-                ConfigReloader::start(
-                    path,
-                    format,
-                    refresh_rate,
-                    source,
-                    modified,
-                    deserializers,
-                    handle,
-                );
-            }
-            Ok(())
-        }
-        Err(e) => Err(e.into()),
+    let config = deserialize(&config, &deserializers, &*err_handler);
+
+    let logger = crate::Logger::with_error_handler(config, err_handler);
+    log::set_max_level(logger.max_log_level());
+    let handle = logger.handle();
+    log::set_boxed_logger(Box::new(logger))?;
+
+    if let Some(refresh_rate) = refresh_rate {
+        ConfigReloader::start(
+            path,
+            format,
+            refresh_rate,
+            source,
+            modified,
+            deserializers,
+            handle,
+        );
     }
+    Ok(())
 }
 
 /// Loads a log4rs logger configuration from a file.
@@ -65,7 +96,7 @@ where
     let source = read_config(path)?;
     let config = format.parse(&source)?;
 
-    Ok(deserialize(&config, &deserializers))
+    Ok(deserialize(&config, &deserializers, &StderrErrorHandler))
 }
 
 /// The various types of formatting errors that can be generated.
@@ -143,16 +174,20 @@ fn read_config(path: &Path) -> anyhow::Result<String> {
     Ok(s)
 }
 
-fn deserialize(config: &RawConfig, deserializers: &Deserializers) -> Config {
+fn deserialize(
+    config: &RawConfig,
+    deserializers: &Deserializers,
+    err_handler: &dyn ErrorHandler,
+) -> Config {
     let (appenders, mut errors) = config.appenders_lossy(deserializers);
-    errors.handle();
+    errors.handle_with(err_handler);
 
     let (config, mut errors) = Config::builder()
         .appenders(appenders)
         .loggers(config.loggers())
         .build_lossy(config.root());
 
-    errors.handle();
+    errors.handle_with(err_handler);
 
     config
 }
@@ -198,7 +233,10 @@ impl ConfigReloader {
             match self.run_once(rate) {
                 Ok(Some(r)) => rate = r,
                 Ok(None) => break,
-                Err(e) => handle_error(&e),
+                Err(e) => self
+                    .handle
+                    .error_handler()
+                    .handle(&ErrorEvent::new(ErrorKind::ConfigReload, &e)),
             }
         }
     }
@@ -223,7 +261,7 @@ impl ConfigReloader {
 
         let config = self.format.parse(&self.source)?;
         let rate = config.refresh_rate();
-        let config = deserialize(&config, &self.deserializers);
+        let config = deserialize(&config, &self.deserializers, &*self.handle.error_handler());
 
         self.handle.set_config(config);
 
@@ -4,6 +4,8 @@
 use log::SetLoggerError;
 use thiserror::Error;
 
+use crate::error::{ErrorEvent, ErrorHandler};
+#[cfg(feature = "config_parsing")]
 use crate::Handle;
 
 pub mod runtime;
@@ -16,10 +18,14 @@ mod raw;
 pub use runtime::{Appender, Config, Logger, Root};
 
 #[cfg(feature = "config_parsing")]
-pub use self::file::{init_file, load_config_file, FormatError};
+pub use self::file::{init_file, init_file_with_error_handler, load_config_file, FormatError};
 #[cfg(feature = "config_parsing")]
 pub use self::raw::{Deserializable, Deserialize, Deserializers, RawConfig};
 
+#[cfg(feature = "config_parsing")]
+#[allow(unused_imports)]
+pub(crate) use self::raw::{de_duration, de_opt_size, de_size};
+
 /// Initializes the global logger as a log4rs logger with the provided config.
 ///
 /// A `Handle` object is returned which can be used to adjust the logging
@@ -35,15 +41,31 @@ pub fn init_config(config: runtime::Config) -> Result<crate::Handle, SetLoggerEr
 ///
 /// A `Handle` object is returned which can be used to adjust the logging
 /// configuration.
+#[deprecated(
+    note = "use `init_config_with_error_handler`, whose handler also receives the failing appender and record"
+)]
 pub fn init_config_with_err_handler(
     config: runtime::Config,
     err_handler: Box<dyn Send + Sync + Fn(&anyhow::Error)>,
 ) -> Result<crate::Handle, SetLoggerError> {
-    let logger = crate::Logger::new_with_err_handler(config, err_handler);
+    init_config_with_error_handler(config, move |e: &ErrorEvent| err_handler(e.error()))
+}
+
+/// Initializes the global logger as a log4rs logger with the provided config
+/// and `ErrorHandler`.
+///
+/// A `Handle` object is returned which can be used to adjust the logging
+/// configuration.
+pub fn init_config_with_error_handler<H>(
+    config: runtime::Config,
+    err_handler: H,
+) -> Result<crate::Handle, SetLoggerError>
+where
+    H: ErrorHandler,
+{
+    let logger = crate::Logger::new_with_error_handler(config, err_handler);
     log::set_max_level(logger.max_log_level());
-    let handle = Handle {
-        shared: logger.0.clone(),
-    };
+    let handle = logger.handle();
     log::set_boxed_logger(Box::new(logger)).map(|()| handle)
 }
 
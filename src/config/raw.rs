@@ -99,7 +99,11 @@ use serde_value::Value;
 use thiserror::Error;
 use typemap_ors::{Key, ShareCloneMap};
 
-use crate::{append::AppenderConfig, config};
+use crate::{
+    append::AppenderConfig,
+    config,
+    error::{ErrorEvent, ErrorHandler, ErrorKind},
+};
 
 #[allow(unused_imports)]
 use crate::append;
@@ -189,6 +193,12 @@ impl Default for Deserializers {
             append::rolling_file::RollingFileAppenderDeserializer,
         );
 
+        #[cfg(feature = "failover_appender")]
+        d.insert("failover", append::failover::FailoverAppenderDeserializer);
+
+        #[cfg(feature = "routing_appender")]
+        d.insert("routing", append::routing::RoutingAppenderDeserializer);
+
         #[cfg(feature = "compound_policy")]
         d.insert(
             "compound",
@@ -207,6 +217,12 @@ impl Default for Deserializers {
             append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRollerDeserializer,
         );
 
+        #[cfg(feature = "time_based_roller")]
+        d.insert(
+            "time_based",
+            append::rolling_file::policy::compound::roll::time_based::TimeBasedRollerDeserializer,
+        );
+
         #[cfg(feature = "size_trigger")]
         d.insert(
             "size",
@@ -225,6 +241,24 @@ impl Default for Deserializers {
             append::rolling_file::policy::compound::trigger::onstartup::OnStartUpTriggerDeserializer,
         );
 
+        #[cfg(feature = "cron_trigger")]
+        d.insert(
+            "cron",
+            append::rolling_file::policy::compound::trigger::cron::CronTriggerDeserializer,
+        );
+
+        #[cfg(feature = "composite_trigger")]
+        d.insert(
+            "any",
+            append::rolling_file::policy::compound::trigger::composite::AnyTriggerDeserializer,
+        );
+
+        #[cfg(feature = "composite_trigger")]
+        d.insert(
+            "all",
+            append::rolling_file::policy::compound::trigger::composite::AllTriggerDeserializer,
+        );
+
         #[cfg(feature = "json_encoder")]
         d.insert("json", encode::json::JsonEncoderDeserializer);
 
@@ -250,6 +284,10 @@ impl Deserializers {
     ///         * Requires the `file_appender` feature.
     ///     * "rolling_file" -> `RollingFileAppenderDeserializer`
     ///         * Requires the `rolling_file_appender` feature.
+    ///     * "failover" -> `FailoverAppenderDeserializer`
+    ///         * Requires the `failover_appender` feature.
+    ///     * "routing" -> `RoutingAppenderDeserializer`
+    ///         * Requires the `routing_appender` feature.
     /// * Encoders
     ///     * "pattern" -> `PatternEncoderDeserializer`
     ///         * Requires the `pattern_encoder` feature.
@@ -266,6 +304,8 @@ impl Deserializers {
     ///         * Requires the `delete_roller` feature.
     ///     * "fixed_window" -> `FixedWindowRollerDeserializer`
     ///         * Requires the `fixed_window_roller` feature.
+    ///     * "time_based" -> `TimeBasedRollerDeserializer`
+    ///         * Requires the `time_based_roller` feature.
     /// * Triggers
     ///     * "size" -> `SizeTriggerDeserializer`
     ///         * Requires the `size_trigger` feature.
@@ -273,6 +313,12 @@ impl Deserializers {
     ///         * Requires the `time_trigger` feature.
     ///     * "onstartup" -> `OnStartUpTriggerDeserializer`
     ///         * Requires the `onstartup_trigger` feature.
+    ///     * "cron" -> `CronTriggerDeserializer`
+    ///         * Requires the `cron_trigger` feature.
+    ///     * "any" -> `AnyTriggerDeserializer`
+    ///         * Requires the `composite_trigger` feature.
+    ///     * "all" -> `AllTriggerDeserializer`
+    ///         * Requires the `composite_trigger` feature.
     pub fn new() -> Deserializers {
         Deserializers::default()
     }
@@ -347,6 +393,11 @@ impl AppenderErrors {
             crate::handle_error(&error.into());
         }
     }
+    pub(crate) fn handle_with(&mut self, err_handler: &dyn ErrorHandler) {
+        for error in self.0.drain(..) {
+            err_handler.handle(&ErrorEvent::new(ErrorKind::Config, &error.into()));
+        }
+    }
 }
 
 impl RawConfig {
@@ -403,7 +454,7 @@ impl RawConfig {
     }
 }
 
-fn de_duration<'de, D>(d: D) -> Result<Option<Duration>, D::Error>
+pub(crate) fn de_duration<'de, D>(d: D) -> Result<Option<Duration>, D::Error>
 where
     D: de::Deserializer<'de>,
 {
@@ -438,6 +489,94 @@ where
     Option::<S>::deserialize(d).map(|r| r.map(|s| s.0))
 }
 
+// Only used by components behind optional features.
+#[allow(dead_code)]
+pub(crate) fn de_size<'de, D>(d: D) -> Result<u64, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    struct V;
+
+    impl<'de2> de::Visitor<'de2> for V {
+        type Value = u64;
+
+        fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            fmt.write_str("a size")
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<u64, E>
+        where
+            E: de::Error,
+        {
+            Ok(v)
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<u64, E>
+        where
+            E: de::Error,
+        {
+            if v < 0 {
+                return Err(E::invalid_value(
+                    de::Unexpected::Signed(v),
+                    &"a non-negative number",
+                ));
+            }
+
+            Ok(v as u64)
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<u64, E>
+        where
+            E: de::Error,
+        {
+            let (number, unit) = match v.find(|c: char| !c.is_ascii_digit()) {
+                Some(n) => (v[..n].trim(), Some(v[n..].trim())),
+                None => (v.trim(), None),
+            };
+
+            let number = match number.parse::<u64>() {
+                Ok(n) => n,
+                Err(_) => return Err(E::invalid_value(de::Unexpected::Str(number), &"a number")),
+            };
+
+            let unit = match unit {
+                Some(u) => u,
+                None => return Ok(number),
+            };
+
+            let number = if unit.eq_ignore_ascii_case("b") {
+                Some(number)
+            } else if unit.eq_ignore_ascii_case("kb") || unit.eq_ignore_ascii_case("kib") {
+                number.checked_mul(1024)
+            } else if unit.eq_ignore_ascii_case("mb") || unit.eq_ignore_ascii_case("mib") {
+                number.checked_mul(1024 * 1024)
+            } else if unit.eq_ignore_ascii_case("gb") || unit.eq_ignore_ascii_case("gib") {
+                number.checked_mul(1024 * 1024 * 1024)
+            } else if unit.eq_ignore_ascii_case("tb") || unit.eq_ignore_ascii_case("tib") {
+                number.checked_mul(1024 * 1024 * 1024 * 1024)
+            } else {
+                return Err(E::invalid_value(de::Unexpected::Str(unit), &"a valid unit"));
+            };
+
+            match number {
+                Some(n) => Ok(n),
+                None => Err(E::invalid_value(de::Unexpected::Str(v), &"a byte size")),
+            }
+        }
+    }
+
+    d.deserialize_any(V)
+}
+
+// Only used by components behind optional features.
+#[allow(dead_code)]
+pub(crate) fn de_opt_size<'de, D>(d: D) -> Result<Option<u64>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    de_size(d).map(Some)
+}
+
 #[derive(Clone, Debug, Derivative, serde::Deserialize)]
 #[derivative(Default)]
 #[serde(deny_unknown_fields)]
@@ -521,7 +660,7 @@ loggers:
     const LINE_ENDING: &'static str = "\r\n";
     #[cfg(not(windows))]
     #[allow(dead_code)]
-    const LINE_ENDING: &'static str = "\n";
+    const LINE_ENDING: &str = "\n";
 
     #[test]
     #[cfg(feature = "yaml_format")]
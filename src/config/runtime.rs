@@ -439,6 +439,15 @@ impl ConfigErrors {
             crate::handle_error(&e.into());
         }
     }
+
+    #[cfg(feature = "config_parsing")]
+    pub(crate) fn handle_with(&mut self, err_handler: &dyn crate::error::ErrorHandler) {
+        use crate::error::{ErrorEvent, ErrorKind};
+
+        for e in self.0.drain(..) {
+            err_handler.handle(&ErrorEvent::new(ErrorKind::Config, &e.into()));
+        }
+    }
 }
 
 /// An error validating a log4rs `Config`.
@@ -2,7 +2,7 @@
 
 use derivative::Derivative;
 use log::Record;
-use std::{fmt, io};
+use std::{fmt, io, str::FromStr};
 
 #[cfg(feature = "config_parsing")]
 use serde::de;
@@ -77,6 +77,9 @@ impl<'de> de::Deserialize<'de> for EncoderConfig {
 }
 
 /// A text or background color.
+///
+/// Writers which can't display 256 colors or RGB colors use the closest color
+/// they support.
 #[allow(missing_docs)]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Color {
@@ -88,6 +91,72 @@ pub enum Color {
     Magenta,
     Cyan,
     White,
+    /// A color from the 256 color palette of xterm.
+    Ansi256(u8),
+    /// A 24-bit color, as red, green and blue components.
+    Rgb(u8, u8, u8),
+}
+
+impl FromStr for Color {
+    type Err = anyhow::Error;
+
+    /// Parses the name of one of the eight basic colors (case insensitive) or
+    /// of its bright variant like `bright_red`, a number from the 256 color
+    /// palette like `208`, or an RGB color like `#ff8800`.
+    fn from_str(s: &str) -> anyhow::Result<Color> {
+        let lower = s.to_ascii_lowercase();
+        if let Some(name) = lower.strip_prefix("bright_") {
+            // The bright variants follow the basic colors in the palette.
+            return match name.parse()? {
+                Color::Ansi256(_) | Color::Rgb(..) => {
+                    anyhow::bail!("invalid color `{}`", s)
+                }
+                basic => Ok(Color::Ansi256(8 + basic_index(basic))),
+            };
+        }
+
+        let color = match lower.as_str() {
+            "black" => Color::Black,
+            "red" => Color::Red,
+            "green" => Color::Green,
+            "yellow" => Color::Yellow,
+            "blue" => Color::Blue,
+            "magenta" => Color::Magenta,
+            "cyan" => Color::Cyan,
+            "white" => Color::White,
+            hex if hex.starts_with('#') => {
+                let component = |i: usize| {
+                    hex.get(i..i + 2)
+                        .and_then(|c| u8::from_str_radix(c, 16).ok())
+                        .ok_or_else(|| anyhow::anyhow!("invalid RGB color `{}`", s))
+                };
+                if hex.len() != 7 {
+                    anyhow::bail!("invalid RGB color `{}`: expected `#rrggbb`", s);
+                }
+                Color::Rgb(component(1)?, component(3)?, component(5)?)
+            }
+            n => n
+                .parse()
+                .map(Color::Ansi256)
+                .map_err(|_| anyhow::anyhow!("invalid color `{}`", s))?,
+        };
+        Ok(color)
+    }
+}
+
+/// Returns the index of a basic color in the 256 color palette.
+pub(crate) fn basic_index(color: Color) -> u8 {
+    match color {
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::White => 7,
+        Color::Ansi256(_) | Color::Rgb(..) => unreachable!("not a basic color"),
+    }
 }
 
 /// The style applied to text output.
@@ -104,6 +173,16 @@ pub struct Style {
     pub background: Option<Color>,
     /// True if the text should have increased intensity.
     pub intense: Option<bool>,
+    /// True if the text should be bold.
+    pub bold: Option<bool>,
+    /// True if the text should be dimmed.
+    pub dim: Option<bool>,
+    /// True if the text should be italic.
+    pub italic: Option<bool>,
+    /// True if the text should be underlined.
+    pub underline: Option<bool>,
+    /// True if the text and background colors should be swapped.
+    pub reverse: Option<bool>,
     #[derivative(Debug = "ignore")]
     _p: (),
 }
@@ -131,6 +210,80 @@ impl Style {
         self.intense = Some(intense);
         self
     }
+
+    /// Sets whether the text is bold.
+    pub fn bold(&mut self, bold: bool) -> &mut Style {
+        self.bold = Some(bold);
+        self
+    }
+
+    /// Sets whether the text is dimmed.
+    pub fn dim(&mut self, dim: bool) -> &mut Style {
+        self.dim = Some(dim);
+        self
+    }
+
+    /// Sets whether the text is italic.
+    pub fn italic(&mut self, italic: bool) -> &mut Style {
+        self.italic = Some(italic);
+        self
+    }
+
+    /// Sets whether the text is underlined.
+    pub fn underline(&mut self, underline: bool) -> &mut Style {
+        self.underline = Some(underline);
+        self
+    }
+
+    /// Sets whether the text and background colors are swapped.
+    pub fn reverse(&mut self, reverse: bool) -> &mut Style {
+        self.reverse = Some(reverse);
+        self
+    }
+}
+
+impl FromStr for Style {
+    type Err = anyhow::Error;
+
+    /// Parses a list of `fg=<color>`, `bg=<color>` and the attributes `bold`,
+    /// `dim`, `italic`, `underline`, `reverse` and `intense`, separated by
+    /// commas or spaces, like `fg=#ff8800,bold`. A color on its own sets the
+    /// text color, as in `bright_red bold`.
+    ///
+    /// Colors are parsed as described by `Color`'s `FromStr` implementation.
+    fn from_str(s: &str) -> anyhow::Result<Style> {
+        let mut style = Style::new();
+        let items = s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|i| !i.is_empty());
+        for item in items {
+            match item.split_once('=') {
+                Some((key, color)) => {
+                    let color = color.parse()?;
+                    match key {
+                        "fg" => style.text(color),
+                        "bg" => style.background(color),
+                        key => anyhow::bail!("unknown style key `{}`", key),
+                    };
+                }
+                None => {
+                    match item {
+                        "bold" => style.bold(true),
+                        "dim" => style.dim(true),
+                        "italic" => style.italic(true),
+                        "underline" => style.underline(true),
+                        "reverse" => style.reverse(true),
+                        "intense" => style.intense(true),
+                        color => match color.parse() {
+                            Ok(color) => style.text(color),
+                            Err(_) => anyhow::bail!("unknown style attribute `{}`", item),
+                        },
+                    };
+                }
+            }
+        }
+        Ok(style)
+    }
 }
 
 /// A trait for types that an `Encode`r will write to.
@@ -149,8 +302,54 @@ pub trait Write: io::Write {
     }
 }
 
-impl<'a, W: Write + ?Sized> Write for &'a mut W {
+impl<W: Write + ?Sized> Write for &mut W {
     fn set_style(&mut self, style: &Style) -> io::Result<()> {
         <W as Write>::set_style(*self, style)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_color() {
+        assert_eq!("Red".parse::<Color>().unwrap(), Color::Red);
+        assert_eq!("208".parse::<Color>().unwrap(), Color::Ansi256(208));
+        assert_eq!(
+            "#FF8800".parse::<Color>().unwrap(),
+            Color::Rgb(0xff, 0x88, 0x00)
+        );
+        assert_eq!("bright_blue".parse::<Color>().unwrap(), Color::Ansi256(12));
+        assert!("orange".parse::<Color>().is_err());
+        assert!("bright_208".parse::<Color>().is_err());
+        assert!("256".parse::<Color>().is_err());
+        assert!("#ff88".parse::<Color>().is_err());
+        assert!("#ff88zz".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn parse_style() {
+        let mut expected = Style::new();
+        expected
+            .text(Color::Rgb(0xff, 0x88, 0x00))
+            .background(Color::Ansi256(236))
+            .bold(true)
+            .underline(true);
+        assert_eq!(
+            "fg=#ff8800, bg=236,bold,underline"
+                .parse::<Style>()
+                .unwrap(),
+            expected
+        );
+        assert_eq!("".parse::<Style>().unwrap(), Style::new());
+
+        let mut expected = Style::new();
+        expected.text(Color::Ansi256(9)).bold(true);
+        assert_eq!("bright_red bold".parse::<Style>().unwrap(), expected);
+
+        assert!("fg=orange".parse::<Style>().is_err());
+        assert!("blink".parse::<Style>().is_err());
+        assert!("color=red".parse::<Style>().is_err());
+    }
+}
@@ -12,7 +12,7 @@
 //! name := identifier
 //! argument := format_string
 //!
-//! format_spec := [ [ fill ] align ] [ min_width ] [ '.' max_width ]
+//! format_spec := [ [ fill ] align ] [ min_width ] [ '.' [ '-' ] max_width ]
 //! fill := character
 //! align := '<' | '>'
 //! min_width := number
@@ -40,43 +40,108 @@
 //! a formatter does not accept any argument.
 //!
 //! * `d`, `date` - The current time. By default, the ISO 8601 format is used.
-//!     A custom format may be provided in the syntax accepted by `chrono`.
-//!     The timezone defaults to local, but can be specified explicitly by
-//!     passing a second argument of `utc` for UTC or `local` for local time.
+//!   A custom format may be provided in the syntax accepted by `chrono`.
+//!   The timezone defaults to local, but can be specified explicitly by
+//!   passing a second argument of `utc` for UTC or `local` for local time.
 //!     * `{d}` - `2016-03-20T14:22:20.644420340-08:00`
 //!     * `{d(%Y-%m-%d %H:%M:%S)}` - `2016-03-20 14:22:20`
 //!     * `{d(%Y-%m-%d %H:%M:%S %Z)(utc)}` - `2016-03-20 22:22:20 UTC`
 //! * `f`, `file` - The source file that the log message came from, or `???` if
-//!     not provided.
+//!   not provided.
 //! * `h`, `highlight` - Styles its argument according to the log level. The
-//!     style is intense red for errors, red for warnings, blue for info, and
-//!     the default style for all other levels.
+//!   style is intense red for errors, yellow for warnings, green for info,
+//!   cyan for trace, and the default style for debug. An optional second
+//!   argument overrides the style of some levels with a comma separated list
+//!   of `<level>=<style>` entries, where a style is a space separated list of
+//!   the items accepted by the `style` formatter below. An empty style leaves
+//!   the level unstyled. The encoder's defaults can also be changed with
+//!   `PatternEncoderBuilder::highlight_style` or the `highlight_styles`
+//!   configuration.
 //!     * `{h(the level is {l})}` -
-//!         <code style="color: red; font-weight: bold">the level is ERROR</code>
+//!       <code style="color: red; font-weight: bold">the level is ERROR</code>
+//!     * `{h({l})(error=bright_red bold, info=, trace=dim)}`
+//! * `style` - Styles its second argument as described by the first, a comma
+//!   or space separated list of:
+//!     * `fg=<color>` and `bg=<color>` - The text and background colors. A
+//!       color is one of the eight basic colors `black`, `red`, `green`,
+//!       `yellow`, `blue`, `magenta`, `cyan` and `white` or their bright
+//!       variants like `bright_red`, a number from the 256 color palette like
+//!       `208`, or an RGB color like `#ff8800`. A color on its own sets the
+//!       text color.
+//!       Terminals which don't support 256 or RGB colors show the closest
+//!       color they do.
+//!     * `bold`, `dim`, `italic`, `underline`, `reverse` and `intense` - Text
+//!       attributes.
+//!
+//!   For example:
+//!     * `{style(fg=#ff8800,bold)({t})}` -
+//!       <code style="color: #ff8800; font-weight: bold">module::path</code>
 //! * `D`, `debug` - Outputs its arguments ONLY in debug build.
 //! * `R`, `release` - Outputs its arguments ONLY in release build.
-//! * `l`, `level` - The log level.
+//! * `l`, `level` - The log level. An optional argument of `short` outputs
+//!   only its first letter, and `lower` outputs it in lowercase.
+//!     * `{l}` - `WARN`
+//!     * `{l(short)}` - `W`
+//!     * `{l(lower)}` - `warn`
 //! * `L`, `line` - The line that the log message came from, or `???` if not
-//!     provided.
+//!   provided.
 //! * `m`, `message` - The log message.
 //! * `M`, `module` - The module that the log message came from, or `???` if not
-//!     provided.
+//!   provided. Accepts the same optional argument as `t`.
 //! * `P`, `pid` - The current process id.
+//! * `H`, `hostname` - The name of the host.
+//! * `E`, `env` - The value of an environment variable, read when the encoder
+//!   is created. The first argument specifies the variable's name, and the
+//!   second argument specifies the default value if it isn't set. The second
+//!   argument is optional, and defaults to the empty string.
+//!     * `{E(APP_ENV)(dev)}` - `production`
+//! * `r`, `relative` - The milliseconds elapsed since the first log4rs
+//!   `Logger` was created, or since the first `PatternEncoder` was created if
+//!   that was earlier.
+//! * `N`, `sequence` - A number which increases by one each time it is
+//!   formatted, starting at 1. The counter is shared by every `PatternEncoder`
+//!   in the process and each `N` in a pattern takes its own number, so the
+//!   numbers written by one appender have gaps if other encoders or other `N`s
+//!   format the sequence too.
 //! * `i`, `tid` - The current system-wide unique thread ID.
 //! * `n` - A platform-specific newline.
-//! * `t`, `target` - The target of the log message.
+//! * `t`, `target` - The target of the log message. An optional argument
+//!   gives a width to fit the target in by abbreviating its leading path
+//!   segments to their first character, from the left. The last segment is
+//!   always kept whole, and on its own if the width is 0.
+//!     * `{t(20)}` - `m::net::http::client` for the target
+//!       `my_crate::net::http::client`
+//!     * `{t(15)}` - `m::n::h::client`
+//!     * `{t(0)}` - `client`
 //! * `T`, `thread` - The name of the current thread.
 //! * `I`, `thread_id` - The pthread ID of the current thread.
 //! * `X`, `mdc` - A value from the [MDC][MDC]. The first argument specifies
-//!     the key, and the second argument specifies the default value if the
-//!     key is not present in the MDC. The second argument is optional, and
-//!     defaults to the empty string.
+//!   the key, and the second argument specifies the default value if the
+//!   key is not present in the MDC. The second argument is optional, and
+//!   defaults to the empty string.
 //!     * `{X(user_id)}` - `123e4567-e89b-12d3-a456-426655440000`
 //!     * `{X(nonexistent_key)(no mapping)}` - `no mapping`
 //! * An "unnamed" formatter simply formats its argument, applying the format
-//!     specification.
+//!   specification.
 //!     * `{({l} {m})}` - `INFO hello`
 //!
+//! # Custom formatters
+//!
+//! Formatters for values specific to an application, like the name of a
+//! tenant or the latency of a request, can be added with
+//! `PatternEncoderBuilder::formatter`:
+//!
+//! ```
+//! use log4rs::encode::pattern::PatternEncoder;
+//!
+//! let encoder = PatternEncoder::builder()
+//!     .formatter("tenant", |w, _record, args| {
+//!         let default = args.first().map_or("none", |s| s.as_str());
+//!         w.write_all(default.as_bytes())
+//!     })
+//!     .build("{tenant(unknown):<10} {m}{n}");
+//! ```
+//!
 //! # Format Specification
 //!
 //! The format specification determines how the output of a formatter is
@@ -92,7 +157,7 @@
 //!
 //! * `<` - Left align by appending the fill character to the formatter output
 //! * `>` - Right align by prepending the fill character to the formatter
-//!     output.
+//!   output.
 //!
 //! ## Width
 //!
@@ -101,6 +166,14 @@
 //! configured. Any output over the maximum length will be truncated, and
 //! output under the minimum length will be padded (see above).
 //!
+//! Widths are measured in the columns the output takes up in a terminal, so
+//! wide characters like those of CJK scripts count twice and combining
+//! characters don't count at all. Characters are never split by truncation.
+//!
+//! Truncation keeps the start of the output by default. A `-` before the
+//! maximum width keeps the end of the output instead, which suits targets and
+//! file paths whose most specific part comes last.
+//!
 //! # Examples
 //!
 //! The default pattern is `{d} {l} {t} - {m}{n}` which produces output like
@@ -108,32 +181,54 @@
 //! message`.
 //!
 //! The pattern `{m:>10.15}` will right-align the log message to a minimum of
-//! 10 bytes, filling in with space characters, and truncate output after 15
-//! bytes. The message `hello` will therefore be displayed as
+//! 10 columns, filling in with space characters, and truncate output after 15
+//! columns. The message `hello` will therefore be displayed as
 //! <code>     hello</code>, while the message `hello there, world!` will be
 //! displayed as `hello there, wo`.
 //!
 //! The pattern `{({l} {m}):15.15}` will output the log level and message
-//! limited to exactly 15 bytes, padding with space characters on the right if
+//! limited to exactly 15 columns, padding with space characters on the right if
 //! necessary. The message `hello` and log level `INFO` will be displayed as
 //! <code>INFO hello     </code>, while the message `hello, world!` and log
 //! level `DEBUG` will be truncated to `DEBUG hello, wo`.
 //!
+//! The pattern `{t:<20.-20}` will output the target in exactly 20 columns,
+//! keeping its end if it is longer. The target `my_crate::net::http::client`
+//! will be displayed as `e::net::http::client`, while `{t(20):<20.-20}`
+//! abbreviates it to `m::net::http::client` instead.
+//!
 //! [MDC]: https://crates.io/crates/log-mdc
 
 use chrono::{Local, Utc};
 use derivative::Derivative;
 use log::{Level, Record};
-use std::{default::Default, io, process, thread};
+use once_cell::sync::Lazy;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    default::Default,
+    env, fmt,
+    hash::{Hash, Hasher},
+    io, process, str,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::encode::{
     self,
-    pattern::parser::{Alignment, Parameters, Parser, Piece},
+    pattern::parser::{Alignment, Parameters, Parser, Piece, Truncate},
     Color, Encode, Style, NEWLINE,
 };
 
 #[cfg(feature = "config_parsing")]
 use crate::config::{Deserialize, Deserializers};
+#[cfg(feature = "config_parsing")]
+use std::collections::BTreeMap;
 
 mod parser;
 
@@ -142,20 +237,102 @@ thread_local!(
     static TID: usize = thread_id::get()
 );
 
+/// The name of the host, looked up once.
+static HOSTNAME: Lazy<String> = Lazy::new(|| hostname().unwrap_or_else(|| "???".to_owned()));
+
+/// The time the first `Logger` or `PatternEncoder` was created, which stands
+/// in for the start of the process.
+static START: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// Records the start time of the relative time formatter, if it isn't already
+/// set.
+pub(crate) fn mark_start() {
+    Lazy::force(&START);
+}
+
+/// The next number of the sequence formatter, shared by all encoders.
+static SEQUENCE: AtomicUsize = AtomicUsize::new(1);
+
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return None;
+    }
+    // The name isn't terminated if it was truncated.
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    env::var("COMPUTERNAME").ok()
+}
+
 /// The pattern encoder's configuration.
 #[cfg(feature = "config_parsing")]
 #[derive(Clone, Eq, PartialEq, Hash, Debug, Default, serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct PatternEncoderConfig {
     pattern: Option<String>,
+    #[serde(default)]
+    highlight_styles: BTreeMap<Level, String>,
 }
 
 fn is_char_boundary(b: u8) -> bool {
     b as i8 >= -0x40
 }
 
-fn char_starts(buf: &[u8]) -> usize {
-    buf.iter().filter(|&&b| is_char_boundary(b)).count()
+/// An iterator over the characters in a buffer, yielding the byte offset and
+/// display width of each.
+///
+/// Bytes which aren't valid UTF-8 count as a column if they could start a
+/// character.
+struct Columns<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Columns<'a> {
+    fn new(buf: &'a [u8]) -> Columns<'a> {
+        Columns { buf, pos: 0 }
+    }
+}
+
+impl<'a> Iterator for Columns<'a> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        let start = self.pos;
+        let lead = *self.buf.get(start)?;
+        let len = match lead {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        let c = self
+            .buf
+            .get(start..start + len)
+            .and_then(|c| str::from_utf8(c).ok())
+            .and_then(|c| c.chars().next());
+        match c {
+            Some(c) => {
+                self.pos += len;
+                // Control characters have no defined width.
+                Some((start, c.width().unwrap_or(1)))
+            }
+            None => {
+                self.pos += 1;
+                Some((start, is_char_boundary(lead) as usize))
+            }
+        }
+    }
+}
+
+/// Returns the display width of `buf`, in columns.
+fn columns(buf: &[u8]) -> usize {
+    Columns::new(buf).map(|(_, width)| width).sum()
 }
 
 struct MaxWidthWriter<'a> {
@@ -165,22 +342,26 @@ struct MaxWidthWriter<'a> {
 
 impl<'a> io::Write for MaxWidthWriter<'a> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // we don't want to report EOF, so just act as a sink past this point
+        if self.remaining == 0 {
+            return Ok(buf.len());
+        }
+
         let mut remaining = self.remaining;
         let mut end = buf.len();
-        for (idx, _) in buf
-            .iter()
-            .enumerate()
-            .filter(|&(_, &b)| is_char_boundary(b))
-        {
-            if remaining == 0 {
+        for (idx, width) in Columns::new(buf) {
+            // A wide character which doesn't fit ends the output rather than
+            // being split.
+            if width > remaining {
                 end = idx;
+                remaining = 0;
                 break;
             }
-            remaining -= 1;
+            remaining -= width;
         }
 
-        // we don't want to report EOF, so just act as a sink past this point
         if end == 0 {
+            self.remaining = 0;
             return Ok(buf.len());
         }
 
@@ -190,7 +371,7 @@ impl<'a> io::Write for MaxWidthWriter<'a> {
                 if len == end {
                     self.remaining = remaining;
                 } else {
-                    self.remaining -= char_starts(&buf[..len]);
+                    self.remaining -= columns(&buf[..len]);
                 }
                 Ok(len)
             }
@@ -209,6 +390,72 @@ impl<'a> encode::Write for MaxWidthWriter<'a> {
     }
 }
 
+/// Buffers output to keep only its rightmost columns.
+struct TruncateLeftWriter {
+    max_width: usize,
+    buf: Vec<BufferedOutput>,
+}
+
+impl TruncateLeftWriter {
+    fn finish(self, w: &mut dyn encode::Write) -> io::Result<()> {
+        let width: usize = self
+            .buf
+            .iter()
+            .map(|out| match out {
+                BufferedOutput::Data(data) => columns(data),
+                BufferedOutput::Style(_) => 0,
+            })
+            .sum();
+        let mut skip = width.saturating_sub(self.max_width);
+        let mut skipping = skip > 0;
+
+        for out in self.buf {
+            match out {
+                BufferedOutput::Data(ref data) => {
+                    let mut start = 0;
+                    if skipping {
+                        start = data.len();
+                        for (idx, width) in Columns::new(data) {
+                            // Skips the zero width characters combined with
+                            // the skipped ones, and the whole of a wide
+                            // character which doesn't fit.
+                            if skip == 0 && width > 0 {
+                                start = idx;
+                                skipping = false;
+                                break;
+                            }
+                            skip = skip.saturating_sub(width);
+                        }
+                    }
+                    w.write_all(&data[start..])?;
+                }
+                // Styles apply to the kept output even if they were set in
+                // the skipped part.
+                BufferedOutput::Style(ref style) => w.set_style(style)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl io::Write for TruncateLeftWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        buffer(&mut self.buf, buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl encode::Write for TruncateLeftWriter {
+    fn set_style(&mut self, style: &Style) -> io::Result<()> {
+        self.buf.push(BufferedOutput::Style(style.clone()));
+        Ok(())
+    }
+}
+
 struct LeftAlignWriter<W> {
     to_fill: usize,
     fill: char,
@@ -228,7 +475,7 @@ impl<W: encode::Write> io::Write for LeftAlignWriter<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         match self.w.write(buf) {
             Ok(len) => {
-                self.to_fill = self.to_fill.saturating_sub(char_starts(&buf[..len]));
+                self.to_fill = self.to_fill.saturating_sub(columns(&buf[..len]));
                 Ok(len)
             }
             Err(e) => Err(e),
@@ -251,6 +498,14 @@ enum BufferedOutput {
     Style(Style),
 }
 
+fn buffer(out: &mut Vec<BufferedOutput>, buf: &[u8]) {
+    if let Some(BufferedOutput::Data(data)) = out.last_mut() {
+        data.extend_from_slice(buf);
+    } else {
+        out.push(BufferedOutput::Data(buf.to_owned()));
+    }
+}
+
 struct RightAlignWriter<W> {
     to_fill: usize,
     fill: char,
@@ -275,17 +530,8 @@ impl<W: encode::Write> RightAlignWriter<W> {
 
 impl<W: encode::Write> io::Write for RightAlignWriter<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.to_fill = self.to_fill.saturating_sub(char_starts(buf));
-
-        let mut pushed = false;
-        if let Some(&mut BufferedOutput::Data(ref mut data)) = self.buf.last_mut() {
-            data.extend_from_slice(buf);
-            pushed = true;
-        };
-
-        if !pushed {
-            self.buf.push(BufferedOutput::Data(buf.to_owned()));
-        }
+        self.to_fill = self.to_fill.saturating_sub(columns(buf));
+        buffer(&mut self.buf, buf);
         Ok(buf.len())
     }
 
@@ -318,57 +564,25 @@ impl Chunk {
             Chunk::Formatted {
                 ref chunk,
                 ref params,
-            } => match (params.min_width, params.max_width, params.align) {
-                (None, None, _) => chunk.encode(w, record),
-                (None, Some(max_width), _) => {
-                    let mut w = MaxWidthWriter {
-                        remaining: max_width,
-                        w,
-                    };
-                    chunk.encode(&mut w, record)
-                }
-                (Some(min_width), None, Alignment::Left) => {
+            } => match (params.min_width, params.align) {
+                (None, _) => encode_truncated(chunk, params, w, record),
+                (Some(min_width), Alignment::Left) => {
                     let mut w = LeftAlignWriter {
                         to_fill: min_width,
                         fill: params.fill,
                         w,
                     };
-                    chunk.encode(&mut w, record)?;
+                    encode_truncated(chunk, params, &mut w, record)?;
                     w.finish()
                 }
-                (Some(min_width), None, Alignment::Right) => {
+                (Some(min_width), Alignment::Right) => {
                     let mut w = RightAlignWriter {
                         to_fill: min_width,
                         fill: params.fill,
                         w,
                         buf: vec![],
                     };
-                    chunk.encode(&mut w, record)?;
-                    w.finish()
-                }
-                (Some(min_width), Some(max_width), Alignment::Left) => {
-                    let mut w = LeftAlignWriter {
-                        to_fill: min_width,
-                        fill: params.fill,
-                        w: MaxWidthWriter {
-                            remaining: max_width,
-                            w,
-                        },
-                    };
-                    chunk.encode(&mut w, record)?;
-                    w.finish()
-                }
-                (Some(min_width), Some(max_width), Alignment::Right) => {
-                    let mut w = RightAlignWriter {
-                        to_fill: min_width,
-                        fill: params.fill,
-                        w: MaxWidthWriter {
-                            remaining: max_width,
-                            w,
-                        },
-                        buf: vec![],
-                    };
-                    chunk.encode(&mut w, record)?;
+                    encode_truncated(chunk, params, &mut w, record)?;
                     w.finish()
                 }
             },
@@ -377,8 +591,34 @@ impl Chunk {
     }
 }
 
-impl<'a> From<Piece<'a>> for Chunk {
-    fn from(piece: Piece<'a>) -> Chunk {
+fn encode_truncated(
+    chunk: &FormattedChunk,
+    params: &Parameters,
+    w: &mut dyn encode::Write,
+    record: &Record,
+) -> io::Result<()> {
+    match (params.max_width, params.truncate) {
+        (None, _) => chunk.encode(w, record),
+        (Some(max_width), Truncate::Right) => {
+            let mut w = MaxWidthWriter {
+                remaining: max_width,
+                w,
+            };
+            chunk.encode(&mut w, record)
+        }
+        (Some(max_width), Truncate::Left) => {
+            let mut buf = TruncateLeftWriter {
+                max_width,
+                buf: vec![],
+            };
+            chunk.encode(&mut buf, record)?;
+            buf.finish(w)
+        }
+    }
+}
+
+impl Chunk {
+    fn parse(piece: Piece, formatters: &Formatters) -> Chunk {
         match piece {
             Piece::Text(text) => Chunk::Text(text.to_owned()),
             Piece::Argument {
@@ -435,8 +675,31 @@ impl<'a> From<Piece<'a>> for Chunk {
                     }
                 }
                 "h" | "highlight" => {
-                    if formatter.args.len() != 1 {
-                        return Chunk::Error("expected exactly one argument".to_owned());
+                    if formatter.args.is_empty() || formatter.args.len() > 2 {
+                        return Chunk::Error("expected one or two arguments".to_owned());
+                    }
+
+                    let styles = match formatter.args.get(1) {
+                        Some(arg) => match text_arg(arg).and_then(|s| HighlightStyles::parse(&s)) {
+                            Ok(styles) => styles,
+                            Err(e) => return Chunk::Error(e),
+                        },
+                        None => HighlightStyles::default(),
+                    };
+                    let chunks = formatter
+                        .args
+                        .swap_remove(0)
+                        .into_iter()
+                        .map(|piece| Chunk::parse(piece, formatters))
+                        .collect();
+                    Chunk::Formatted {
+                        chunk: FormattedChunk::Highlight(styles, chunks),
+                        params: parameters,
+                    }
+                }
+                "style" => {
+                    if formatter.args.len() != 2 {
+                        return Chunk::Error("expected exactly two arguments".to_owned());
                     }
 
                     let chunks = formatter
@@ -444,10 +707,17 @@ impl<'a> From<Piece<'a>> for Chunk {
                         .pop()
                         .unwrap()
                         .into_iter()
-                        .map(From::from)
+                        .map(|piece| Chunk::parse(piece, formatters))
                         .collect();
+                    let style = match text_arg(&formatter.args[0])
+                        .and_then(|s| s.parse::<Style>().map_err(|e| e.to_string()))
+                    {
+                        Ok(style) => style,
+                        Err(e) => return Chunk::Error(e),
+                    };
+
                     Chunk::Formatted {
-                        chunk: FormattedChunk::Highlight(chunks),
+                        chunk: FormattedChunk::Style(style, chunks),
                         params: parameters,
                     }
                 }
@@ -461,7 +731,7 @@ impl<'a> From<Piece<'a>> for Chunk {
                         .pop()
                         .unwrap()
                         .into_iter()
-                        .map(From::from)
+                        .map(|piece| Chunk::parse(piece, formatters))
                         .collect();
                     Chunk::Formatted {
                         chunk: FormattedChunk::Debug(chunks),
@@ -478,24 +748,74 @@ impl<'a> From<Piece<'a>> for Chunk {
                         .pop()
                         .unwrap()
                         .into_iter()
-                        .map(From::from)
+                        .map(|piece| Chunk::parse(piece, formatters))
                         .collect();
                     Chunk::Formatted {
                         chunk: FormattedChunk::Release(chunks),
                         params: parameters,
                     }
                 }
-                "l" | "level" => no_args(&formatter.args, parameters, FormattedChunk::Level),
+                "l" | "level" => {
+                    let format = match formatter.args.as_slice() {
+                        [] => Ok(LevelFormat::Default),
+                        [arg] => text_arg(arg).and_then(|format| match format.as_str() {
+                            "short" => Ok(LevelFormat::Short),
+                            "lower" => Ok(LevelFormat::Lower),
+                            _ => Err(format!("invalid level format `{}`", format)),
+                        }),
+                        _ => Err("expected at most one argument".to_owned()),
+                    };
+                    match format {
+                        Ok(format) => Chunk::Formatted {
+                            chunk: FormattedChunk::Level(format),
+                            params: parameters,
+                        },
+                        Err(e) => Chunk::Error(e),
+                    }
+                }
                 "m" | "message" => no_args(&formatter.args, parameters, FormattedChunk::Message),
-                "M" | "module" => no_args(&formatter.args, parameters, FormattedChunk::Module),
+                "M" | "module" => match abbreviation(&formatter.args) {
+                    Ok(width) => Chunk::Formatted {
+                        chunk: FormattedChunk::Module(width),
+                        params: parameters,
+                    },
+                    Err(e) => Chunk::Error(e),
+                },
                 "n" => no_args(&formatter.args, parameters, FormattedChunk::Newline),
                 "f" | "file" => no_args(&formatter.args, parameters, FormattedChunk::File),
                 "L" | "line" => no_args(&formatter.args, parameters, FormattedChunk::Line),
                 "T" | "thread" => no_args(&formatter.args, parameters, FormattedChunk::Thread),
                 "I" | "thread_id" => no_args(&formatter.args, parameters, FormattedChunk::ThreadId),
                 "P" | "pid" => no_args(&formatter.args, parameters, FormattedChunk::ProcessId),
+                "H" | "hostname" => no_args(&formatter.args, parameters, FormattedChunk::Hostname),
+                "r" | "relative" => no_args(&formatter.args, parameters, FormattedChunk::Relative),
+                "N" | "sequence" => no_args(&formatter.args, parameters, FormattedChunk::Sequence),
+                "E" | "env" => {
+                    let value = match formatter.args.as_slice() {
+                        [name] => text_arg(name).map(|name| env::var(name).ok()),
+                        [name, default] => text_arg(name).and_then(|name| {
+                            let default = text_arg(default)?;
+                            Ok(Some(env::var(name).unwrap_or(default)))
+                        }),
+                        [] => Err("missing environment variable name".to_owned()),
+                        _ => Err("expected at most two arguments".to_owned()),
+                    };
+                    match value {
+                        Ok(value) => Chunk::Formatted {
+                            chunk: FormattedChunk::Env(value.unwrap_or_default()),
+                            params: parameters,
+                        },
+                        Err(e) => Chunk::Error(e),
+                    }
+                }
                 "i" | "tid" => no_args(&formatter.args, parameters, FormattedChunk::SystemThreadId),
-                "t" | "target" => no_args(&formatter.args, parameters, FormattedChunk::Target),
+                "t" | "target" => match abbreviation(&formatter.args) {
+                    Ok(width) => Chunk::Formatted {
+                        chunk: FormattedChunk::Target(width),
+                        params: parameters,
+                    },
+                    Err(e) => Chunk::Error(e),
+                },
                 "X" | "mdc" => {
                     if formatter.args.len() > 2 {
                         return Chunk::Error("expected at most two arguments".to_owned());
@@ -546,20 +866,102 @@ impl<'a> From<Piece<'a>> for Chunk {
                         .pop()
                         .unwrap()
                         .into_iter()
-                        .map(From::from)
+                        .map(|piece| Chunk::parse(piece, formatters))
                         .collect();
                     Chunk::Formatted {
                         chunk: FormattedChunk::Align(chunks),
                         params: parameters,
                     }
                 }
-                name => Chunk::Error(format!("unknown formatter `{}`", name)),
+                name => match formatters.get(name) {
+                    Some(format) => {
+                        let args = formatter
+                            .args
+                            .iter()
+                            .map(|arg| text_arg(arg))
+                            .collect::<Result<_, _>>();
+                        match args {
+                            Ok(args) => Chunk::Formatted {
+                                chunk: FormattedChunk::Custom(CustomFormatter {
+                                    name: name.to_owned(),
+                                    args,
+                                    format: format.clone(),
+                                }),
+                                params: parameters,
+                            },
+                            Err(e) => Chunk::Error(e),
+                        }
+                    }
+                    None => Chunk::Error(format!("unknown formatter `{}`", name)),
+                },
             },
             Piece::Error(err) => Chunk::Error(err),
         }
     }
 }
 
+/// Returns the text of an argument which may not contain formatters.
+fn text_arg(arg: &[Piece]) -> Result<String, String> {
+    let mut text = String::new();
+    for piece in arg {
+        match *piece {
+            Piece::Text(t) => text.push_str(t),
+            Piece::Argument { .. } => return Err("unexpected formatter".to_owned()),
+            Piece::Error(ref e) => return Err(e.clone()),
+        }
+    }
+    Ok(text)
+}
+
+/// Parses the optional width a path is abbreviated to.
+fn abbreviation(args: &[Vec<Piece>]) -> Result<Option<usize>, String> {
+    match args {
+        [] => Ok(None),
+        [arg] => {
+            let width = text_arg(arg)?;
+            match width.trim().parse() {
+                Ok(width) => Ok(Some(width)),
+                Err(_) => Err(format!("invalid abbreviation width `{}`", width)),
+            }
+        }
+        _ => Err("expected at most one argument".to_owned()),
+    }
+}
+
+/// Abbreviates the segments of a `::` separated path to their first
+/// character, from the left, until it fits in `width` columns.
+///
+/// The last segment is kept whole even if the path doesn't fit, and on its
+/// own if `width` is 0.
+fn abbreviate(path: &str, width: usize) -> Cow<'_, str> {
+    let mut total = path.width();
+    if total <= width {
+        return Cow::Borrowed(path);
+    }
+
+    let (last, init) = match path.rsplit_once("::") {
+        Some((init, last)) => (last, init),
+        None => return Cow::Borrowed(path),
+    };
+    if width == 0 {
+        return Cow::Borrowed(last);
+    }
+
+    let mut abbreviated = String::with_capacity(path.len());
+    for segment in init.split("::") {
+        match segment.chars().next() {
+            Some(first) if total > width => {
+                abbreviated.push(first);
+                total -= segment.width().saturating_sub(first.width().unwrap_or(0));
+            }
+            _ => abbreviated.push_str(segment),
+        }
+        abbreviated.push_str("::");
+    }
+    abbreviated.push_str(last);
+    Cow::Owned(abbreviated)
+}
+
 fn no_args(arg: &[Vec<Piece>], params: Parameters, chunk: FormattedChunk) -> Chunk {
     if arg.is_empty() {
         Chunk::Formatted { chunk, params }
@@ -568,31 +970,161 @@ fn no_args(arg: &[Vec<Piece>], params: Parameters, chunk: FormattedChunk) -> Chu
     }
 }
 
+/// The styles the highlight formatter applies to each level.
+///
+/// Levels without a style use the encoder's style, or the default one.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+struct HighlightStyles([Option<Style>; 5]);
+
+impl HighlightStyles {
+    /// Parses a comma separated list of `<level>=<style>` entries like
+    /// `error=bright_red bold, trace=dim`.
+    fn parse(s: &str) -> Result<HighlightStyles, String> {
+        let mut styles = HighlightStyles::default();
+        for entry in s.split(',').filter(|e| !e.trim().is_empty()) {
+            let (level, style) = match entry.split_once('=') {
+                Some(entry) => entry,
+                None => return Err(format!("invalid highlight style `{}`", entry.trim())),
+            };
+            let level = level
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid level `{}`", level.trim()))?;
+            let style = style.parse().map_err(|e: anyhow::Error| e.to_string())?;
+            styles.set(level, style);
+        }
+        Ok(styles)
+    }
+
+    fn set(&mut self, level: Level, style: Style) {
+        self.0[level as usize - 1] = Some(style);
+    }
+
+    /// Fills in the levels without a style from `defaults`.
+    fn inherit(&mut self, defaults: &HighlightStyles) {
+        for (style, default) in self.0.iter_mut().zip(&defaults.0) {
+            if style.is_none() {
+                style.clone_from(default);
+            }
+        }
+    }
+
+    /// Returns the style of `level`, or `None` if it isn't highlighted.
+    fn get(&self, level: Level) -> Option<Style> {
+        if let Some(ref style) = self.0[level as usize - 1] {
+            return Some(style.clone());
+        }
+        match level {
+            Level::Error => Some(Style::new().text(Color::Red).intense(true).clone()),
+            Level::Warn => Some(Style::new().text(Color::Yellow).clone()),
+            Level::Info => Some(Style::new().text(Color::Green).clone()),
+            Level::Debug => None,
+            Level::Trace => Some(Style::new().text(Color::Cyan).clone()),
+        }
+    }
+}
+
+/// Applies the encoder's highlight styles to the highlight formatters in
+/// `chunks`.
+fn inherit_highlight_styles(chunks: &mut [Chunk], defaults: &HighlightStyles) {
+    for chunk in chunks {
+        let chunk = match chunk {
+            Chunk::Formatted { chunk, .. } => chunk,
+            _ => continue,
+        };
+        match chunk {
+            FormattedChunk::Highlight(styles, chunks) => {
+                styles.inherit(defaults);
+                inherit_highlight_styles(chunks, defaults);
+            }
+            FormattedChunk::Align(chunks)
+            | FormattedChunk::Style(_, chunks)
+            | FormattedChunk::Debug(chunks)
+            | FormattedChunk::Release(chunks) => inherit_highlight_styles(chunks, defaults),
+            _ => {}
+        }
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 enum Timezone {
     Utc,
     Local,
 }
 
+/// The function of a custom formatter, which writes its output for a record
+/// given the formatter's arguments.
+type FormatFn = dyn Fn(&mut dyn encode::Write, &Record, &[String]) -> io::Result<()> + Send + Sync;
+
+/// Custom formatters by name.
+type Formatters = HashMap<String, Arc<FormatFn>>;
+
+/// A use of a custom formatter in a pattern.
+#[derive(Clone)]
+struct CustomFormatter {
+    name: String,
+    args: Vec<String>,
+    format: Arc<FormatFn>,
+}
+
+impl fmt::Debug for CustomFormatter {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CustomFormatter")
+            .field("name", &self.name)
+            .field("args", &self.args)
+            .finish()
+    }
+}
+
+impl PartialEq for CustomFormatter {
+    fn eq(&self, other: &CustomFormatter) -> bool {
+        self.name == other.name
+            && self.args == other.args
+            && Arc::ptr_eq(&self.format, &other.format)
+    }
+}
+
+impl Eq for CustomFormatter {}
+
+impl Hash for CustomFormatter {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.args.hash(state);
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+enum LevelFormat {
+    Default,
+    Short,
+    Lower,
+}
+
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 enum FormattedChunk {
     Time(String, Timezone),
-    Level,
+    Level(LevelFormat),
     Message,
-    Module,
+    Module(Option<usize>),
     File,
     Line,
     Thread,
     ThreadId,
     ProcessId,
     SystemThreadId,
-    Target,
+    Hostname,
+    Relative,
+    Sequence,
+    Env(String),
+    Target(Option<usize>),
     Newline,
     Align(Vec<Chunk>),
-    Highlight(Vec<Chunk>),
+    Highlight(HighlightStyles, Vec<Chunk>),
+    Style(Style, Vec<Chunk>),
     Debug(Vec<Chunk>),
     Release(Vec<Chunk>),
     Mdc(String, String),
+    Custom(CustomFormatter),
 }
 
 impl FormattedChunk {
@@ -602,9 +1134,21 @@ impl FormattedChunk {
             FormattedChunk::Time(ref fmt, Timezone::Local) => {
                 write!(w, "{}", Local::now().format(fmt))
             }
-            FormattedChunk::Level => write!(w, "{}", record.level()),
+            FormattedChunk::Level(LevelFormat::Default) => write!(w, "{}", record.level()),
+            FormattedChunk::Level(LevelFormat::Short) => {
+                w.write_all(&record.level().as_str().as_bytes()[..1])
+            }
+            FormattedChunk::Level(LevelFormat::Lower) => {
+                w.write_all(record.level().as_str().to_ascii_lowercase().as_bytes())
+            }
             FormattedChunk::Message => w.write_fmt(*record.args()),
-            FormattedChunk::Module => w.write_all(record.module_path().unwrap_or("???").as_bytes()),
+            FormattedChunk::Module(width) => {
+                let module = record.module_path().unwrap_or("???");
+                match width {
+                    Some(width) => w.write_all(abbreviate(module, width).as_bytes()),
+                    None => w.write_all(module.as_bytes()),
+                }
+            }
             FormattedChunk::File => w.write_all(record.file().unwrap_or("???").as_bytes()),
             FormattedChunk::Line => match record.line() {
                 Some(line) => write!(w, "{}", line),
@@ -615,10 +1159,19 @@ impl FormattedChunk {
             }
             FormattedChunk::ThreadId => w.write_all(thread_id::get().to_string().as_bytes()),
             FormattedChunk::ProcessId => w.write_all(process::id().to_string().as_bytes()),
+            FormattedChunk::Hostname => w.write_all(HOSTNAME.as_bytes()),
+            FormattedChunk::Relative => write!(w, "{}", START.elapsed().as_millis()),
+            FormattedChunk::Sequence => {
+                write!(w, "{}", SEQUENCE.fetch_add(1, Ordering::Relaxed))
+            }
+            FormattedChunk::Env(ref value) => w.write_all(value.as_bytes()),
             FormattedChunk::SystemThreadId => {
                 TID.with(|tid| w.write_all(tid.to_string().as_bytes()))
             }
-            FormattedChunk::Target => w.write_all(record.target().as_bytes()),
+            FormattedChunk::Target(width) => match width {
+                Some(width) => w.write_all(abbreviate(record.target(), width).as_bytes()),
+                None => w.write_all(record.target().as_bytes()),
+            },
             FormattedChunk::Newline => w.write_all(NEWLINE.as_bytes()),
             FormattedChunk::Align(ref chunks) => {
                 for chunk in chunks {
@@ -626,27 +1179,26 @@ impl FormattedChunk {
                 }
                 Ok(())
             }
-            FormattedChunk::Highlight(ref chunks) => {
-                match record.level() {
-                    Level::Error => {
-                        w.set_style(Style::new().text(Color::Red).intense(true))?;
-                    }
-                    Level::Warn => w.set_style(Style::new().text(Color::Yellow))?,
-                    Level::Info => w.set_style(Style::new().text(Color::Green))?,
-                    Level::Trace => w.set_style(Style::new().text(Color::Cyan))?,
-                    _ => {}
+            FormattedChunk::Highlight(ref styles, ref chunks) => {
+                let style = styles.get(record.level());
+                if let Some(ref style) = style {
+                    w.set_style(style)?;
                 }
                 for chunk in chunks {
                     chunk.encode(w, record)?;
                 }
-                match record.level() {
-                    Level::Error | Level::Warn | Level::Info | Level::Trace => {
-                        w.set_style(&Style::new())?
-                    }
-                    _ => {}
+                if style.is_some() {
+                    w.set_style(&Style::new())?;
                 }
                 Ok(())
             }
+            FormattedChunk::Style(ref style, ref chunks) => {
+                w.set_style(style)?;
+                for chunk in chunks {
+                    chunk.encode(w, record)?;
+                }
+                w.set_style(&Style::new())
+            }
             FormattedChunk::Debug(ref chunks) => {
                 if cfg!(debug_assertions) {
                     for chunk in chunks {
@@ -666,6 +1218,7 @@ impl FormattedChunk {
             FormattedChunk::Mdc(ref key, ref default) => {
                 log_mdc::get(key, |v| write!(w, "{}", v.unwrap_or(default)))
             }
+            FormattedChunk::Custom(ref custom) => (custom.format)(w, record, &custom.args),
         }
     }
 }
@@ -680,10 +1233,12 @@ pub struct PatternEncoder {
     pattern: String,
 }
 
+const DEFAULT_PATTERN: &str = "{d} {l} {t} - {m}{n}";
+
 /// Returns a `PatternEncoder` using the default pattern of `{d} {l} {t} - {m}{n}`.
 impl Default for PatternEncoder {
     fn default() -> PatternEncoder {
-        PatternEncoder::new("{d} {l} {t} - {m}{n}")
+        PatternEncoder::new(DEFAULT_PATTERN)
     }
 }
 
@@ -701,8 +1256,81 @@ impl PatternEncoder {
     ///
     /// The pattern string syntax is documented in the `pattern` module.
     pub fn new(pattern: &str) -> PatternEncoder {
+        PatternEncoder::builder().build(pattern)
+    }
+
+    /// Returns a new `PatternEncoder` builder.
+    pub fn builder() -> PatternEncoderBuilder {
+        PatternEncoderBuilder::default()
+    }
+}
+
+/// A builder for `PatternEncoder`s.
+///
+/// With the `config_parsing` feature, a builder can also be registered as the
+/// `pattern` encoder deserializer, so patterns in configuration files can use
+/// its custom formatters:
+///
+/// ```
+/// # #[cfg(feature = "config_parsing")]
+/// # fn f() {
+/// use log4rs::{config::Deserializers, encode::pattern::PatternEncoder};
+///
+/// let mut deserializers = Deserializers::default();
+/// deserializers.insert(
+///     "pattern",
+///     PatternEncoder::builder().formatter("tenant", |w, _, _| w.write_all(b"acme")),
+/// );
+/// # }
+/// ```
+#[derive(Derivative)]
+#[derivative(Debug)]
+#[derive(Clone, Default)]
+pub struct PatternEncoderBuilder {
+    highlight_styles: HighlightStyles,
+    #[derivative(Debug = "ignore")]
+    formatters: Formatters,
+}
+
+impl PatternEncoderBuilder {
+    /// Sets the style the `h` formatter applies to records at `level`.
+    ///
+    /// Styles given to the formatter in the pattern take precedence.
+    pub fn highlight_style(mut self, level: Level, style: Style) -> PatternEncoderBuilder {
+        self.highlight_styles.set(level, style);
+        self
+    }
+
+    /// Adds a custom formatter, used in patterns as `{name}`.
+    ///
+    /// The function is passed the writer, the record, and the arguments given
+    /// to the formatter in the pattern, like `tenant` in `{name(tenant)}`.
+    /// Arguments may not contain other formatters. The format specification
+    /// applies to the function's output as it does to built-in formatters.
+    ///
+    /// Built-in formatters take precedence over custom ones with the same
+    /// name.
+    pub fn formatter<F>(mut self, name: &str, format: F) -> PatternEncoderBuilder
+    where
+        F: Fn(&mut dyn encode::Write, &Record, &[String]) -> io::Result<()> + Send + Sync + 'static,
+    {
+        self.formatters.insert(name.to_owned(), Arc::new(format));
+        self
+    }
+
+    /// Consumes the `PatternEncoderBuilder`, producing a `PatternEncoder`
+    /// from a pattern string.
+    ///
+    /// The pattern string syntax is documented in the `pattern` module.
+    pub fn build(self, pattern: &str) -> PatternEncoder {
+        mark_start();
+
+        let mut chunks: Vec<Chunk> = Parser::new(pattern)
+            .map(|piece| Chunk::parse(piece, &self.formatters))
+            .collect();
+        inherit_highlight_styles(&mut chunks, &self.highlight_styles);
         PatternEncoder {
-            chunks: Parser::new(pattern).map(From::from).collect(),
+            chunks,
             pattern: pattern.to_owned(),
         }
     }
@@ -718,6 +1346,13 @@ impl PatternEncoder {
 /// # The pattern to follow when formatting logs. Defaults to
 /// # "{d} {l} {t} - {m}{n}".
 /// pattern: "{d} {l} {t} - {m}{n}"
+///
+/// # The styles the `h` formatter applies to each level, overriding the
+/// # default ones. Styles given to the formatter in the pattern take
+/// # precedence. Optional.
+/// highlight_styles:
+///   error: bright_red bold
+///   trace: dim
 /// ```
 #[cfg(feature = "config_parsing")]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -729,16 +1364,37 @@ impl Deserialize for PatternEncoderDeserializer {
 
     type Config = PatternEncoderConfig;
 
+    fn deserialize(
+        &self,
+        config: PatternEncoderConfig,
+        deserializers: &Deserializers,
+    ) -> anyhow::Result<Box<dyn Encode>> {
+        PatternEncoder::builder().deserialize(config, deserializers)
+    }
+}
+
+/// Deserializes a `PatternEncoder` with the builder's custom formatters and
+/// highlight styles, in the format of the `PatternEncoderDeserializer`.
+#[cfg(feature = "config_parsing")]
+impl Deserialize for PatternEncoderBuilder {
+    type Trait = dyn Encode;
+
+    type Config = PatternEncoderConfig;
+
     fn deserialize(
         &self,
         config: PatternEncoderConfig,
         _: &Deserializers,
     ) -> anyhow::Result<Box<dyn Encode>> {
-        let encoder = match config.pattern {
-            Some(pattern) => PatternEncoder::new(&pattern),
-            None => PatternEncoder::default(),
-        };
-        Ok(Box::new(encoder))
+        let mut builder = self.clone();
+        for (level, style) in config.highlight_styles {
+            let style = style
+                .parse()
+                .map_err(|e: anyhow::Error| e.context(format!("invalid {} style", level)))?;
+            builder = builder.highlight_style(level, style);
+        }
+        let pattern = config.pattern.as_deref().unwrap_or(DEFAULT_PATTERN);
+        Ok(Box::new(builder.build(pattern)))
     }
 }
 
@@ -758,10 +1414,10 @@ mod tests {
     use crate::encode::Encode;
 
     fn error_free(encoder: &PatternEncoder) -> bool {
-        encoder.chunks.iter().all(|c| match *c {
-            Chunk::Error(_) => false,
-            _ => true,
-        })
+        encoder
+            .chunks
+            .iter()
+            .all(|c| !matches!(*c, Chunk::Error(_)))
     }
 
     #[test]
@@ -1063,4 +1719,293 @@ mod tests {
             assert!(debug_buf.is_empty());
         }
     }
+
+    #[test]
+    #[cfg(feature = "ansi_writer")]
+    fn style() {
+        use crate::encode::writer::ansi::AnsiWriter;
+
+        let pw = PatternEncoder::new("{style(fg=red, underline)({l})} {m}");
+        assert!(error_free(&pw));
+        let mut buf = vec![];
+        pw.encode(
+            &mut AnsiWriter(&mut buf),
+            &Record::builder()
+                .level(Level::Info)
+                .args(format_args!("the message"))
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(buf, &b"\x1b[0;31;4mINFO\x1b[0m the message"[..]);
+
+        assert!(error_free(&PatternEncoder::new(
+            "{style(fg=#ff8800,bg=236,bold)({m})}"
+        )));
+        assert!(!error_free(&PatternEncoder::new("{style(fg=orange)({m})}")));
+        assert!(!error_free(&PatternEncoder::new("{style({l})({m})}")));
+        assert!(!error_free(&PatternEncoder::new("{style(bold)}")));
+    }
+
+    #[test]
+    #[cfg(feature = "ansi_writer")]
+    fn highlight_styles() {
+        use crate::encode::{writer::ansi::AnsiWriter, Color, Style};
+
+        let encode = |pw: &PatternEncoder, level| {
+            let mut buf = vec![];
+            pw.encode(
+                &mut AnsiWriter(&mut buf),
+                &Record::builder().level(level).build(),
+            )
+            .unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+
+        let pw = PatternEncoder::new("{h({l})}");
+        assert_eq!(encode(&pw, Level::Error), "\x1b[0;31;1mERROR\x1b[0m");
+        assert_eq!(encode(&pw, Level::Debug), "DEBUG");
+
+        let pw = PatternEncoder::builder()
+            .highlight_style(Level::Warn, Style::new().text(Color::Magenta).clone())
+            .highlight_style(Level::Trace, Style::new().text(Color::Blue).clone())
+            .build("{h({l})(warn=blue underline, debug=cyan, info=)}");
+        assert!(error_free(&pw));
+        assert_eq!(encode(&pw, Level::Warn), "\x1b[0;34;4mWARN\x1b[0m");
+        assert_eq!(encode(&pw, Level::Debug), "\x1b[0;36mDEBUG\x1b[0m");
+        assert_eq!(encode(&pw, Level::Info), "\x1b[0mINFO\x1b[0m");
+        assert_eq!(encode(&pw, Level::Trace), "\x1b[0;34mTRACE\x1b[0m");
+        assert_eq!(encode(&pw, Level::Error), "\x1b[0;31;1mERROR\x1b[0m");
+
+        assert!(!error_free(&PatternEncoder::new("{h({l})(fatal=red)}")));
+        assert!(!error_free(&PatternEncoder::new("{h({l})(error)}")));
+        assert!(!error_free(&PatternEncoder::new("{h({l})(error=orange)}")));
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml_format", feature = "config_parsing"))]
+    fn deserialize_highlight_styles() {
+        use super::{PatternEncoderConfig, PatternEncoderDeserializer};
+        use crate::config::{Deserialize, Deserializers};
+
+        let config = serde_yaml::from_str::<PatternEncoderConfig>(
+            "pattern: \"{h({l})}\"\nhighlight_styles:\n  ERROR: bright_red bold\n  trace: dim",
+        )
+        .unwrap();
+        assert_eq!(config.highlight_styles.len(), 2);
+        PatternEncoderDeserializer
+            .deserialize(config, &Deserializers::default())
+            .unwrap();
+
+        let config =
+            serde_yaml::from_str::<PatternEncoderConfig>("highlight_styles:\n  error: blink")
+                .unwrap();
+        assert!(PatternEncoderDeserializer
+            .deserialize(config, &Deserializers::default())
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn wide_chars() {
+        let encode = |pattern, message| {
+            let pw = PatternEncoder::new(pattern);
+            let mut buf = vec![];
+            pw.encode(
+                &mut SimpleWriter(&mut buf),
+                &Record::builder().args(format_args!("{}", message)).build(),
+            )
+            .unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+
+        assert_eq!(encode("{m:<6}|", "日本"), "日本  |");
+        assert_eq!(encode("{m:>6}|", "日本"), "  日本|");
+        assert_eq!(encode("{m:.3}|", "日本語"), "日|");
+        assert_eq!(encode("{m:.4}|", "日本語"), "日本|");
+        assert_eq!(
+            encode("{m:<2.2}|", "e\u{301}e\u{301}e\u{301}"),
+            "e\u{301}e\u{301}|"
+        );
+        assert_eq!(encode("{m:.-3}|", "日本語"), "語|");
+        assert_eq!(encode("{m:.-2}|", "xe\u{301}e\u{301}"), "e\u{301}e\u{301}|");
+    }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn truncate_left() {
+        let pw = PatternEncoder::new("{t:>8.-8}|{t:.-30}|{({t}{m}):.-5}");
+        let mut buf = vec![];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder()
+                .target("my_crate::client")
+                .args(format_args!("abc"))
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(buf, &b"::client|my_crate::client|ntabc"[..]);
+    }
+
+    #[test]
+    #[cfg(feature = "ansi_writer")]
+    fn truncate_left_style() {
+        use crate::encode::writer::ansi::AnsiWriter;
+
+        let pw = PatternEncoder::new("{({h({l})}{m}):.-4}");
+        let mut buf = vec![];
+        pw.encode(
+            &mut AnsiWriter(&mut buf),
+            &Record::builder()
+                .level(Level::Warn)
+                .args(format_args!("ab"))
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(buf, &b"\x1b[0;33mRN\x1b[0mab"[..]);
+    }
+
+    #[test]
+    fn abbreviate() {
+        use super::abbreviate;
+
+        let target = "my_crate::net::http::client";
+        assert_eq!(abbreviate(target, 30), target);
+        assert_eq!(abbreviate(target, 20), "m::net::http::client");
+        assert_eq!(abbreviate(target, 15), "m::n::h::client");
+        assert_eq!(abbreviate(target, 5), "m::n::h::client");
+        assert_eq!(abbreviate(target, 0), "client");
+        assert_eq!(abbreviate("client", 3), "client");
+        assert_eq!(abbreviate("日本::client", 8), "日::client");
+    }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn abbreviated_target() {
+        let pw = PatternEncoder::new("{t(15)} {M(0)}");
+        assert!(error_free(&pw));
+        let mut buf = vec![];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder()
+                .target("my_crate::net::http::client")
+                .module_path(Some("my_crate::net"))
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(buf, &b"m::n::h::client net"[..]);
+
+        assert!(!error_free(&PatternEncoder::new("{t(wide)}")));
+        assert!(!error_free(&PatternEncoder::new("{M(1)(2)}")));
+    }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn level_formats() {
+        let pw = PatternEncoder::new("{l} {l(short)} {l(lower)}");
+        let mut buf = vec![];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder().level(Level::Warn).build(),
+        )
+        .unwrap();
+        assert_eq!(buf, b"WARN W warn");
+
+        assert!(!error_free(&PatternEncoder::new("{l(upper)}")));
+    }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn env() {
+        std::env::set_var("LOG4RS_TEST_PATTERN_ENV", "set");
+        let pw = PatternEncoder::new(
+            "{E(LOG4RS_TEST_PATTERN_ENV)(default)} {E(LOG4RS_TEST_PATTERN_UNSET)(default)}|{E(LOG4RS_TEST_PATTERN_UNSET)}|",
+        );
+        let mut buf = vec![];
+        pw.encode(&mut SimpleWriter(&mut buf), &Record::builder().build())
+            .unwrap();
+        assert_eq!(buf, b"set default||");
+
+        assert!(!error_free(&PatternEncoder::new("{E}")));
+        assert!(!error_free(&PatternEncoder::new("{E({l})}")));
+    }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn hostname_relative_sequence() {
+        let pw = PatternEncoder::new("{H}|{r}|{N}|{N}");
+        let mut buf = vec![];
+        pw.encode(&mut SimpleWriter(&mut buf), &Record::builder().build())
+            .unwrap();
+        let buf = String::from_utf8(buf).unwrap();
+        let fields = buf.split('|').collect::<Vec<_>>();
+        assert_eq!(fields.len(), 4);
+        assert!(!fields[0].is_empty());
+        assert_ne!(fields[0], "???");
+        fields[1].parse::<u128>().unwrap();
+        let first = fields[2].parse::<usize>().unwrap();
+        assert!(fields[3].parse::<usize>().unwrap() > first);
+    }
+
+    #[test]
+    #[cfg(feature = "simple_writer")]
+    fn custom_formatter() {
+        let pw = PatternEncoder::builder()
+            .formatter("tenant", |w, record, args| {
+                write!(w, "{}:{}", record.target(), args.join(","))
+            })
+            .build("{tenant(a)(b):>10} {tenant:.4} {({tenant})}");
+        assert!(error_free(&pw));
+        let mut buf = vec![];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder().target("acme").build(),
+        )
+        .unwrap();
+        assert_eq!(buf, b"  acme:a,b acme acme:");
+
+        // Built-in formatters take precedence.
+        let pw = PatternEncoder::builder()
+            .formatter("l", |w, _, _| w.write_all(b"custom"))
+            .build("{l}");
+        let mut buf = vec![];
+        pw.encode(
+            &mut SimpleWriter(&mut buf),
+            &Record::builder().level(Level::Info).build(),
+        )
+        .unwrap();
+        assert_eq!(buf, b"INFO");
+
+        assert!(!error_free(&PatternEncoder::new("{tenant}")));
+        assert!(!error_free(
+            &PatternEncoder::builder()
+                .formatter("tenant", |_, _, _| Ok(()))
+                .build("{tenant({l})}")
+        ));
+    }
+
+    #[test]
+    #[cfg(all(
+        feature = "yaml_format",
+        feature = "config_parsing",
+        feature = "simple_writer"
+    ))]
+    fn deserialize_custom_formatter() {
+        use super::PatternEncoderConfig;
+        use crate::config::{Deserialize, Deserializers};
+
+        let builder = PatternEncoder::builder().formatter("tenant", |w, _, _| w.write_all(b"acme"));
+        let config =
+            serde_yaml::from_str::<PatternEncoderConfig>("pattern: \"{tenant} {m}\"").unwrap();
+        let encoder = builder
+            .deserialize(config, &Deserializers::default())
+            .unwrap();
+        let mut buf = vec![];
+        encoder
+            .encode(
+                &mut SimpleWriter(&mut buf),
+                &Record::builder().args(format_args!("hi")).build(),
+            )
+            .unwrap();
+        assert_eq!(buf, b"acme hi");
+    }
 }
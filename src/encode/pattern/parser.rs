@@ -23,6 +23,7 @@ pub struct Parameters {
     pub align: Alignment,
     pub min_width: Option<usize>,
     pub max_width: Option<usize>,
+    pub truncate: Truncate,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -31,6 +32,13 @@ pub enum Alignment {
     Right,
 }
 
+/// The side output over the maximum width is removed from.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Truncate {
+    Left,
+    Right,
+}
+
 #[derive(Clone, Debug)]
 pub struct Parser<'a> {
     pattern: &'a str,
@@ -126,6 +134,7 @@ impl<'a> Parser<'a> {
             align: Alignment::Left,
             min_width: None,
             max_width: None,
+            truncate: Truncate::Right,
         };
 
         if !self.consume(':') {
@@ -153,6 +162,9 @@ impl<'a> Parser<'a> {
         }
 
         if self.consume('.') {
+            if self.consume('-') {
+                params.truncate = Truncate::Left;
+            }
             if let Some(max_width) = self.integer() {
                 params.max_width = Some(max_width);
             }
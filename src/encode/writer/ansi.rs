@@ -2,8 +2,13 @@
 //!
 //! Requires the `ansi_writer` feature.
 
-use crate::encode::{self, Color, Style};
-use std::{fmt, io};
+use once_cell::sync::Lazy;
+use std::{
+    env, fmt,
+    io::{self, Write},
+};
+
+use crate::encode::{self, basic_index, Color, Style};
 
 /// An `encode::Write`r that wraps an `io::Write`r, emitting ANSI escape codes
 /// for text style.
@@ -30,53 +35,183 @@ impl<W: io::Write> io::Write for AnsiWriter<W> {
 
 impl<W: io::Write> encode::Write for AnsiWriter<W> {
     fn set_style(&mut self, style: &Style) -> io::Result<()> {
-        let mut buf = [0; 12];
-        buf[0] = b'\x1b';
-        buf[1] = b'[';
-        buf[2] = b'0';
-        let mut idx = 3;
-
-        if let Some(text) = style.text {
-            buf[idx] = b';';
-            buf[idx + 1] = b'3';
-            buf[idx + 2] = color_byte(text);
-            idx += 3;
-        }
+        self.0.write_all(&sgr(style, *COLOR_SUPPORT))
+    }
+}
+
+/// The colors a terminal can display.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+enum ColorSupport {
+    /// The eight basic colors and their bright variants.
+    Basic,
+    /// The 256 color palette of xterm.
+    Ansi256,
+    /// 24-bit RGB colors.
+    TrueColor,
+}
 
-        if let Some(background) = style.background {
-            buf[idx] = b';';
-            buf[idx + 1] = b'4';
-            buf[idx + 2] = color_byte(background);
-            idx += 3;
+impl ColorSupport {
+    /// Detects the colors a terminal supports from the `COLORTERM` and `TERM`
+    /// environment variables, in the way most terminal applications do.
+    fn detect(colorterm: Option<&str>, term: Option<&str>) -> ColorSupport {
+        if let Some("truecolor") | Some("24bit") = colorterm {
+            return ColorSupport::TrueColor;
         }
+        match term {
+            Some(term) if term.ends_with("-direct") => ColorSupport::TrueColor,
+            Some(term) if term.contains("256color") => ColorSupport::Ansi256,
+            _ => ColorSupport::Basic,
+        }
+    }
+}
 
-        if let Some(intense) = style.intense {
-            buf[idx] = b';';
-            if intense {
-                buf[idx + 1] = b'1';
-                idx += 2;
-            } else {
-                buf[idx + 1] = b'2';
-                buf[idx + 2] = b'2';
-                idx += 3;
-            }
+static COLOR_SUPPORT: Lazy<ColorSupport> = Lazy::new(|| {
+    ColorSupport::detect(
+        env::var("COLORTERM").ok().as_deref(),
+        env::var("TERM").ok().as_deref(),
+    )
+});
+
+/// The xterm RGB values of the eight basic colors.
+const BASIC: [(Color, (u8, u8, u8)); 8] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::White, (229, 229, 229)),
+];
+
+/// The levels of each component in the 6x6x6 color cube of the 256 color
+/// palette.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Returns the RGB value of a color from the 256 color palette.
+fn ansi256_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=7 => BASIC[n as usize].1,
+        // The bright variants of the basic colors.
+        8 => (127, 127, 127),
+        9..=15 => {
+            let (r, g, b) = BASIC[n as usize - 8].1;
+            let bright = |c: u8| if c == 0 { 0 } else { 255 };
+            (bright(r), bright(g), bright(b))
+        }
+        16..=231 => {
+            let n = n - 16;
+            (
+                CUBE[n as usize / 36],
+                CUBE[n as usize / 6 % 6],
+                CUBE[n as usize % 6],
+            )
         }
-        buf[idx] = b'm';
-        self.0.write_all(&buf[..=idx])
+        _ => {
+            let level = 8 + (n - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2) as u32;
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+/// Returns the color of the 256 color palette closest to an RGB color.
+fn rgb_to_ansi256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |c: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| (i32::from(CUBE[i]) - i32::from(c)).abs())
+            .unwrap() as u8
+    };
+    let (r, g, b) = rgb;
+    let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+
+    let average = (u32::from(r) + u32::from(g) + u32::from(b)) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    if distance(ansi256_rgb(gray), rgb) < distance(ansi256_rgb(cube), rgb) {
+        gray
+    } else {
+        cube
     }
 }
 
-fn color_byte(c: Color) -> u8 {
-    match c {
-        Color::Black => b'0',
-        Color::Red => b'1',
-        Color::Green => b'2',
-        Color::Yellow => b'3',
-        Color::Blue => b'4',
-        Color::Magenta => b'5',
-        Color::Cyan => b'6',
-        Color::White => b'7',
+/// Returns the basic color closest to `color`, and whether it is the bright
+/// variant of that color.
+pub(crate) fn to_basic(color: Color) -> (Color, bool) {
+    let rgb = match color {
+        Color::Ansi256(n @ 0..=7) => return (BASIC[n as usize].0, false),
+        Color::Ansi256(n @ 8..=15) => return (BASIC[n as usize - 8].0, true),
+        Color::Ansi256(n) => ansi256_rgb(n),
+        Color::Rgb(r, g, b) => (r, g, b),
+        basic => return (basic, false),
+    };
+    let basic = BASIC
+        .iter()
+        .min_by_key(|(_, basic)| distance(*basic, rgb))
+        .unwrap()
+        .0;
+    (basic, false)
+}
+
+/// Returns the SGR escape sequence setting `style`, downgrading colors the
+/// terminal doesn't support.
+fn sgr(style: &Style, support: ColorSupport) -> Vec<u8> {
+    let mut buf = b"\x1b[0".to_vec();
+
+    let mut color = |base: u8, color: Color| {
+        let color = match (color, support) {
+            (Color::Rgb(r, g, b), ColorSupport::Ansi256) => {
+                Color::Ansi256(rgb_to_ansi256((r, g, b)))
+            }
+            (Color::Rgb(..), ColorSupport::Basic) | (Color::Ansi256(_), ColorSupport::Basic) => {
+                match to_basic(color) {
+                    (basic, false) => basic,
+                    (basic, true) => {
+                        // Bright colors are 90-97 and 100-107.
+                        let _ = write!(buf, ";{}", base + 60 + basic_index(basic));
+                        return;
+                    }
+                }
+            }
+            (color, _) => color,
+        };
+        let _ = match color {
+            Color::Ansi256(n) => write!(buf, ";{};5;{}", base + 8, n),
+            Color::Rgb(r, g, b) => write!(buf, ";{};2;{};{};{}", base + 8, r, g, b),
+            basic => write!(buf, ";{}", base + basic_index(basic)),
+        };
+    };
+
+    if let Some(text) = style.text {
+        color(30, text);
+    }
+    if let Some(background) = style.background {
+        color(40, background);
+    }
+
+    if let Some(intense) = style.intense {
+        buf.extend_from_slice(if intense { b";1" } else { b";22" });
+    }
+    let attributes = [
+        (style.bold, b";1"),
+        (style.dim, b";2"),
+        (style.italic, b";3"),
+        (style.underline, b";4"),
+        (style.reverse, b";7"),
+    ];
+    for (enabled, code) in attributes.iter() {
+        // The attributes were reset above.
+        if *enabled == Some(true) {
+            buf.extend_from_slice(*code);
+        }
     }
+
+    buf.push(b'm');
+    buf
 }
 
 #[cfg(test)]
@@ -106,4 +241,51 @@ mod test {
         w.write_all(b" normal\n").unwrap();
         w.flush().unwrap();
     }
+
+    #[test]
+    fn sgr_codes() {
+        let mut style = Style::new();
+        style
+            .text(Color::Rgb(0xff, 0x88, 0x00))
+            .background(Color::Ansi256(236))
+            .bold(true)
+            .underline(true);
+        let sgr = |support| String::from_utf8(sgr(&style, support)).unwrap();
+        assert_eq!(
+            sgr(ColorSupport::TrueColor),
+            "\x1b[0;38;2;255;136;0;48;5;236;1;4m"
+        );
+        assert_eq!(sgr(ColorSupport::Ansi256), "\x1b[0;38;5;208;48;5;236;1;4m");
+        assert_eq!(sgr(ColorSupport::Basic), "\x1b[0;33;40;1;4m");
+
+        let style = Style::new().text(Color::Ansi256(9)).intense(false).clone();
+        assert_eq!(
+            String::from_utf8(super::sgr(&style, ColorSupport::Basic)).unwrap(),
+            "\x1b[0;91;22m"
+        );
+    }
+
+    #[test]
+    fn downgrade() {
+        assert_eq!(rgb_to_ansi256((0, 0, 0)), 16);
+        assert_eq!(rgb_to_ansi256((255, 255, 255)), 231);
+        assert_eq!(rgb_to_ansi256((128, 128, 128)), 244);
+        assert_eq!(to_basic(Color::Rgb(200, 10, 10)), (Color::Red, false));
+        assert_eq!(to_basic(Color::Ansi256(12)), (Color::Blue, true));
+        assert_eq!(to_basic(Color::Ansi256(231)), (Color::White, false));
+        assert_eq!(to_basic(Color::Cyan), (Color::Cyan, false));
+    }
+
+    #[test]
+    fn detect() {
+        let detect = ColorSupport::detect;
+        assert_eq!(
+            detect(Some("truecolor"), Some("xterm")),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(detect(None, Some("xterm-direct")), ColorSupport::TrueColor);
+        assert_eq!(detect(None, Some("xterm-256color")), ColorSupport::Ansi256);
+        assert_eq!(detect(None, Some("xterm")), ColorSupport::Basic);
+        assert_eq!(detect(None, None), ColorSupport::Basic);
+    }
 }
@@ -31,9 +31,11 @@ static COLOR_MODE: Lazy<ColorMode> = Lazy::new(|| {
 });
 
 /// The color output mode for a `ConsoleAppender`
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
 pub enum ColorMode {
-    /// Print color only if the output is recognized as a console
+    /// Print color only if the output is recognized as a console, unless the
+    /// `NO_COLOR`, `CLICOLOR_FORCE` or `CLICOLOR` environment variables say
+    /// otherwise
     #[default]
     Auto,
     /// Force color output
@@ -42,6 +44,15 @@ pub enum ColorMode {
     Never,
 }
 
+impl ColorMode {
+    fn resolve(self) -> ColorMode {
+        match self {
+            ColorMode::Auto => *COLOR_MODE,
+            mode => mode,
+        }
+    }
+}
+
 /// An `encode::Write`r that outputs to a console.
 pub struct ConsoleWriter(imp::Writer);
 
@@ -51,7 +62,17 @@ impl ConsoleWriter {
     /// Returns `None` if standard out is not a console buffer on Windows, and
     /// if it is not a TTY on Unix.
     pub fn stdout() -> Option<ConsoleWriter> {
-        imp::Writer::stdout().map(ConsoleWriter)
+        ConsoleWriter::stdout_with_color(ColorMode::Auto)
+    }
+
+    /// Returns a new `ConsoleWriter` that will write to standard out, with
+    /// the given color mode.
+    ///
+    /// With `ColorMode::Auto`, this is equivalent to `stdout`. With
+    /// `ColorMode::Always`, it is returned on Unix even if standard out is
+    /// not a TTY. With `ColorMode::Never`, `None` is returned.
+    pub fn stdout_with_color(mode: ColorMode) -> Option<ConsoleWriter> {
+        imp::Writer::stdout(mode.resolve()).map(ConsoleWriter)
     }
 
     /// Returns a new `ConsoleWriter` that will write to standard error.
@@ -59,11 +80,37 @@ impl ConsoleWriter {
     /// Returns `None` if standard error is not a console buffer on Windows, and
     /// if it is not a TTY on Unix.
     pub fn stderr() -> Option<ConsoleWriter> {
-        imp::Writer::stderr().map(ConsoleWriter)
+        ConsoleWriter::stderr_with_color(ColorMode::Auto)
+    }
+
+    /// Returns a new `ConsoleWriter` that will write to standard error, with
+    /// the given color mode.
+    ///
+    /// With `ColorMode::Auto`, this is equivalent to `stderr`. With
+    /// `ColorMode::Always`, it is returned on Unix even if standard error is
+    /// not a TTY. With `ColorMode::Never`, `None` is returned.
+    pub fn stderr_with_color(mode: ColorMode) -> Option<ConsoleWriter> {
+        imp::Writer::stderr(mode.resolve()).map(ConsoleWriter)
+    }
+
+    /// Returns whether standard out is a console buffer on Windows or a TTY
+    /// on Unix, whatever the color mode.
+    // Only used by components behind optional features.
+    #[allow(dead_code)]
+    pub(crate) fn is_stdout_tty() -> bool {
+        imp::Writer::stdout(ColorMode::Auto).is_some()
+    }
+
+    /// Returns whether standard error is a console buffer on Windows or a
+    /// TTY on Unix, whatever the color mode.
+    // Only used by components behind optional features.
+    #[allow(dead_code)]
+    pub(crate) fn is_stderr_tty() -> bool {
+        imp::Writer::stderr(ColorMode::Auto).is_some()
     }
 
     /// Locks the console, preventing other threads from writing concurrently.
-    pub fn lock(&self) -> ConsoleWriterLock {
+    pub fn lock(&self) -> ConsoleWriterLock<'_> {
         ConsoleWriterLock(self.0.lock())
     }
 }
@@ -126,10 +173,7 @@ mod imp {
     use crate::{
         encode::{
             self,
-            writer::{
-                ansi::AnsiWriter,
-                console::{ColorMode, COLOR_MODE},
-            },
+            writer::{ansi::AnsiWriter, console::ColorMode},
             Style,
         },
         priv_io::{StdWriter, StdWriterLock},
@@ -138,9 +182,9 @@ mod imp {
     pub struct Writer(AnsiWriter<StdWriter>);
 
     impl Writer {
-        pub fn stdout() -> Option<Writer> {
+        pub fn stdout(mode: ColorMode) -> Option<Writer> {
             let writer = || Writer(AnsiWriter(StdWriter::stdout()));
-            match *COLOR_MODE {
+            match mode {
                 ColorMode::Auto => {
                     if unsafe { libc::isatty(libc::STDOUT_FILENO) } != 1 {
                         None
@@ -153,9 +197,9 @@ mod imp {
             }
         }
 
-        pub fn stderr() -> Option<Writer> {
+        pub fn stderr(mode: ColorMode) -> Option<Writer> {
             let writer = || Writer(AnsiWriter(StdWriter::stderr()));
-            match *COLOR_MODE {
+            match mode {
                 ColorMode::Auto => {
                     if unsafe { libc::isatty(libc::STDERR_FILENO) } != 1 {
                         None
@@ -168,7 +212,7 @@ mod imp {
             }
         }
 
-        pub fn lock(&self) -> WriterLock {
+        pub fn lock(&self) -> WriterLock<'_> {
             WriterLock(AnsiWriter((self.0).0.lock()))
         }
     }
@@ -239,7 +283,7 @@ mod imp {
     use crate::{
         encode::{
             self,
-            writer::console::{ColorMode, COLOR_MODE},
+            writer::{ansi::to_basic, console::ColorMode},
             Color, Style,
         },
         priv_io::{StdWriter, StdWriterLock},
@@ -257,50 +301,49 @@ mod imp {
         fn set_style(&self, style: &Style) -> io::Result<()> {
             let mut attrs = self.defaults;
 
+            let rgb = (wincon::FOREGROUND_RED | wincon::FOREGROUND_GREEN | wincon::FOREGROUND_BLUE)
+                as minwindef::WORD;
+            let intensity = wincon::FOREGROUND_INTENSITY as minwindef::WORD;
+
             if let Some(text) = style.text {
-                attrs &= !((wincon::FOREGROUND_RED
-                    | wincon::FOREGROUND_GREEN
-                    | wincon::FOREGROUND_BLUE) as minwindef::WORD);
-                attrs |= match text {
-                    Color::Black => 0,
-                    Color::Red => wincon::FOREGROUND_RED,
-                    Color::Green => wincon::FOREGROUND_GREEN,
-                    Color::Yellow => wincon::FOREGROUND_RED | wincon::FOREGROUND_GREEN,
-                    Color::Blue => wincon::FOREGROUND_BLUE,
-                    Color::Magenta => wincon::FOREGROUND_RED | wincon::FOREGROUND_BLUE,
-                    Color::Cyan => wincon::FOREGROUND_GREEN | wincon::FOREGROUND_BLUE,
-                    Color::White => {
-                        wincon::FOREGROUND_RED | wincon::FOREGROUND_GREEN | wincon::FOREGROUND_BLUE
-                    }
-                } as minwindef::WORD;
+                let (attr, bright) = color_attrs(text);
+                attrs &= !rgb;
+                attrs |= attr;
+                if bright {
+                    attrs |= intensity;
+                }
             }
 
             if let Some(background) = style.background {
-                attrs &= !((wincon::BACKGROUND_RED
-                    | wincon::BACKGROUND_GREEN
-                    | wincon::BACKGROUND_BLUE) as minwindef::WORD);
-                attrs |= match background {
-                    Color::Black => 0,
-                    Color::Red => wincon::BACKGROUND_RED,
-                    Color::Green => wincon::BACKGROUND_GREEN,
-                    Color::Yellow => wincon::BACKGROUND_RED | wincon::BACKGROUND_GREEN,
-                    Color::Blue => wincon::BACKGROUND_BLUE,
-                    Color::Magenta => wincon::BACKGROUND_RED | wincon::BACKGROUND_BLUE,
-                    Color::Cyan => wincon::BACKGROUND_GREEN | wincon::BACKGROUND_BLUE,
-                    Color::White => {
-                        wincon::BACKGROUND_RED | wincon::BACKGROUND_GREEN | wincon::BACKGROUND_BLUE
-                    }
-                } as minwindef::WORD;
+                // The background attributes are the foreground ones shifted.
+                let (attr, bright) = color_attrs(background);
+                attrs &= !(rgb << 4);
+                attrs |= attr << 4;
+                if bright {
+                    attrs |= intensity << 4;
+                }
             }
 
-            if let Some(intense) = style.intense {
+            // The console can't display bold text, so it is shown intense.
+            let intense = match style.bold {
+                Some(true) => Some(true),
+                _ => style.intense,
+            };
+            if let Some(intense) = intense {
                 if intense {
-                    attrs |= wincon::FOREGROUND_INTENSITY as minwindef::WORD;
+                    attrs |= intensity;
                 } else {
-                    attrs &= !(wincon::FOREGROUND_INTENSITY as minwindef::WORD);
+                    attrs &= !intensity;
                 }
             }
 
+            if style.underline == Some(true) {
+                attrs |= wincon::COMMON_LVB_UNDERSCORE as minwindef::WORD;
+            }
+            if style.reverse == Some(true) {
+                attrs |= wincon::COMMON_LVB_REVERSE_VIDEO as minwindef::WORD;
+            }
+
             if unsafe { wincon::SetConsoleTextAttribute(self.handle, attrs) } == 0 {
                 Err(io::Error::last_os_error())
             } else {
@@ -309,13 +352,33 @@ mod imp {
         }
     }
 
+    /// Returns the foreground attributes of the basic color closest to
+    /// `color`, and whether it is a bright color.
+    fn color_attrs(color: Color) -> (minwindef::WORD, bool) {
+        let (color, bright) = to_basic(color);
+        let attrs = match color {
+            Color::Black => 0,
+            Color::Red => wincon::FOREGROUND_RED,
+            Color::Green => wincon::FOREGROUND_GREEN,
+            Color::Yellow => wincon::FOREGROUND_RED | wincon::FOREGROUND_GREEN,
+            Color::Blue => wincon::FOREGROUND_BLUE,
+            Color::Magenta => wincon::FOREGROUND_RED | wincon::FOREGROUND_BLUE,
+            Color::Cyan => wincon::FOREGROUND_GREEN | wincon::FOREGROUND_BLUE,
+            Color::White => {
+                wincon::FOREGROUND_RED | wincon::FOREGROUND_GREEN | wincon::FOREGROUND_BLUE
+            }
+            Color::Ansi256(_) | Color::Rgb(..) => unreachable!("not a basic color"),
+        };
+        (attrs as minwindef::WORD, bright)
+    }
+
     pub struct Writer {
         console: RawConsole,
         inner: StdWriter,
     }
 
     impl Writer {
-        pub fn stdout() -> Option<Writer> {
+        pub fn stdout(mode: ColorMode) -> Option<Writer> {
             unsafe {
                 let handle = processenv::GetStdHandle(winbase::STD_OUTPUT_HANDLE);
                 if handle.is_null() || handle == handleapi::INVALID_HANDLE_VALUE {
@@ -335,14 +398,14 @@ mod imp {
                     inner: StdWriter::stdout(),
                 };
 
-                match *COLOR_MODE {
+                match mode {
                     ColorMode::Auto | ColorMode::Always => Some(writer),
                     ColorMode::Never => None,
                 }
             }
         }
 
-        pub fn stderr() -> Option<Writer> {
+        pub fn stderr(mode: ColorMode) -> Option<Writer> {
             unsafe {
                 let handle = processenv::GetStdHandle(winbase::STD_ERROR_HANDLE);
                 if handle.is_null() || handle == handleapi::INVALID_HANDLE_VALUE {
@@ -359,10 +422,10 @@ mod imp {
                         handle,
                         defaults: info.wAttributes,
                     },
-                    inner: StdWriter::stdout(),
+                    inner: StdWriter::stderr(),
                 };
 
-                match *COLOR_MODE {
+                match mode {
                     ColorMode::Auto | ColorMode::Always => Some(writer),
                     ColorMode::Never => None,
                 }
@@ -463,4 +526,12 @@ mod test {
         w.write_all(b" normal\n").unwrap();
         w.flush().unwrap();
     }
+
+    #[test]
+    fn color_mode() {
+        assert!(ConsoleWriter::stdout_with_color(ColorMode::Never).is_none());
+        assert!(ConsoleWriter::stderr_with_color(ColorMode::Never).is_none());
+        #[cfg(unix)]
+        assert!(ConsoleWriter::stdout_with_color(ColorMode::Always).is_some());
+    }
 }
@@ -0,0 +1,515 @@
+//! Error reporting.
+//!
+//! Errors which log4rs can't return to a caller, such as an appender failing
+//! to write a record or a configuration file failing to reload, are passed to
+//! the logger's `ErrorHandler` as an `ErrorEvent`.
+//!
+//! The default handler writes events to stderr through a
+//! `RateLimitedErrorHandler`, so that an appender failing on every record
+//! (e.g. because the disk is full) reports the failure periodically along
+//! with the number of repeats, rather than once per record.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[cfg(test)]
+use mock_instant::Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+use log::Record;
+
+/// The operation which produced an error.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An appender failed to write a record.
+    Append,
+    /// The configuration contained an invalid component.
+    Config,
+    /// Reloading the configuration file failed.
+    ConfigReload,
+    /// Rolling a log file on request failed.
+    Roll,
+    /// A hook run on an archived log file failed.
+    PostRoll,
+    /// Reopening a log file on request failed.
+    Reopen,
+    /// A failover appender switched to another appender, or an appender it
+    /// had switched away from recovered. The record was still written.
+    Failover,
+}
+
+/// An error reported to an `ErrorHandler`.
+#[derive(Debug)]
+pub struct ErrorEvent<'a> {
+    kind: ErrorKind,
+    error: &'a anyhow::Error,
+    appender: Option<&'a str>,
+    record: Option<&'a Record<'a>>,
+    suppressed: u64,
+}
+
+impl<'a> ErrorEvent<'a> {
+    /// Creates a new `ErrorEvent`.
+    pub fn new(kind: ErrorKind, error: &'a anyhow::Error) -> ErrorEvent<'a> {
+        ErrorEvent {
+            kind,
+            error,
+            appender: None,
+            record: None,
+            suppressed: 0,
+        }
+    }
+
+    /// Sets the name of the appender which produced the error.
+    pub fn appender(mut self, appender: &'a str) -> ErrorEvent<'a> {
+        self.appender = Some(appender);
+        self
+    }
+
+    /// Sets the record which was being logged when the error occurred.
+    pub fn record(mut self, record: &'a Record<'a>) -> ErrorEvent<'a> {
+        self.record = Some(record);
+        self
+    }
+
+    /// Sets the number of identical errors which were suppressed before this
+    /// one.
+    pub fn suppressed(mut self, suppressed: u64) -> ErrorEvent<'a> {
+        self.suppressed = suppressed;
+        self
+    }
+
+    /// Returns the operation which produced the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the error itself.
+    pub fn error(&self) -> &'a anyhow::Error {
+        self.error
+    }
+
+    /// Returns the name of the appender which produced the error, if any.
+    pub fn appender_name(&self) -> Option<&'a str> {
+        self.appender
+    }
+
+    /// Returns the record which was being logged when the error occurred, if
+    /// any.
+    ///
+    /// The record's level, target, module path, file and line identify the
+    /// logging call site.
+    pub fn log_record(&self) -> Option<&'a Record<'a>> {
+        self.record
+    }
+
+    /// Returns the number of identical errors which were suppressed by rate
+    /// limiting since this error was last reported.
+    pub fn suppressed_count(&self) -> u64 {
+        self.suppressed
+    }
+
+    /// Returns the kind of the underlying I/O error, if the error was caused
+    /// by one.
+    pub fn io_error_kind(&self) -> Option<io::ErrorKind> {
+        self.error
+            .chain()
+            .find_map(|e| e.downcast_ref::<io::Error>())
+            .map(io::Error::kind)
+    }
+}
+
+impl fmt::Display for ErrorEvent<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(appender) = self.appender {
+            write!(fmt, "appender `{}`: ", appender)?;
+        }
+        write!(fmt, "{}", self.error)?;
+        if self.suppressed > 0 {
+            write!(fmt, " ({} similar errors suppressed)", self.suppressed)?;
+        }
+        Ok(())
+    }
+}
+
+/// A trait implemented by log4rs error handlers.
+///
+/// It is implemented for all `Fn(&ErrorEvent)` closures.
+pub trait ErrorHandler: Send + Sync + 'static {
+    /// Handles an error.
+    fn handle(&self, event: &ErrorEvent);
+}
+
+impl<F> ErrorHandler for F
+where
+    F: Fn(&ErrorEvent) + Send + Sync + 'static,
+{
+    fn handle(&self, event: &ErrorEvent) {
+        self(event)
+    }
+}
+
+/// An error handler which writes events to stderr.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct StderrErrorHandler;
+
+impl ErrorHandler for StderrErrorHandler {
+    fn handle(&self, event: &ErrorEvent) {
+        let _ = writeln!(io::stderr(), "log4rs: {}", event);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Key {
+    kind: ErrorKind,
+    appender: Option<String>,
+    message: String,
+}
+
+#[derive(Debug)]
+struct Window {
+    start: Instant,
+    reported: u32,
+    suppressed: u64,
+}
+
+#[derive(Debug)]
+struct Windows {
+    windows: HashMap<Key, Window>,
+    swept: Instant,
+}
+
+impl Windows {
+    /// Removes the windows other than `current`'s which have expired,
+    /// returning the keys of those with suppressed events and their counts.
+    fn sweep(&mut self, now: Instant, interval: Duration, current: &Key) -> Vec<(Key, u64)> {
+        self.swept = now;
+        let mut expired = vec![];
+        self.windows.retain(|key, window| {
+            if key == current || now - window.start < interval {
+                return true;
+            }
+            if window.suppressed > 0 {
+                expired.push((key.clone(), window.suppressed));
+            }
+            false
+        });
+        expired
+    }
+}
+
+/// An error handler which limits the rate of events passed to another one.
+///
+/// Events are considered identical if they have the same kind, appender and
+/// error message. At most `burst` identical events are passed along per
+/// `interval`; the rest are counted. The count is attached to the next
+/// identical event passed along once the interval has elapsed. If identical
+/// events stop occurring, the count is passed along with their last message
+/// once another event is handled after the interval has elapsed, or when the
+/// handler is dropped. Counts are not reported while no events at all occur.
+#[derive(Debug)]
+pub struct RateLimitedErrorHandler<H: ErrorHandler> {
+    inner: H,
+    interval: Duration,
+    burst: u32,
+    windows: Mutex<Windows>,
+}
+
+impl<H: ErrorHandler> RateLimitedErrorHandler<H> {
+    /// Creates a new `RateLimitedErrorHandler` which passes at most 5
+    /// identical events every 60 seconds to `inner`.
+    pub fn new(inner: H) -> RateLimitedErrorHandler<H> {
+        RateLimitedErrorHandler {
+            inner,
+            interval: Duration::from_secs(60),
+            burst: 5,
+            windows: Mutex::new(Windows {
+                windows: HashMap::new(),
+                swept: Instant::now(),
+            }),
+        }
+    }
+
+    fn report_suppressed(&self, suppressed: Vec<(Key, u64)>) {
+        for (key, count) in suppressed {
+            let error = anyhow::anyhow!("{}", key.message);
+            let mut event = ErrorEvent::new(key.kind, &error).suppressed(count);
+            if let Some(appender) = &key.appender {
+                event = event.appender(appender);
+            }
+            self.inner.handle(&event);
+        }
+    }
+
+    /// Sets the length of the rate limiting interval.
+    pub fn interval(mut self, interval: Duration) -> RateLimitedErrorHandler<H> {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the number of identical events passed along per interval.
+    pub fn burst(mut self, burst: u32) -> RateLimitedErrorHandler<H> {
+        self.burst = burst;
+        self
+    }
+}
+
+impl<H: ErrorHandler> ErrorHandler for RateLimitedErrorHandler<H> {
+    fn handle(&self, event: &ErrorEvent) {
+        let now = Instant::now();
+        let key = Key {
+            kind: event.kind,
+            appender: event.appender.map(ToOwned::to_owned),
+            message: event.error.to_string(),
+        };
+
+        let (suppressed, expired) = {
+            let mut windows = self.windows.lock().unwrap();
+            let interval = self.interval;
+            // Report the counts of errors which stopped occurring, which also
+            // keeps the map from growing without bound.
+            let expired = if now - windows.swept >= interval {
+                windows.sweep(now, interval, &key)
+            } else {
+                vec![]
+            };
+
+            let window = windows.windows.entry(key).or_insert(Window {
+                start: now,
+                reported: 0,
+                suppressed: 0,
+            });
+            let mut carried = 0;
+            if now - window.start >= interval {
+                carried = window.suppressed;
+                *window = Window {
+                    start: now,
+                    reported: 0,
+                    suppressed: 0,
+                };
+            }
+
+            if window.reported >= self.burst {
+                window.suppressed += 1;
+                (None, expired)
+            } else {
+                window.reported += 1;
+                (Some(carried), expired)
+            }
+        };
+
+        self.report_suppressed(expired);
+        if let Some(suppressed) = suppressed {
+            self.inner.handle(&ErrorEvent {
+                suppressed: event.suppressed + suppressed,
+                ..*event
+            });
+        }
+    }
+}
+
+impl<H: ErrorHandler> Drop for RateLimitedErrorHandler<H> {
+    fn drop(&mut self) {
+        let windows = self.windows.get_mut().unwrap_or_else(|e| e.into_inner());
+        let suppressed = windows
+            .windows
+            .drain()
+            .filter(|(_, w)| w.suppressed > 0)
+            .map(|(k, w)| (k, w.suppressed))
+            .collect();
+        self.report_suppressed(suppressed);
+    }
+}
+
+pub(crate) fn default_error_handler() -> Box<dyn ErrorHandler> {
+    Box::new(RateLimitedErrorHandler::new(StderrErrorHandler))
+}
+
+#[derive(Clone)]
+struct Scope {
+    handler: Arc<dyn ErrorHandler>,
+    appender: Arc<str>,
+}
+
+thread_local! {
+    static SCOPE: RefCell<Option<Scope>> = const { RefCell::new(None) };
+}
+
+/// Reports errors which occur outside of a logging call, for example in a
+/// hook run on a background thread, to the error handler of the logger
+/// whose appender caused them.
+///
+/// Loggers make their error handler current while they call into an
+/// appender, so components capture it with `BackgroundReporter::current`
+/// before handing work off to another thread. Errors reported outside of any
+/// logger go to stderr.
+// Only used by components behind optional features.
+#[allow(dead_code)]
+#[derive(Clone, Default)]
+pub(crate) struct BackgroundReporter(Option<Scope>);
+
+#[allow(dead_code)]
+impl BackgroundReporter {
+    /// Creates a reporter which passes errors of the appender named
+    /// `appender` to `handler`.
+    pub(crate) fn new(handler: Arc<dyn ErrorHandler>, appender: Arc<str>) -> BackgroundReporter {
+        BackgroundReporter(Some(Scope { handler, appender }))
+    }
+
+    /// Returns the reporter of the logging call the current thread is in.
+    pub(crate) fn current() -> BackgroundReporter {
+        BackgroundReporter(SCOPE.with(|scope| scope.borrow().clone()))
+    }
+
+    /// Runs `f` with this reporter as the current one.
+    pub(crate) fn enter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Restore(Option<Scope>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                SCOPE.with(|scope| *scope.borrow_mut() = previous);
+            }
+        }
+
+        let _restore = Restore(SCOPE.with(|scope| scope.replace(self.0.clone())));
+        f()
+    }
+
+    /// Reports an error, attributing it to the reporter's appender unless the
+    /// event names one.
+    pub(crate) fn report(&self, event: &ErrorEvent) {
+        match &self.0 {
+            Some(scope) => scope.handler.handle(&ErrorEvent {
+                appender: event.appender.or(Some(&scope.appender)),
+                ..*event
+            }),
+            None => StderrErrorHandler.handle(event),
+        }
+    }
+}
+
+impl fmt::Debug for BackgroundReporter {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_tuple("BackgroundReporter")
+            .field(&self.0.as_ref().map(|scope| &scope.appender))
+            .finish()
+    }
+}
+
+/// Reports an error which occurred outside of a logging call to the current
+/// thread's `BackgroundReporter`.
+// Only used by components behind optional features.
+#[allow(dead_code)]
+pub(crate) fn report_background(event: &ErrorEvent) {
+    BackgroundReporter::current().report(event)
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::anyhow;
+    use mock_instant::MockClock;
+
+    use super::*;
+
+    fn collector() -> (impl ErrorHandler, Arc<Mutex<Vec<String>>>) {
+        let events = Arc::new(Mutex::new(vec![]));
+        let sink = events.clone();
+        let handler = move |e: &ErrorEvent| sink.lock().unwrap().push(e.to_string());
+        (handler, events)
+    }
+
+    #[test]
+    fn event_details() {
+        let error = anyhow::Error::new(io::Error::new(io::ErrorKind::Other, "disk full"))
+            .context("error writing record");
+        let record = Record::builder().target("app::db").line(Some(7)).build();
+        let event = ErrorEvent::new(ErrorKind::Append, &error)
+            .appender("file")
+            .record(&record);
+
+        assert_eq!(event.kind(), ErrorKind::Append);
+        assert_eq!(event.appender_name(), Some("file"));
+        assert_eq!(event.log_record().unwrap().target(), "app::db");
+        assert_eq!(event.io_error_kind(), Some(io::ErrorKind::Other));
+        assert_eq!(event.to_string(), "appender `file`: error writing record");
+    }
+
+    #[test]
+    fn rate_limited() {
+        let (inner, events) = collector();
+        let handler = RateLimitedErrorHandler::new(inner)
+            .interval(Duration::from_secs(10))
+            .burst(2);
+        let full = anyhow!("disk full");
+        let other = anyhow!("permission denied");
+
+        for _ in 0..5 {
+            handler.handle(&ErrorEvent::new(ErrorKind::Append, &full).appender("file"));
+        }
+        handler.handle(&ErrorEvent::new(ErrorKind::Append, &other).appender("file"));
+        handler.handle(&ErrorEvent::new(ErrorKind::Append, &full).appender("other"));
+
+        MockClock::advance(Duration::from_secs(10));
+        handler.handle(&ErrorEvent::new(ErrorKind::Append, &full).appender("file"));
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "appender `file`: disk full",
+                "appender `file`: disk full",
+                "appender `file`: permission denied",
+                "appender `other`: disk full",
+                "appender `file`: disk full (3 similar errors suppressed)",
+            ]
+        );
+    }
+
+    #[test]
+    fn rate_limited_pending_counts() {
+        let (inner, events) = collector();
+        let handler = RateLimitedErrorHandler::new(inner)
+            .interval(Duration::from_secs(10))
+            .burst(1);
+        let full = anyhow!("disk full");
+        let other = anyhow!("permission denied");
+        let gone = anyhow!("file removed");
+
+        for _ in 0..3 {
+            handler.handle(&ErrorEvent::new(ErrorKind::Append, &full).appender("file"));
+        }
+        for _ in 0..2 {
+            handler.handle(&ErrorEvent::new(ErrorKind::Reopen, &gone));
+        }
+
+        MockClock::advance(Duration::from_secs(10));
+        handler.handle(&ErrorEvent::new(ErrorKind::Append, &other).appender("file"));
+        handler.handle(&ErrorEvent::new(ErrorKind::Append, &other).appender("file"));
+        drop(handler);
+
+        let mut events = events.lock().unwrap().clone();
+        events[2..4].sort();
+        assert_eq!(
+            events,
+            [
+                "appender `file`: disk full",
+                "file removed",
+                "appender `file`: disk full (2 similar errors suppressed)",
+                "file removed (1 similar errors suppressed)",
+                "appender `file`: permission denied",
+                "appender `file`: permission denied (1 similar errors suppressed)",
+            ]
+        );
+    }
+}
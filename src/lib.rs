@@ -19,10 +19,17 @@
 //!       - Rollers
 //!         - [delete](append/rolling_file/policy/compound/roll/delete/struct.DeleteRollerDeserializer.html#configuration): requires the `delete_roller` feature
 //!         - [fixed_window](append/rolling_file/policy/compound/roll/fixed_window/struct.FixedWindowRollerDeserializer.html#configuration): requires the `fixed_window_roller` feature
+//!         - [time_based](append/rolling_file/policy/compound/roll/time_based/struct.TimeBasedRollerDeserializer.html#configuration): requires the `time_based_roller` feature
 //!       - Triggers
 //!         - [size](append/rolling_file/policy/compound/trigger/size/struct.SizeTriggerDeserializer.html#configuration): requires the `size_trigger` feature
 //!         - [time](append/rolling_file/policy/compound/trigger/tine/struct.TimeTriggerDeserializer.html#configuration): requires the `time_trigger` feature
 //!         - [onstartup](append/rolling_file/policy/compound/trigger/tine/struct.OnStartUpTriggerDeserializer.html#configuration): requires the `onstartup_trigger` feature
+//!         - [cron](append/rolling_file/policy/compound/trigger/cron/struct.CronTriggerDeserializer.html#configuration): requires the `cron_trigger` feature
+//!         - [any](append/rolling_file/policy/compound/trigger/composite/struct.AnyTriggerDeserializer.html#configuration): requires the `composite_trigger` feature
+//!         - [all](append/rolling_file/policy/compound/trigger/composite/struct.AllTriggerDeserializer.html#configuration): requires the `composite_trigger` feature
+//!   - [failover](append/failover/struct.FailoverAppenderDeserializer.html#configuration): requires the `failover_appender` feature.
+//!   - [routing](append/routing/struct.RoutingAppenderDeserializer.html#configuration): requires the `routing_appender` feature.
+//!   - [capture](append/capture/index.html): requires the `capture_appender` feature. Stores records in memory for tests, and can only be created programmatically.
 //!
 //! ## Encoders
 //!
@@ -68,6 +75,18 @@
 //! The "root" logger is the ancestor of all other loggers. Since it has no
 //! ancestors, its additivity cannot be configured.
 //!
+//! ## Errors
+//!
+//! Errors which can't be returned to the caller, such as an appender failing
+//! to write a record, are passed to the logger's error handler. The default
+//! handler writes them to stderr, rate limited. See the [error](error/index.html)
+//! module for writing a custom one.
+//!
+//! ## Statistics
+//!
+//! The logger and its appenders count the records they process. A snapshot
+//! is available from `Handle::stats`; see the [stats](stats/index.html) module.
+//!
 //! # Configuration
 //!
 //! For a detailed breakdown on configuration, refer to the
@@ -182,6 +201,7 @@
 
 use std::{
     cmp, collections::HashMap, fmt, hash::BuildHasherDefault, io, io::prelude::*, sync::Arc,
+    time::Instant,
 };
 
 use arc_swap::ArcSwap;
@@ -191,16 +211,25 @@ use log::{Level, LevelFilter, Metadata, Record};
 pub mod append;
 pub mod config;
 pub mod encode;
+pub mod error;
 pub mod filter;
 #[cfg(feature = "console_writer")]
 mod priv_io;
+#[cfg(all(unix, feature = "signals"))]
+mod signal;
+pub mod stats;
 
 pub use config::{init_config, Config};
 
 #[cfg(feature = "config_parsing")]
 pub use config::{init_file, init_raw_config};
 
-use self::{append::Append, filter::Filter};
+use self::{
+    append::Append,
+    error::{BackgroundReporter, ErrorEvent, ErrorHandler, ErrorKind},
+    filter::Filter,
+    stats::{AppenderCounters, LoggerCounters, Stats},
+};
 
 type FnvHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FnvHasher>>;
 
@@ -271,14 +300,22 @@ impl ConfiguredLogger {
         self.level >= level
     }
 
-    fn log(&self, record: &log::Record, appenders: &[Appender]) -> Result<(), Vec<anyhow::Error>> {
+    fn log(
+        &self,
+        record: &log::Record,
+        appenders: &[Appender],
+        counters: &LoggerCounters,
+    ) -> Result<(), Vec<(usize, anyhow::Error)>> {
         let mut errors = vec![];
         if self.enabled(record.level()) {
+            counters.accepted.incr(record.level());
             for &idx in &self.appenders {
                 if let Err(err) = appenders[idx].append(record) {
-                    errors.push(err);
+                    errors.push((idx, err));
                 }
             }
+        } else {
+            counters.rejected.incr(record.level());
         }
 
         if errors.is_empty() {
@@ -291,8 +328,11 @@ impl ConfiguredLogger {
 
 #[derive(Debug)]
 struct Appender {
+    name: String,
     appender: Box<dyn Append>,
     filters: Vec<Box<dyn Filter>>,
+    counters: AppenderCounters,
+    reporter: BackgroundReporter,
 }
 
 impl Appender {
@@ -301,11 +341,18 @@ impl Appender {
             match filter.filter(record) {
                 filter::Response::Accept => break,
                 filter::Response::Neutral => {}
-                filter::Response::Reject => return Ok(()),
+                filter::Response::Reject => {
+                    self.counters.rejected.incr(record.level());
+                    return Ok(());
+                }
             }
         }
 
-        self.appender.append(record)
+        self.counters.accepted.incr(record.level());
+        let start = Instant::now();
+        let result = self.reporter.enter(|| self.appender.append(record));
+        self.counters.record_append(start.elapsed(), result.is_ok());
+        result
     }
 
     fn flush(&self) {
@@ -316,7 +363,8 @@ impl Appender {
 struct SharedLogger {
     root: ConfiguredLogger,
     appenders: Vec<Appender>,
-    err_handler: Box<dyn Send + Sync + Fn(&anyhow::Error)>,
+    err_handler: Arc<dyn ErrorHandler>,
+    counters: Arc<LoggerCounters>,
 }
 
 impl fmt::Debug for SharedLogger {
@@ -329,17 +377,10 @@ impl fmt::Debug for SharedLogger {
 }
 
 impl SharedLogger {
-    fn new(config: config::Config) -> SharedLogger {
-        Self::new_with_err_handler(
-            config,
-            Box::new(|e: &anyhow::Error| {
-                let _ = writeln!(io::stderr(), "log4rs: {}", e);
-            }),
-        )
-    }
-    fn new_with_err_handler(
+    fn new(
         config: config::Config,
-        err_handler: Box<dyn Send + Sync + Fn(&anyhow::Error)>,
+        err_handler: Arc<dyn ErrorHandler>,
+        counters: Arc<LoggerCounters>,
     ) -> SharedLogger {
         let (appenders, root, mut loggers) = config.unpack();
 
@@ -377,8 +418,15 @@ impl SharedLogger {
         let appenders = appenders
             .into_iter()
             .map(|appender| {
-                let (_, appender, filters) = appender.unpack();
-                Appender { appender, filters }
+                let (name, appender, filters) = appender.unpack();
+                let reporter = BackgroundReporter::new(err_handler.clone(), Arc::from(&*name));
+                Appender {
+                    name,
+                    appender,
+                    filters,
+                    counters: AppenderCounters::default(),
+                    reporter,
+                }
             })
             .collect();
 
@@ -386,6 +434,7 @@ impl SharedLogger {
             root,
             appenders,
             err_handler,
+            counters,
         }
     }
 }
@@ -398,16 +447,43 @@ pub struct Logger(Arc<ArcSwap<SharedLogger>>);
 impl Logger {
     /// Create a new `Logger` given a configuration.
     pub fn new(config: config::Config) -> Logger {
-        Logger(Arc::new(ArcSwap::new(Arc::new(SharedLogger::new(config)))))
+        Self::with_error_handler(config, Arc::from(error::default_error_handler()))
     }
     /// Create a new `Logger` given a configuration and err handler.
+    #[deprecated(
+        note = "use `new_with_error_handler`, whose handler also receives the failing appender and record"
+    )]
     pub fn new_with_err_handler(
         config: config::Config,
         err_handler: Box<dyn Send + Sync + Fn(&anyhow::Error)>,
     ) -> Logger {
-        Logger(Arc::new(ArcSwap::new(Arc::new(
-            SharedLogger::new_with_err_handler(config, err_handler),
-        ))))
+        Self::new_with_error_handler(config, move |e: &ErrorEvent| err_handler(e.error()))
+    }
+
+    /// Create a new `Logger` given a configuration and an `ErrorHandler`.
+    ///
+    /// Unlike the handler passed to the deprecated `new_with_err_handler`, an
+    /// `ErrorHandler` receives the name of the failing appender and the
+    /// record being logged.
+    pub fn new_with_error_handler<H>(config: config::Config, err_handler: H) -> Logger
+    where
+        H: ErrorHandler,
+    {
+        Self::with_error_handler(config, Arc::new(err_handler))
+    }
+
+    pub(crate) fn with_error_handler(
+        config: config::Config,
+        err_handler: Arc<dyn ErrorHandler>,
+    ) -> Logger {
+        #[cfg(feature = "pattern_encoder")]
+        encode::pattern::mark_start();
+
+        Logger(Arc::new(ArcSwap::new(Arc::new(SharedLogger::new(
+            config,
+            err_handler,
+            Arc::default(),
+        )))))
     }
 
     /// Set the max log level above which everything will be filtered.
@@ -433,13 +509,17 @@ impl log::Log for Logger {
 
     fn log(&self, record: &log::Record) {
         let shared = self.0.load();
-        if let Err(errs) = shared
-            .root
-            .find(record.target())
-            .log(record, &shared.appenders)
+        if let Err(errs) =
+            shared
+                .root
+                .find(record.target())
+                .log(record, &shared.appenders, &shared.counters)
         {
-            for e in errs {
-                (shared.err_handler)(&e)
+            for (idx, e) in errs {
+                let event = ErrorEvent::new(ErrorKind::Append, &e)
+                    .appender(&shared.appenders[idx].name)
+                    .record(record);
+                shared.err_handler.handle(&event);
             }
         }
     }
@@ -463,8 +543,11 @@ pub struct Handle {
 
 impl Handle {
     /// Sets the logging configuration.
+    ///
+    /// The logger's error handler and counters are kept.
     pub fn set_config(&self, config: Config) {
-        let shared = SharedLogger::new(config);
+        let old = self.shared.load();
+        let shared = SharedLogger::new(config, old.err_handler.clone(), old.counters.clone());
         log::set_max_level(shared.root.max_log_level());
         self.shared.store(Arc::new(shared));
     }
@@ -473,11 +556,120 @@ impl Handle {
     pub fn max_log_level(&self) -> LevelFilter {
         self.shared.load().root.max_log_level()
     }
+
+    /// Returns a snapshot of the logger's counters.
+    pub fn stats(&self) -> Stats {
+        let shared = self.shared.load();
+        let appenders = shared
+            .appenders
+            .iter()
+            .map(|a| a.counters.snapshot(&a.name, a.appender.stats()))
+            .collect();
+        shared.counters.snapshot(appenders)
+    }
+
+    /// Rolls the log file of the appender named `appender` over.
+    ///
+    /// The roll happens exactly as it would if the appender's policy had
+    /// triggered it: the appender stops writing, closes its file, and passes
+    /// it to its roller. Returns an error if there is no appender with that
+    /// name, if the appender can't roll (only rolling file appenders can,
+    /// and failover and routing appenders wrapping them), or if rolling
+    /// fails.
+    pub fn roll(&self, appender: &str) -> anyhow::Result<()> {
+        let shared = self.shared.load();
+        match shared.appenders.iter().find(|a| a.name == appender) {
+            Some(a) => a.reporter.enter(|| a.appender.roll()),
+            None => anyhow::bail!("no appender named `{}`", appender),
+        }
+    }
+
+    /// Rolls the log file of the appender named `appender` over each time
+    /// the process receives `signal`, for example `libc::SIGUSR1`.
+    ///
+    /// The rolls happen on a background thread, which looks the appender up
+    /// in the configuration current at the time of the signal. Failed rolls
+    /// are reported to the logger's error handler.
+    ///
+    /// Requires the `signals` feature, and is only available on Unix.
+    #[cfg(all(unix, feature = "signals"))]
+    pub fn roll_on_signal(&self, signal: std::os::raw::c_int, appender: &str) -> io::Result<()> {
+        let appender = appender.to_owned();
+        signal::on_signal(signal, self.clone(), move |handle| {
+            if let Err(e) = handle.roll(&appender) {
+                handle
+                    .shared
+                    .load()
+                    .err_handler
+                    .handle(&ErrorEvent::new(ErrorKind::Roll, &e).appender(&appender));
+            }
+        })
+    }
+
+    /// Closes and reopens the log files of all appenders, for example after
+    /// `logrotate` renamed them.
+    ///
+    /// Every appender is reopened even if some fail. The failures are
+    /// returned as a single error, and, as with `roll`, not reported to the
+    /// logger's error handler.
+    pub fn reopen_files(&self) -> anyhow::Result<()> {
+        let failures = self
+            .reopen_each()
+            .into_iter()
+            .map(|(name, e)| format!("appender `{}`: {}", name, e))
+            .collect::<Vec<_>>();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("{}", failures.join("; ")))
+        }
+    }
+
+    /// Reopens every appender, returning the names of those which failed
+    /// with their errors.
+    fn reopen_each(&self) -> Vec<(String, anyhow::Error)> {
+        let shared = self.shared.load();
+        shared
+            .appenders
+            .iter()
+            .filter_map(|appender| {
+                let e = appender
+                    .reporter
+                    .enter(|| appender.appender.reopen())
+                    .err()?;
+                Some((appender.name.clone(), e))
+            })
+            .collect()
+    }
+
+    /// Reopens the log files of all appenders each time the process receives
+    /// `signal`, conventionally `libc::SIGHUP` or `libc::SIGUSR1`.
+    ///
+    /// The files are reopened on a background thread as by `reopen_files`,
+    /// and each failure is reported to the logger's error handler.
+    ///
+    /// Requires the `signals` feature, and is only available on Unix.
+    #[cfg(all(unix, feature = "signals"))]
+    pub fn reopen_on_signal(&self, signal: std::os::raw::c_int) -> io::Result<()> {
+        signal::on_signal(signal, self.clone(), |handle| {
+            let err_handler = handle.shared.load().err_handler.clone();
+            for (name, e) in handle.reopen_each() {
+                err_handler.handle(&ErrorEvent::new(ErrorKind::Reopen, &e).appender(&name));
+            }
+        })
+    }
+
+    /// Returns the logger's error handler.
+    #[cfg(feature = "config_parsing")]
+    pub(crate) fn error_handler(&self) -> Arc<dyn ErrorHandler> {
+        self.shared.load().err_handler.clone()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use log::{Level, LevelFilter, Log};
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     use super::*;
 
@@ -588,4 +780,318 @@ mod test {
                 .build()
         ));
     }
+
+    #[test]
+    fn error_handler_receives_appender_and_record() {
+        use std::sync::Mutex;
+
+        #[derive(Debug)]
+        struct Broken;
+
+        impl Append for Broken {
+            fn append(&self, _: &Record) -> anyhow::Result<()> {
+                Err(anyhow::anyhow!("broken"))
+            }
+
+            fn flush(&self) {}
+        }
+
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("broken", Box::new(Broken)))
+            .build(
+                config::Root::builder()
+                    .appender("broken")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let sink = events.clone();
+        let logger = super::Logger::new_with_error_handler(config, move |e: &ErrorEvent| {
+            let record = e.log_record().unwrap();
+            sink.lock().unwrap().push((
+                e.kind(),
+                e.appender_name().unwrap().to_owned(),
+                record.target().to_owned(),
+                e.to_string(),
+            ));
+        });
+
+        logger.log(&Record::builder().target("app::db").build());
+        assert_eq!(
+            *events.lock().unwrap(),
+            [(
+                ErrorKind::Append,
+                "broken".to_owned(),
+                "app::db".to_owned(),
+                "appender `broken`: broken".to_owned()
+            )]
+        );
+
+        // The error handler is kept across reconfiguration.
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("other", Box::new(Broken)))
+            .build(
+                config::Root::builder()
+                    .appender("other")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        logger.handle().set_config(config);
+        logger.log(&Record::builder().build());
+        assert_eq!(events.lock().unwrap()[1].1, "other");
+    }
+
+    #[test]
+    fn background_errors_reach_own_logger() {
+        use std::sync::Mutex;
+
+        #[derive(Debug)]
+        struct Deferred;
+
+        impl Append for Deferred {
+            fn append(&self, _: &Record) -> anyhow::Result<()> {
+                let reporter = BackgroundReporter::current();
+                std::thread::spawn(move || {
+                    let e = anyhow::anyhow!("deferred");
+                    reporter.report(&ErrorEvent::new(ErrorKind::PostRoll, &e));
+                })
+                .join()
+                .unwrap();
+                Ok(())
+            }
+
+            fn flush(&self) {}
+        }
+
+        let logger = |name: &str| {
+            let config = config::Config::builder()
+                .appender(config::Appender::builder().build(name, Box::new(Deferred)))
+                .build(
+                    config::Root::builder()
+                        .appender(name)
+                        .build(LevelFilter::Info),
+                )
+                .unwrap();
+            let events = Arc::new(Mutex::new(vec![]));
+            let sink = events.clone();
+            let logger = super::Logger::new_with_error_handler(config, move |e: &ErrorEvent| {
+                sink.lock().unwrap().push(e.to_string())
+            });
+            (logger, events)
+        };
+        let (first, first_events) = logger("first");
+        let (second, second_events) = logger("second");
+
+        first.log(&Record::builder().build());
+        second.log(&Record::builder().build());
+        assert_eq!(
+            *first_events.lock().unwrap(),
+            ["appender `first`: deferred"]
+        );
+        assert_eq!(
+            *second_events.lock().unwrap(),
+            ["appender `second`: deferred"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "threshold_filter")]
+    fn stats() {
+        #[derive(Debug)]
+        struct Nop;
+
+        impl Append for Nop {
+            fn append(&self, _: &Record) -> anyhow::Result<()> {
+                Ok(())
+            }
+
+            fn flush(&self) {}
+        }
+
+        let config = config::Config::builder()
+            .appender(
+                config::Appender::builder()
+                    .filter(Box::new(filter::threshold::ThresholdFilter::new(
+                        LevelFilter::Warn,
+                    )))
+                    .build("nop", Box::new(Nop)),
+            )
+            .build(
+                config::Root::builder()
+                    .appender("nop")
+                    .build(LevelFilter::Info),
+            )
+            .unwrap();
+        let logger = super::Logger::new(config);
+        let handle = logger.handle();
+
+        for level in [Level::Error, Level::Info, Level::Info, Level::Debug] {
+            logger.log(&Record::builder().level(level).build());
+        }
+
+        let stats = handle.stats();
+        assert_eq!(stats.accepted().get(Level::Info), 2);
+        assert_eq!(stats.accepted().total(), 3);
+        assert_eq!(stats.rejected().get(Level::Debug), 1);
+
+        let nop = stats.appender("nop").unwrap();
+        assert_eq!(nop.accepted().get(Level::Error), 1);
+        assert_eq!(nop.rejected().get(Level::Info), 2);
+        assert_eq!(nop.errors(), 0);
+        assert_eq!(nop.bytes_written(), None);
+    }
+
+    #[derive(Debug, Default)]
+    struct Rollable {
+        rolls: Arc<AtomicUsize>,
+        reopens: Arc<AtomicUsize>,
+    }
+
+    impl Append for Rollable {
+        fn append(&self, _: &Record) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn flush(&self) {}
+
+        fn roll(&self) -> anyhow::Result<()> {
+            self.rolls.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn reopen(&self) -> anyhow::Result<()> {
+            self.reopens.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct Plain;
+
+    impl Append for Plain {
+        fn append(&self, _: &Record) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn rollable_logger() -> (super::Logger, Arc<AtomicUsize>) {
+        let appender = Rollable::default();
+        let rolls = appender.rolls.clone();
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("rollable", Box::new(appender)))
+            .appender(config::Appender::builder().build("plain", Box::new(Plain)))
+            .build(config::Root::builder().build(LevelFilter::Info))
+            .unwrap();
+        (super::Logger::new(config), rolls)
+    }
+
+    #[test]
+    fn roll() {
+        let (logger, rolls) = rollable_logger();
+        let handle = logger.handle();
+
+        handle.roll("rollable").unwrap();
+        assert_eq!(rolls.load(Ordering::SeqCst), 1);
+
+        let err = handle.roll("missing").unwrap_err();
+        assert_eq!(err.to_string(), "no appender named `missing`");
+        assert!(handle.roll("plain").is_err());
+    }
+
+    #[derive(Debug)]
+    struct Stuck;
+
+    impl Append for Stuck {
+        fn append(&self, _: &Record) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn flush(&self) {}
+
+        fn reopen(&self) -> anyhow::Result<()> {
+            Err(anyhow::anyhow!("stuck"))
+        }
+    }
+
+    #[test]
+    fn reopen_files() {
+        use std::sync::Mutex;
+
+        let rollable = Rollable::default();
+        let reopens = rollable.reopens.clone();
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("stuck", Box::new(Stuck)))
+            .appender(config::Appender::builder().build("rollable", Box::new(rollable)))
+            .appender(config::Appender::builder().build("plain", Box::new(Plain)))
+            .build(config::Root::builder().build(LevelFilter::Info))
+            .unwrap();
+        let events = Arc::new(Mutex::new(vec![]));
+        let sink = events.clone();
+        let logger = super::Logger::new_with_error_handler(config, move |e: &ErrorEvent| {
+            sink.lock().unwrap().push((e.kind(), e.to_string()));
+        });
+
+        let err = logger.handle().reopen_files().unwrap_err();
+        assert_eq!(err.to_string(), "appender `stuck`: stuck");
+        assert_eq!(reopens.load(Ordering::SeqCst), 1);
+        // The caller reports the error, as with `roll`.
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "signals"))]
+    fn roll_on_signal() {
+        use signal_hook::{
+            consts::{SIGKILL, SIGUSR2},
+            low_level::raise,
+        };
+        use std::{thread, time::Duration};
+
+        let (logger, rolls) = rollable_logger();
+        let handle = logger.handle();
+        handle.roll_on_signal(SIGUSR2, "rollable").unwrap();
+        assert!(handle.roll_on_signal(SIGKILL, "rollable").is_err());
+
+        raise(SIGUSR2).unwrap();
+        for _ in 0..100 {
+            if rolls.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(rolls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "signals"))]
+    fn reopen_on_signal() {
+        use signal_hook::{consts::SIGUSR1, low_level::raise};
+        use std::{sync::Mutex, thread, time::Duration};
+
+        let config = config::Config::builder()
+            .appender(config::Appender::builder().build("stuck", Box::new(Stuck)))
+            .build(config::Root::builder().build(LevelFilter::Info))
+            .unwrap();
+        let events = Arc::new(Mutex::new(vec![]));
+        let sink = events.clone();
+        let logger = super::Logger::new_with_error_handler(config, move |e: &ErrorEvent| {
+            sink.lock().unwrap().push((e.kind(), e.to_string()));
+        });
+        logger.handle().reopen_on_signal(SIGUSR1).unwrap();
+
+        raise(SIGUSR1).unwrap();
+        for _ in 0..100 {
+            if !events.lock().unwrap().is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            *events.lock().unwrap(),
+            [(ErrorKind::Reopen, "appender `stuck`: stuck".to_owned())]
+        );
+    }
 }
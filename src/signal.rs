@@ -0,0 +1,34 @@
+//! Acting on Unix signals.
+//!
+//! Requires the `signals` feature.
+
+use std::{io, os::raw::c_int, thread};
+
+use signal_hook::{consts::FORBIDDEN, iterator::Signals};
+
+use crate::Handle;
+
+/// Spawns a thread which calls `f` each time the process receives `signal`.
+///
+/// Signals which arrive while `f` runs are coalesced into a single call.
+pub(crate) fn on_signal<F>(signal: c_int, handle: Handle, f: F) -> io::Result<()>
+where
+    F: Fn(&Handle) + Send + 'static,
+{
+    if FORBIDDEN.contains(&signal) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("signal {} can't be handled", signal),
+        ));
+    }
+
+    let mut signals = Signals::new([signal])?;
+    thread::Builder::new()
+        .name(format!("log4rs signal {}", signal))
+        .spawn(move || {
+            for _ in signals.forever() {
+                f(&handle);
+            }
+        })?;
+    Ok(())
+}
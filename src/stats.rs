@@ -0,0 +1,350 @@
+//! Logging statistics.
+//!
+//! The logger counts the records it accepts and rejects, and each appender
+//! counts the records it accepts, rejects through its filters and fails to
+//! write, along with the time spent writing them. Appenders which write to
+//! files additionally report the number of bytes written and rolls performed.
+//!
+//! A snapshot of the counters is returned by `Handle::stats`, and can be
+//! rendered in the Prometheus text exposition format with
+//! `Stats::to_prometheus`.
+
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use log::Level;
+
+/// Counters maintained by an appender itself.
+///
+/// A counter is `None` if the appender does not track it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AppendStats {
+    /// The number of bytes written.
+    pub bytes_written: Option<u64>,
+    /// The number of times the log file was rolled.
+    pub rolls: Option<u64>,
+}
+
+/// Record counts broken down by level.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LevelCounts([u64; 5]);
+
+impl LevelCounts {
+    /// Returns the count for a level.
+    pub fn get(&self, level: Level) -> u64 {
+        self.0[level as usize - 1]
+    }
+
+    /// Returns the count for all levels.
+    pub fn total(&self) -> u64 {
+        self.0.iter().sum()
+    }
+}
+
+/// A snapshot of an appender's counters.
+#[derive(Clone, Debug)]
+pub struct AppenderStats {
+    name: String,
+    accepted: LevelCounts,
+    rejected: LevelCounts,
+    errors: u64,
+    append_time: Duration,
+    append: AppendStats,
+}
+
+impl AppenderStats {
+    /// Returns the name of the appender.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of records which passed the appender's filters.
+    pub fn accepted(&self) -> LevelCounts {
+        self.accepted
+    }
+
+    /// Returns the number of records rejected by the appender's filters.
+    pub fn rejected(&self) -> LevelCounts {
+        self.rejected
+    }
+
+    /// Returns the number of records the appender failed to write.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Returns the total time spent in the appender's `append` method.
+    pub fn append_time(&self) -> Duration {
+        self.append_time
+    }
+
+    /// Returns the number of bytes written, if the appender tracks it.
+    pub fn bytes_written(&self) -> Option<u64> {
+        self.append.bytes_written
+    }
+
+    /// Returns the number of rolls performed, if the appender tracks it.
+    pub fn rolls(&self) -> Option<u64> {
+        self.append.rolls
+    }
+}
+
+/// A snapshot of the logger's counters.
+///
+/// Logger counters accumulate for the lifetime of the logger. Appender
+/// counters start from zero whenever the configuration is replaced, since
+/// the appenders are recreated.
+#[derive(Clone, Debug)]
+pub struct Stats {
+    accepted: LevelCounts,
+    rejected: LevelCounts,
+    appenders: Vec<AppenderStats>,
+}
+
+impl Stats {
+    /// Returns the number of records accepted by the logger's level filters.
+    pub fn accepted(&self) -> LevelCounts {
+        self.accepted
+    }
+
+    /// Returns the number of records rejected by the logger's level filters.
+    pub fn rejected(&self) -> LevelCounts {
+        self.rejected
+    }
+
+    /// Returns the counters of each appender.
+    pub fn appenders(&self) -> &[AppenderStats] {
+        &self.appenders
+    }
+
+    /// Returns the counters of the named appender.
+    pub fn appender(&self, name: &str) -> Option<&AppenderStats> {
+        self.appenders.iter().find(|a| a.name == name)
+    }
+
+    /// Renders the counters in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        metric_header(
+            &mut out,
+            "log4rs_records_total",
+            "Records seen by the logger, by level and outcome.",
+        );
+        for (outcome, counts) in [("accepted", self.accepted), ("rejected", self.rejected)] {
+            for level in levels() {
+                let _ = writeln!(
+                    out,
+                    "log4rs_records_total{{level=\"{}\",outcome=\"{}\"}} {}",
+                    level.as_str().to_lowercase(),
+                    outcome,
+                    counts.get(level)
+                );
+            }
+        }
+
+        metric_header(
+            &mut out,
+            "log4rs_appender_records_total",
+            "Records seen by an appender, by level and outcome.",
+        );
+        for appender in &self.appenders {
+            for (outcome, counts) in [
+                ("accepted", appender.accepted),
+                ("rejected", appender.rejected),
+            ] {
+                for level in levels() {
+                    let _ = writeln!(
+                        out,
+                        "log4rs_appender_records_total{{appender=\"{}\",level=\"{}\",outcome=\"{}\"}} {}",
+                        escape_label(&appender.name),
+                        level.as_str().to_lowercase(),
+                        outcome,
+                        counts.get(level)
+                    );
+                }
+            }
+        }
+
+        self.appender_metric(
+            &mut out,
+            "log4rs_appender_errors_total",
+            "Records an appender failed to write.",
+            |a| Some(a.errors.to_string()),
+        );
+        self.appender_metric(
+            &mut out,
+            "log4rs_appender_append_seconds_total",
+            "Time spent writing records.",
+            |a| Some(a.append_time.as_secs_f64().to_string()),
+        );
+        self.appender_metric(
+            &mut out,
+            "log4rs_appender_bytes_written_total",
+            "Bytes written by an appender.",
+            |a| a.append.bytes_written.map(|v| v.to_string()),
+        );
+        self.appender_metric(
+            &mut out,
+            "log4rs_appender_rolls_total",
+            "Log file rolls performed by an appender.",
+            |a| a.append.rolls.map(|v| v.to_string()),
+        );
+
+        out
+    }
+
+    fn appender_metric<F>(&self, out: &mut String, name: &str, help: &str, value: F)
+    where
+        F: Fn(&AppenderStats) -> Option<String>,
+    {
+        let values = self
+            .appenders
+            .iter()
+            .filter_map(|a| value(a).map(|v| (a, v)))
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            return;
+        }
+
+        metric_header(out, name, help);
+        for (appender, value) in values {
+            let _ = writeln!(
+                out,
+                "{}{{appender=\"{}\"}} {}",
+                name,
+                escape_label(&appender.name),
+                value
+            );
+        }
+    }
+}
+
+fn levels() -> impl Iterator<Item = Level> {
+    IntoIterator::into_iter([
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ])
+}
+
+fn metric_header(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct LevelCounters([AtomicU64; 5]);
+
+impl LevelCounters {
+    pub(crate) fn incr(&self, level: Level) {
+        self.0[level as usize - 1].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LevelCounts {
+        let mut counts = [0; 5];
+        for (count, counter) in counts.iter_mut().zip(&self.0) {
+            *count = counter.load(Ordering::Relaxed);
+        }
+        LevelCounts(counts)
+    }
+}
+
+/// The logger's live counters.
+#[derive(Debug, Default)]
+pub(crate) struct LoggerCounters {
+    pub(crate) accepted: LevelCounters,
+    pub(crate) rejected: LevelCounters,
+}
+
+/// An appender's live counters.
+#[derive(Debug, Default)]
+pub(crate) struct AppenderCounters {
+    pub(crate) accepted: LevelCounters,
+    pub(crate) rejected: LevelCounters,
+    pub(crate) errors: AtomicU64,
+    pub(crate) append_nanos: AtomicU64,
+}
+
+impl AppenderCounters {
+    pub(crate) fn record_append(&self, elapsed: Duration, ok: bool) {
+        self.append_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        if !ok {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self, name: &str, append: AppendStats) -> AppenderStats {
+        AppenderStats {
+            name: name.to_owned(),
+            accepted: self.accepted.snapshot(),
+            rejected: self.rejected.snapshot(),
+            errors: self.errors.load(Ordering::Relaxed),
+            append_time: Duration::from_nanos(self.append_nanos.load(Ordering::Relaxed)),
+            append,
+        }
+    }
+}
+
+impl LoggerCounters {
+    pub(crate) fn snapshot(&self, appenders: Vec<AppenderStats>) -> Stats {
+        Stats {
+            accepted: self.accepted.snapshot(),
+            rejected: self.rejected.snapshot(),
+            appenders,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prometheus() {
+        let logger = LoggerCounters::default();
+        logger.accepted.incr(Level::Info);
+        logger.rejected.incr(Level::Trace);
+
+        let appender = AppenderCounters::default();
+        appender.accepted.incr(Level::Info);
+        appender.record_append(Duration::from_millis(500), false);
+        let append = AppendStats {
+            bytes_written: Some(42),
+            rolls: None,
+        };
+        let stats = logger.snapshot(vec![appender.snapshot("my \"file\"", append)]);
+
+        assert_eq!(stats.accepted().get(Level::Info), 1);
+        assert_eq!(stats.rejected().total(), 1);
+        assert_eq!(stats.appender("my \"file\"").unwrap().errors(), 1);
+
+        let text = stats.to_prometheus();
+        assert!(text.contains("# TYPE log4rs_records_total counter\n"));
+        assert!(text.contains("log4rs_records_total{level=\"info\",outcome=\"accepted\"} 1\n"));
+        assert!(text.contains("log4rs_records_total{level=\"trace\",outcome=\"rejected\"} 1\n"));
+        assert!(text.contains(
+            "log4rs_appender_records_total{appender=\"my \\\"file\\\"\",level=\"info\",outcome=\"accepted\"} 1\n"
+        ));
+        assert!(text.contains("log4rs_appender_errors_total{appender=\"my \\\"file\\\"\"} 1\n"));
+        assert!(text
+            .contains("log4rs_appender_append_seconds_total{appender=\"my \\\"file\\\"\"} 0.5\n"));
+        assert!(
+            text.contains("log4rs_appender_bytes_written_total{appender=\"my \\\"file\\\"\"} 42\n")
+        );
+        assert!(!text.contains("log4rs_appender_rolls_total"));
+    }
+}
@@ -0,0 +1,70 @@
+//! Tests for routing records to a test-scoped logger. They live in their own
+//! binary since they install the global logger.
+
+use std::thread;
+
+use log::Level;
+use log4rs::{append::capture, assert_logged, assert_not_logged};
+
+#[test]
+fn captures_current_thread() {
+    let logs = capture::capture_thread();
+    log::warn!(user = "bob", attempts = 3; "login failed");
+    log::trace!("tracing");
+
+    // Records logged on other threads are not captured.
+    thread::spawn(|| log::error!("elsewhere")).join().unwrap();
+
+    assert_logged!(logs, Level::Warn, "login");
+    assert_logged!(logs, Level::Trace, "tracing", "trace records are captured");
+    assert_not_logged!(logs, Level::Error, "elsewhere");
+
+    let records = logs.records();
+    assert_eq!(records[0].key_value("user"), Some("bob"));
+    assert_eq!(records[0].key_value("attempts"), Some("3"));
+
+    let handle = logs.handle();
+    drop(logs);
+    log::warn!("after");
+    assert_eq!(handle.len(), 2);
+}
+
+#[test]
+fn nested() {
+    let outer = capture::capture_thread();
+    {
+        let inner = capture::capture_thread();
+        log::info!("inner");
+        assert_logged!(inner, Level::Info, "inner");
+    }
+    log::info!("outer");
+    assert_eq!(outer.len(), 1);
+    assert_logged!(outer, Level::Info, "outer");
+}
+
+#[test]
+fn dropped_out_of_order() {
+    let outer = capture::capture_thread();
+    let inner = capture::capture_thread();
+    let (outer_logs, inner_logs) = (outer.handle(), inner.handle());
+
+    // Dropping the outer guard first ends both captures.
+    drop(outer);
+    log::info!("after outer");
+    drop(inner);
+    log::info!("after inner");
+    assert!(outer_logs.is_empty());
+    assert!(inner_logs.is_empty());
+
+    let logs = capture::capture_thread();
+    log::info!("captured");
+    assert_eq!(logs.len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "expected a WARN record containing \"missing\"")]
+fn assert_logged_fails() {
+    let logs = capture::capture_thread();
+    log::warn!("present");
+    assert_logged!(logs, Level::Warn, "missing");
+}